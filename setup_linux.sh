@@ -10,6 +10,7 @@ sudo apt-get install -y \
     libxkbcommon-dev \
     libegl1-mesa-dev \
     libdbus-1-dev \
+    libasound2-dev \
     libgtk-3-dev \
     build-essential \
     pkg-config \
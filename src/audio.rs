@@ -0,0 +1,523 @@
+//! Sound output for video blocks.
+//!
+//! Every stream that plays sound owns an `AudioSource`: its decode worker pushes
+//! resampled, interleaved stereo samples tagged with their position on the playback
+//! timeline, and the process-wide `Mixer` drains all sources into one sink. The sink is
+//! the default sound device, or a sink without hardware chosen with the
+//! `MABLOCKS_AUDIO_SINK` environment variable: `null` renders the mix in real time and
+//! discards it, `file:<path>` also records it to a WAV file. Tests always use the null
+//! sink.
+//!
+//! Each source reports which timeline position was heard at which instant; streams
+//! with sound time their frames from this audio clock instead of the wall clock.
+
+use crate::constants::{AUDIO_FALLBACK_RATE, AUDIO_SINK_PERIOD_MILLIS, AUDIO_SYNC_TOLERANCE_SECS};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::{Duration, Instant};
+
+/// Samples per frame: the mix is interleaved stereo.
+pub const CHANNELS: usize = 2;
+
+/// Environment variable selecting the sink.
+const SINK_ENV: &str = "MABLOCKS_AUDIO_SINK";
+
+/// Global mute, applied by the mixer so muted streams keep their clocks running.
+static MUTED: AtomicBool = AtomicBool::new(false);
+
+/// Mutes or unmutes all sound.
+pub fn set_muted(muted: bool) {
+    MUTED.store(muted, Ordering::Relaxed);
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Sources and mixing
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Samples of a source waiting to be played.
+#[derive(Default)]
+struct SourceBuffer {
+    samples: VecDeque<f32>,
+    /// Timeline position of the first queued frame.
+    front: f64,
+    /// Timeline position heard at an instant, from the latest render that played samples.
+    clock: Option<(f64, Instant)>,
+}
+
+/// The sound of one stream, fed by its decode worker and drained by the mixer.
+pub struct AudioSource {
+    rate: u32,
+    buffer: Mutex<SourceBuffer>,
+    /// Volume (0..=1) as f32 bits.
+    volume: AtomicU32,
+    paused: AtomicBool,
+}
+
+impl AudioSource {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            buffer: Mutex::new(SourceBuffer::default()),
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            paused: AtomicBool::new(false),
+        }
+    }
+
+    /// Queues interleaved stereo `samples` starting at `timeline`. Queued audio stays
+    /// contiguous on the timeline: a gap to the queued samples is filled with silence and
+    /// an overlap is cut from the new samples.
+    pub fn push(&self, timeline: f64, samples: &[f32]) {
+        let Ok(mut buffer) = self.buffer.lock() else {
+            return;
+        };
+        let rate = f64::from(self.rate);
+        let queued = buffer.samples.len() / CHANNELS;
+        if queued == 0 {
+            buffer.front = timeline;
+            buffer.samples.extend(samples);
+            return;
+        }
+        let end = buffer.front + queued as f64 / rate;
+        let gap = timeline - end;
+        if gap.abs() <= AUDIO_SYNC_TOLERANCE_SECS {
+            buffer.samples.extend(samples);
+        } else if gap > 0.0 {
+            // At most a second of silence, in case of a bogus timestamp.
+            let frames = (gap.min(1.0) * rate).round() as usize;
+            buffer
+                .samples
+                .extend(std::iter::repeat_n(0.0, frames * CHANNELS));
+            buffer.samples.extend(samples);
+        } else {
+            let skip = ((-gap * rate).round() as usize * CHANNELS).min(samples.len());
+            buffer.samples.extend(&samples[skip..]);
+        }
+    }
+
+    /// Drops all queued samples, e.g. after a seek.
+    pub fn clear(&self) {
+        if let Ok(mut buffer) = self.buffer.lock() {
+            buffer.samples.clear();
+            buffer.clock = None;
+        }
+    }
+
+    /// Seconds of audio queued.
+    pub fn buffered(&self) -> f64 {
+        self.buffer.lock().map_or(0.0, |b| {
+            (b.samples.len() / CHANNELS) as f64 / f64::from(self.rate)
+        })
+    }
+
+    /// Takes all queued samples, with the timeline position of the first one.
+    #[cfg(test)]
+    pub(crate) fn take_queued(&self) -> (f64, Vec<f32>) {
+        let mut buffer = self.buffer.lock().unwrap();
+        let front = buffer.front;
+        (front, buffer.samples.drain(..).collect())
+    }
+
+    /// Returns the timeline position that was being heard at the returned instant, while
+    /// the source plays.
+    pub fn clock(&self) -> Option<(f64, Instant)> {
+        self.buffer.lock().ok()?.clock
+    }
+
+    pub fn set_volume(&self, volume: f32) {
+        self.volume
+            .store(volume.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    /// Paused sources keep their samples but are not played.
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+        if let Ok(mut buffer) = self.buffer.lock() {
+            buffer.clock = None;
+        }
+    }
+
+    /// Adds the next queued frames, scaled by `gain`, to `out` and consumes them.
+    fn mix_into(&self, out: &mut [f32], gain: f32, now: Instant) {
+        if self.paused.load(Ordering::Relaxed) {
+            return;
+        }
+        let Ok(mut buffer) = self.buffer.lock() else {
+            return;
+        };
+        let frames = (out.len() / CHANNELS).min(buffer.samples.len() / CHANNELS);
+        // Without samples the clock stops, and the stream falls back to the wall clock.
+        buffer.clock = (frames > 0).then_some((buffer.front, now));
+        for (out, sample) in out
+            .iter_mut()
+            .zip(buffer.samples.drain(..frames * CHANNELS))
+        {
+            *out += sample * gain;
+        }
+        buffer.front += frames as f64 / f64::from(self.rate);
+    }
+}
+
+/// Mixes the sources of all streams with sound at the output sample rate.
+pub struct Mixer {
+    rate: u32,
+    sources: Mutex<Vec<Weak<AudioSource>>>,
+}
+
+impl Mixer {
+    /// A mixer without a sink; only `global` starts one.
+    pub(crate) fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            sources: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns the process-wide mixer, starting the sink on first use.
+    pub fn global() -> &'static Mixer {
+        static MIXER: OnceLock<Arc<Mixer>> = OnceLock::new();
+        MIXER.get_or_init(start_sink)
+    }
+
+    /// Output sample rate; sources are pushed samples at this rate.
+    pub fn rate(&self) -> u32 {
+        self.rate
+    }
+
+    /// Adds a source to the mix. Dropping it removes it again.
+    pub fn add_source(&self) -> Arc<AudioSource> {
+        let source = Arc::new(AudioSource::new(self.rate));
+        if let Ok(mut sources) = self.sources.lock() {
+            sources.push(Arc::downgrade(&source));
+        }
+        source
+    }
+
+    /// Fills interleaved stereo `out` with the mix of all sources. While muted, the
+    /// sources are consumed but not heard.
+    pub fn render(&self, out: &mut [f32]) {
+        out.fill(0.0);
+        let now = Instant::now();
+        let muted = MUTED.load(Ordering::Relaxed);
+        let Ok(mut sources) = self.sources.lock() else {
+            return;
+        };
+        sources.retain(|source| source.strong_count() > 0);
+        for source in sources.iter().filter_map(Weak::upgrade) {
+            let gain = if muted {
+                0.0
+            } else {
+                f32::from_bits(source.volume.load(Ordering::Relaxed))
+            };
+            source.mix_into(out, gain, now);
+        }
+        for sample in out.iter_mut() {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Sinks (the device stream is !Send and lives on the "audio-output" thread)
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Where the mix is played.
+#[derive(Debug, PartialEq)]
+enum AudioSink {
+    Device,
+    Null,
+    File(PathBuf),
+}
+
+impl AudioSink {
+    fn from_env() -> Self {
+        if cfg!(test) {
+            return Self::Null;
+        }
+        Self::parse(&std::env::var(SINK_ENV).unwrap_or_default())
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "" | "device" => Self::Device,
+            "null" => Self::Null,
+            _ => match value.strip_prefix("file:") {
+                Some(path) => Self::File(PathBuf::from(path)),
+                None => {
+                    log::error!("audio: unknown {SINK_ENV} value {value:?}, using the device");
+                    Self::Device
+                }
+            },
+        }
+    }
+}
+
+/// Starts the sink on its own thread and returns its mixer once the rate is known.
+fn start_sink() -> Arc<Mixer> {
+    let sink = AudioSink::from_env();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let spawned = std::thread::Builder::new()
+        .name("audio-output".to_string())
+        .spawn(move || run_sink(sink, tx));
+    match spawned.ok().and_then(|_| rx.recv().ok()) {
+        Some(mixer) => mixer,
+        None => {
+            log::error!("audio: failed to start the output thread");
+            Arc::new(Mixer::new(AUDIO_FALLBACK_RATE))
+        }
+    }
+}
+
+fn run_sink(sink: AudioSink, tx: Sender<Arc<Mixer>>) {
+    let wav = match sink {
+        AudioSink::Device => match play_device() {
+            Ok((_stream, mixer)) => {
+                tx.send(mixer).ok();
+                // The stream plays for as long as it is alive.
+                loop {
+                    std::thread::park();
+                }
+            }
+            Err(e) => {
+                log::error!("audio: {e}; sound is discarded");
+                None
+            }
+        },
+        AudioSink::Null => None,
+        AudioSink::File(path) => match WavWriter::create(&path, AUDIO_FALLBACK_RATE) {
+            Ok(writer) => Some(writer),
+            Err(e) => {
+                log::error!("audio: failed to create {:?}: {e}", path);
+                None
+            }
+        },
+    };
+    let mixer = Arc::new(Mixer::new(AUDIO_FALLBACK_RATE));
+    tx.send(Arc::clone(&mixer)).ok();
+    run_timed(&mixer, wav);
+}
+
+/// Renders the mix in real time without a device, recording it to `wav` if given.
+fn run_timed(mixer: &Mixer, mut wav: Option<WavWriter>) {
+    let period = Duration::from_millis(AUDIO_SINK_PERIOD_MILLIS);
+    let frames = (u64::from(mixer.rate) * AUDIO_SINK_PERIOD_MILLIS / 1000) as usize;
+    let mut mix = vec![0.0; frames * CHANNELS];
+    let mut next = Instant::now();
+    loop {
+        mixer.render(&mut mix);
+        if let Some(writer) = wav.as_mut() {
+            if let Err(e) = writer.write(&mix) {
+                log::error!("audio: failed to write the WAV file: {e}");
+                wav = None;
+            }
+        }
+        next += period;
+        std::thread::sleep(next.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// Opens the default output device and starts playing the mix on it.
+fn play_device() -> Result<(cpal::Stream, Arc<Mixer>), String> {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("no audio output device")?;
+    let supported = device
+        .default_output_config()
+        .map_err(|e| format!("no output configuration: {e}"))?;
+    let mixer = Arc::new(Mixer::new(supported.sample_rate().0));
+    let config = supported.config();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => device_stream::<f32>(&device, &config, Arc::clone(&mixer)),
+        cpal::SampleFormat::I16 => device_stream::<i16>(&device, &config, Arc::clone(&mixer)),
+        cpal::SampleFormat::U16 => device_stream::<u16>(&device, &config, Arc::clone(&mixer)),
+        format => Err(format!("unsupported sample format {format:?}")),
+    }?;
+    stream
+        .play()
+        .map_err(|e| format!("failed to start the output stream: {e}"))?;
+    Ok((stream, mixer))
+}
+
+/// Builds an output stream rendering the stereo mix into the device's channels: the
+/// first two get left and right, a mono device gets both.
+fn device_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mixer: Arc<Mixer>,
+) -> Result<cpal::Stream, String>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    use cpal::traits::DeviceTrait;
+
+    let channels = usize::from(config.channels);
+    let mut mix = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                mix.resize(data.len() / channels * CHANNELS, 0.0);
+                mixer.render(&mut mix);
+                for (out, frame) in data
+                    .chunks_exact_mut(channels)
+                    .zip(mix.chunks_exact(CHANNELS))
+                {
+                    for (channel, sample) in out.iter_mut().enumerate() {
+                        let value = match channel {
+                            _ if channels == 1 => (frame[0] + frame[1]) * 0.5,
+                            0 | 1 => frame[channel],
+                            _ => 0.0,
+                        };
+                        *sample = T::from_sample(value);
+                    }
+                }
+            },
+            |e| log::error!("audio: output stream error: {e}"),
+            None,
+        )
+        .map_err(|e| format!("failed to open the output stream: {e}"))
+}
+
+/// Writes interleaved stereo f32 samples to a WAV file, updating the header after every
+/// write so the file is complete whenever the app stops.
+struct WavWriter {
+    file: File,
+    data_bytes: u32,
+}
+
+impl WavWriter {
+    fn create(path: &Path, rate: u32) -> std::io::Result<Self> {
+        let mut file = File::create(path)?;
+        let block_align = (CHANNELS * 4) as u16;
+        let mut header = Vec::with_capacity(44);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&36u32.to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16u32.to_le_bytes());
+        // Format 3: IEEE float.
+        header.extend_from_slice(&3u16.to_le_bytes());
+        header.extend_from_slice(&(CHANNELS as u16).to_le_bytes());
+        header.extend_from_slice(&rate.to_le_bytes());
+        header.extend_from_slice(&(rate * u32::from(block_align)).to_le_bytes());
+        header.extend_from_slice(&block_align.to_le_bytes());
+        header.extend_from_slice(&32u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&0u32.to_le_bytes());
+        file.write_all(&header)?;
+        Ok(Self {
+            file,
+            data_bytes: 0,
+        })
+    }
+
+    fn write(&mut self, samples: &[f32]) -> std::io::Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        self.file.write_all(&bytes)?;
+        self.data_bytes = self.data_bytes.saturating_add(bytes.len() as u32);
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(36u32.saturating_add(self.data_bytes)).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_bytes.to_le_bytes())?;
+        self.file.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixer_mixes_sources_and_mutes() {
+        let mixer = Mixer::new(100);
+        let loud = mixer.add_source();
+        let quiet = mixer.add_source();
+        loud.push(0.0, &[0.5; 20]);
+        quiet.push(0.0, &[0.25; 20]);
+        quiet.set_volume(0.5);
+
+        let mut out = [0.0; 8];
+        mixer.render(&mut out);
+        assert_eq!(out, [0.625; 8]);
+        assert!((loud.buffered() - 0.06).abs() < 1e-9);
+
+        // Muted sources are still consumed, so their clocks keep running.
+        set_muted(true);
+        mixer.render(&mut out);
+        set_muted(false);
+        assert_eq!(out, [0.0; 8]);
+        assert!((loud.buffered() - 0.02).abs() < 1e-9);
+
+        // Dropped sources leave the mix; the rest plays out and then renders silence.
+        drop(quiet);
+        mixer.render(&mut out);
+        assert_eq!(out, [0.5, 0.5, 0.5, 0.5, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(mixer.sources.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_source_clock_and_gaps() {
+        let mixer = Mixer::new(100);
+        let source = mixer.add_source();
+        source.push(1.0, &[0.1; 20]);
+        assert_eq!(source.clock(), None);
+
+        let mut out = [0.0; 8];
+        mixer.render(&mut out);
+        assert_eq!(source.clock().map(|(t, _)| t), Some(1.0));
+        mixer.render(&mut out);
+        assert!((source.clock().unwrap().0 - 1.04).abs() < 1e-9);
+
+        // Queued audio ends at 1.1: a later chunk is preceded by silence...
+        source.push(1.2, &[0.1; 4]);
+        assert!((source.buffered() - 0.14).abs() < 1e-9);
+        // ...and one overlapping what is queued is cut.
+        source.push(1.2, &[0.1; 8]);
+        assert!((source.buffered() - 0.16).abs() < 1e-9);
+
+        // Paused sources are not played and have no clock.
+        source.set_paused(true);
+        mixer.render(&mut out);
+        assert_eq!(out, [0.0; 8]);
+        assert_eq!(source.clock(), None);
+        assert!((source.buffered() - 0.16).abs() < 1e-9);
+
+        source.clear();
+        assert_eq!(source.buffered(), 0.0);
+    }
+
+    #[test]
+    fn test_file_sink_writes_wav() {
+        assert_eq!(AudioSink::parse(""), AudioSink::Device);
+        assert_eq!(AudioSink::parse("null"), AudioSink::Null);
+        assert_eq!(
+            AudioSink::parse("file:/tmp/mix.wav"),
+            AudioSink::File(PathBuf::from("/tmp/mix.wav"))
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_audio_{}.wav", uuid::Uuid::new_v4()));
+        let mut writer = WavWriter::create(&path, 48_000).unwrap();
+        writer.write(&[0.5, -0.5, 0.25, -0.25]).unwrap();
+        writer.write(&[1.0, -1.0]).unwrap();
+        drop(writer);
+
+        let bytes = std::fs::read(&path).unwrap();
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        assert_eq!(bytes.len(), 44 + 24);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(u32_at(4), 36 + 24);
+        assert_eq!(u32_at(24), 48_000);
+        assert_eq!(u32_at(40), 24);
+        assert_eq!(f32::from_le_bytes(bytes[44..48].try_into().unwrap()), 0.5);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
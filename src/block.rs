@@ -6,14 +6,23 @@ use crate::constants::{
     COLOR_COUNTER_BUTTON_HOVER, COUNTER_BADGE_DAY_COLORS, COLOR_LABEL_BG_ALPHA, COLOR_NORMAL_GROUP_BG, COUNTER_BADGE_OFFSET,
     COUNTER_BADGE_RADIUS, COUNTER_FONT_SIZE, DEFAULT_GROUP_SIZE, FOLDER_CORNER_RADIUS,
     FOLDER_PREVIEW_SCALE, FOLDER_TAB_CORNER_RADIUS, FOLDER_TAB_HEIGHT, FOLDER_TAB_WIDTH_RATIO,
-    GROUP_TEXTURE_SCALE, LABEL_BG_EXPANSION, LABEL_FONT_SIZE, LABEL_PADDING, MIN_BLOCK_SIZE,
-    ROW_QUANTIZATION_HEIGHT, UUID_COLOR_LIGHTNESS_MIN, UUID_COLOR_LIGHTNESS_RANGE,
-    UUID_COLOR_SATURATION_MIN, UUID_COLOR_SATURATION_RANGE,
+    COLOR_MISSING_BG, COLOR_MISSING_BORDER, GROUP_TEXTURE_SCALE, LABEL_BG_EXPANSION,
+    LABEL_FONT_SIZE, LABEL_PADDING, MIN_BLOCK_SIZE, MISSING_BORDER_WIDTH, ROW_QUANTIZATION_HEIGHT, UUID_COLOR_LIGHTNESS_MIN, UUID_COLOR_LIGHTNESS_RANGE,
+    UUID_COLOR_SATURATION_MIN, UUID_COLOR_SATURATION_RANGE, COLOR_SCRUB_PROGRESS, COLOR_SCRUB_TRACK,
+    SCRUB_BAR_HEIGHT, SCRUB_BAR_HIT_HEIGHT, SCRUB_BAR_MARGIN, SCRUB_TIME_FONT_SIZE,
+    COLOR_TRIM_BUTTON, COLOR_TRIM_BUTTON_ACTIVE, COLOR_TRIM_BUTTON_HOVER, COLOR_TRIM_MARKER,
+    TRIM_MARKER_WIDTH, COLOR_POSTER_BUTTON, COLOR_POSTER_BUTTON_ACTIVE, COLOR_POSTER_BUTTON_HOVER,
+    COLOR_SOUND_BUTTON, COLOR_SOUND_BUTTON_ACTIVE, COLOR_SOUND_BUTTON_HOVER,
+    COLOR_SOUND_BUTTON_MUTED, VOLUME_STEPS, COLOR_CLIP_BUTTON, COLOR_CLIP_BUTTON_ACTIVE,
+    COLOR_CLIP_BUTTON_HOVER, COLOR_SPEED_BUTTON, COLOR_SPEED_BUTTON_ACTIVE,
+    COLOR_SPEED_BUTTON_HOVER, PLAYBACK_SPEEDS, DETAIL_TIERS, MAX_BLOCK_DIMENSION,
 };
-use crate::image_loader::AnimationFrame;
+use crate::image_loader::{AnimationFrame, PosterFrame};
 use eframe::egui::{self, pos2, vec2, Align2, Color32, FontId, Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::path::Path;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
@@ -53,11 +62,85 @@ pub struct AnimationState {
     pub frame_elapsed: Duration,
     pub animation_enabled: bool,
     pub has_animation: bool,
-    /// Streaming video decoder handle. Some only for video-format blocks while playing.
-    /// When Some, `frames` always contains exactly one entry (the first frame).
+    /// Streaming decoder handle. Some only while a video, or an animation looping in
+    /// `PlaybackMode::Loop`, plays. When Some, `frames` always contains exactly one entry
+    /// (the first frame).
     pub video: Option<crate::video_stream::VideoBlockHandle>,
     /// Tracks the last video frame seq consumed, to detect new frames from the decoder.
     pub video_seq: u64,
+    /// Decoded poster frame, shown instead of the first frame while not playing.
+    pub poster: Option<egui::ColorImage>,
+    /// Set while a ping-pong animation plays backwards.
+    pub backwards: bool,
+}
+
+/// Order in which the frames of an animation are played. `Loop` streams the frames from
+/// the file; the other modes step through pre-decoded ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlaybackMode {
+    #[default]
+    Loop,
+    Reverse,
+    /// Forward to the last frame, then backward to the first, and so on.
+    PingPong,
+    /// Forward once, then stays on the last frame.
+    Once,
+}
+
+impl PlaybackMode {
+    /// The mode the speed button switches to next (RMB).
+    pub fn next(self) -> Self {
+        match self {
+            Self::Loop => Self::Reverse,
+            Self::Reverse => Self::PingPong,
+            Self::PingPong => Self::Once,
+            Self::Once => Self::Loop,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Loop => "Loop",
+            Self::Reverse => "Reverse",
+            Self::PingPong => "Ping-pong",
+            Self::Once => "Play once",
+        }
+    }
+
+    /// Returns the frame shown after `current` out of `count`, or None once a play-once
+    /// animation has reached its last frame. `backwards` is the ping-pong direction.
+    fn step(self, current: usize, count: usize, backwards: &mut bool) -> Option<usize> {
+        match self {
+            Self::Loop => Some((current + 1) % count),
+            Self::Reverse => Some((current + count - 1) % count),
+            Self::Once => (current + 1 < count).then_some(current + 1),
+            Self::PingPong => {
+                if count < 2 {
+                    return Some(current);
+                }
+                if (*backwards && current == 0) || (!*backwards && current + 1 >= count) {
+                    *backwards = !*backwards;
+                }
+                Some(if *backwards { current - 1 } else { current + 1 })
+            }
+        }
+    }
+}
+
+/// A sharper texture of a block's still image, decoded while the block is shown larger
+/// than its regular texture (see `ImageBlock::wanted_detail_tier`).
+pub struct DetailTexture {
+    pub texture: egui::TextureHandle,
+    /// The `DETAIL_TIERS` entry it was decoded for.
+    pub tier: u32,
+}
+
+impl DetailTexture {
+    /// Returns true if the whole image fit within the tier, so no higher tier is sharper.
+    fn is_full_resolution(&self) -> bool {
+        let [w, h] = self.texture.size();
+        (w.max(h) as u32) < self.tier
+    }
 }
 
 /// Manages group-related data when multiple blocks are combined.
@@ -74,23 +157,116 @@ pub struct BlockControlHover {
     pub close_hovered: bool,
     pub chain_hovered: bool,
     pub counter_hovered: bool,
+    pub scrub_hovered: bool,
+    pub trim_in_hovered: bool,
+    pub trim_out_hovered: bool,
+    pub poster_hovered: bool,
+    pub sound_hovered: bool,
+    pub clip_hovered: bool,
+    pub speed_hovered: bool,
 }
 
 impl BlockControlHover {
+    /// `poster_rect` and `speed_rect` are the poster and speed buttons of videos and
+    /// animations, `video_rects` are the extra controls of video blocks.
     pub fn from_mouse_pos(
         mouse_pos: Option<Pos2>,
         rects: &(Rect, Rect, Rect),
+        poster_rect: Option<Rect>,
+        speed_rect: Option<Rect>,
+        video_rects: Option<&VideoControlRects>,
         is_group: bool,
     ) -> Self {
         let (close_rect, chain_rect, counter_rect) = rects;
+        let video_hovered = |rect: fn(&VideoControlRects) -> Rect| {
+            mouse_pos.is_some_and(|p| video_rects.is_some_and(|r| rect(r).contains(p)))
+        };
         Self {
             close_hovered: mouse_pos.is_some_and(|p| close_rect.contains(p)),
             chain_hovered: mouse_pos.is_some_and(|p| chain_rect.contains(p)),
             counter_hovered: !is_group && mouse_pos.is_some_and(|p| counter_rect.contains(p)),
+            scrub_hovered: video_hovered(|r| r.scrub),
+            trim_in_hovered: video_hovered(|r| r.trim_in),
+            trim_out_hovered: video_hovered(|r| r.trim_out),
+            poster_hovered: mouse_pos.is_some_and(|p| poster_rect.is_some_and(|r| r.contains(p))),
+            sound_hovered: mouse_pos.is_some_and(|p| {
+                video_rects
+                    .and_then(|r| r.sound)
+                    .is_some_and(|r| r.contains(p))
+            }),
+            clip_hovered: video_hovered(|r| r.clip),
+            speed_hovered: mouse_pos.is_some_and(|p| speed_rect.is_some_and(|r| r.contains(p))),
+        }
+    }
+
+    pub fn any_hovered(&self) -> bool {
+        self.close_hovered
+            || self.chain_hovered
+            || self.counter_hovered
+            || self.scrub_hovered
+            || self.trim_in_hovered
+            || self.trim_out_hovered
+            || self.poster_hovered
+            || self.sound_hovered
+            || self.clip_hovered
+            || self.speed_hovered
+    }
+}
+
+/// Calculates the hit-rect of the poster button, left of the counter button.
+pub fn poster_button_rect(rect: Rect, zoom: f32) -> Rect {
+    let (_, _, counter_rect) = block_control_rects(rect, zoom);
+    counter_rect.translate(-control_step(counter_rect, zoom))
+}
+
+/// Calculates the hit-rect of the speed button, left of the poster button.
+pub fn speed_button_rect(rect: Rect, zoom: f32) -> Rect {
+    let poster_rect = poster_button_rect(rect, zoom);
+    poster_rect.translate(-control_step(poster_rect, zoom))
+}
+
+/// Offset between neighbouring control buttons.
+fn control_step(button_rect: Rect, zoom: f32) -> Vec2 {
+    Vec2::new(button_rect.width() + BUTTON_SPACING * zoom, 0.0)
+}
+
+/// Hit-rects of the controls shown only on video blocks.
+#[derive(Clone, Copy)]
+pub struct VideoControlRects {
+    pub scrub: Rect,
+    /// Trim buttons, left of the speed button.
+    pub trim_in: Rect,
+    pub trim_out: Rect,
+    /// Clip export button, left of the trim buttons.
+    pub clip: Rect,
+    /// Sound button left of the clip button, for videos with audio.
+    pub sound: Option<Rect>,
+}
+
+impl VideoControlRects {
+    pub fn new(rect: Rect, zoom: f32, has_sound: bool) -> Self {
+        let speed_rect = speed_button_rect(rect, zoom);
+        let step = control_step(speed_rect, zoom);
+        let trim_out = speed_rect.translate(-step);
+        let trim_in = trim_out.translate(-step);
+        let clip = trim_in.translate(-step);
+        Self {
+            scrub: scrub_bar_rect(rect, zoom),
+            trim_in,
+            trim_out,
+            clip,
+            sound: has_sound.then(|| clip.translate(-step)),
         }
     }
 }
 
+/// Which end of a video block's trim range is edited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrimPoint {
+    In,
+    Out,
+}
+
 /// Calculates the hit-rects for block control buttons (close, chain, counter) based on the block's current rect and zoom.
 pub fn block_control_rects(rect: Rect, zoom: f32) -> (Rect, Rect, Rect) {
     let btn_size = BUTTON_BASE_SIZE * zoom;
@@ -119,6 +295,18 @@ pub fn block_control_rects(rect: Rect, zoom: f32) -> (Rect, Rect, Rect) {
     (close_rect, chain_rect, counter_rect)
 }
 
+/// Calculates the hit-rect of a video block's scrub bar, along the bottom of the image.
+pub fn scrub_bar_rect(rect: Rect, zoom: f32) -> Rect {
+    let image_rect = rect.shrink((BLOCK_PADDING + SCRUB_BAR_MARGIN) * zoom);
+    Rect::from_min_max(
+        pos2(
+            image_rect.min.x,
+            image_rect.max.y - SCRUB_BAR_HIT_HEIGHT * zoom,
+        ),
+        image_rect.max,
+    )
+}
+
 /// Returns days since Unix epoch — used as an absolute "today" stamp.
 pub fn current_day() -> u32 {
     (SystemTime::now()
@@ -134,6 +322,42 @@ pub fn weekday_of(day: u32) -> usize {
     ((day + 3) % 7) as usize
 }
 
+/// Returns the entry of `PLAYBACK_SPEEDS` closest to `speed`, e.g. for a speed read from
+/// a file; anything that is not a positive number becomes normal speed.
+pub fn nearest_playback_speed(speed: f32) -> f32 {
+    if speed.is_nan() || speed <= 0.0 {
+        return 1.0;
+    }
+    let speed = speed.min(PLAYBACK_SPEEDS[PLAYBACK_SPEEDS.len() - 1]);
+    PLAYBACK_SPEEDS
+        .iter()
+        .copied()
+        .min_by(|a, b| (a - speed).abs().total_cmp(&(b - speed).abs()))
+        .unwrap_or(1.0)
+}
+
+/// Frame shown by a block with `poster` and `trim` while it is not playing; see
+/// `ImageBlock::poster_frame`.
+pub fn poster_frame_of(
+    poster: Option<PosterFrame>,
+    trim: Option<crate::video_stream::TrimRange>,
+) -> Option<PosterFrame> {
+    poster.or_else(|| {
+        trim.filter(|t| t.start > 0.0)
+            .map(|t| PosterFrame::Time(t.start))
+    })
+}
+
+/// Clamps a volume read from a file into 0..=1, like `AudioSource::set_volume`; anything
+/// that is not a number becomes full volume.
+pub fn restored_volume(volume: f32) -> f32 {
+    if volume.is_nan() {
+        1.0
+    } else {
+        volume.clamp(0.0, 1.0)
+    }
+}
+
 /// The core entity of the application, representing an image or a group of images.
 /// It handles its own rendering, animation state, and interaction properties.
 pub struct ImageBlock {
@@ -153,6 +377,31 @@ pub struct ImageBlock {
     pub counter_start_day: u32,
     pub is_full_sequence: bool,
     pub file_size: u64,
+    /// Content fingerprint of the source file, recorded while it was reachable.
+    pub fingerprint: Option<u64>,
+    /// Set when loading the block's file failed (e.g. it was moved or deleted).
+    pub is_missing: bool,
+    /// Position in seconds chosen with the scrub bar; playback starts from here.
+    pub video_position: Option<f64>,
+    /// Probed properties of a video file; None for images.
+    pub video_metadata: Option<crate::video_stream::VideoMetadata>,
+    /// In and out points of a video block; playback loops between them.
+    pub video_trim: Option<crate::video_stream::TrimRange>,
+    /// Frame chosen as the still image of a video or animation.
+    pub poster: Option<PosterFrame>,
+    /// Set when the sound of a video block is turned off.
+    pub audio_muted: bool,
+    /// Volume (0..=1) of a video block's sound.
+    pub volume: f32,
+    /// Set while a clip of the video is being exported.
+    pub exporting_clip: bool,
+    /// Playback rate of a video or animation (1.0 is normal speed).
+    pub playback_speed: f32,
+    /// Frame order of an animation; videos always play forward.
+    pub playback_mode: PlaybackMode,
+    /// Sharper texture shown instead of `texture` while zoomed in; dropped once the block
+    /// scrolls away.
+    pub detail: Option<DetailTexture>,
 }
 
 /// Contextual configuration passed during the rendering phase of a block.
@@ -200,6 +449,8 @@ impl ImageBlock {
                 has_animation,
                 video: None,
                 video_seq: 0,
+                poster: None,
+                backwards: false,
             },
             group: GroupData {
                 is_group: false,
@@ -216,6 +467,18 @@ impl ImageBlock {
             counter_start_day: 0,
             is_full_sequence,
             file_size: 0,
+            fingerprint: None,
+            is_missing: false,
+            video_position: None,
+            video_metadata: None,
+            video_trim: None,
+            poster: None,
+            audio_muted: false,
+            volume: 1.0,
+            exporting_clip: false,
+            playback_speed: 1.0,
+            playback_mode: PlaybackMode::Loop,
+            detail: None,
         }
     }
 
@@ -246,6 +509,8 @@ impl ImageBlock {
                 has_animation: false,
                 video: None,
                 video_seq: 0,
+                poster: None,
+                backwards: false,
             },
             group: GroupData {
                 is_group: true,
@@ -262,6 +527,18 @@ impl ImageBlock {
             counter_start_day: 0,
             is_full_sequence: true,
             file_size: 0,
+            fingerprint: None,
+            is_missing: false,
+            video_position: None,
+            video_metadata: None,
+            video_trim: None,
+            poster: None,
+            audio_muted: false,
+            volume: 1.0,
+            exporting_clip: false,
+            playback_speed: 1.0,
+            playback_mode: PlaybackMode::Loop,
+            detail: None,
         }
     }
 
@@ -305,14 +582,13 @@ impl ImageBlock {
     /// Advances the animation state based on elapsed time. Returns true if the frame changed.
     pub fn update_animation(&mut self, dt: f32) -> bool {
         // Video streaming path: check if a new frame arrived from the decoder thread.
+        // Frames also arrive while paused when the video is scrubbed.
         if let Some(ref handle) = self.anim.video {
-            if !self.anim.animation_enabled {
-                return false;
-            }
             if let Ok(guard) = handle.latest_frame.lock() {
                 if let Some(ref frame) = *guard {
                     if frame.seq != self.anim.video_seq {
                         self.anim.video_seq = frame.seq;
+                        self.anim.current_frame = frame.index;
                         self.texture
                             .set(frame.image.clone(), egui::TextureOptions::LINEAR);
                         return true;
@@ -327,11 +603,23 @@ impl ImageBlock {
             return false;
         }
 
-        self.anim.frame_elapsed += Duration::from_secs_f32(dt.max(0.0));
+        if self.animation_finished() {
+            return false;
+        }
+
+        self.anim.frame_elapsed += Duration::from_secs_f32(dt.max(0.0) * self.playback_speed);
         let mut updated = false;
         while self.anim.frame_elapsed >= self.anim.frames[self.anim.current_frame].duration {
+            let Some(next) = self.playback_mode.step(
+                self.anim.current_frame,
+                self.anim.frames.len(),
+                &mut self.anim.backwards,
+            ) else {
+                self.anim.frame_elapsed = Duration::ZERO;
+                break;
+            };
             self.anim.frame_elapsed -= self.anim.frames[self.anim.current_frame].duration;
-            self.anim.current_frame = (self.anim.current_frame + 1) % self.anim.frames.len();
+            self.anim.current_frame = next;
             let frame_image = self.anim.frames[self.anim.current_frame].image.clone();
             self.texture.set(frame_image, egui::TextureOptions::LINEAR);
             updated = true;
@@ -339,20 +627,37 @@ impl ImageBlock {
         updated
     }
 
+    /// Returns true once a play-once animation shows its last frame.
+    fn animation_finished(&self) -> bool {
+        self.playback_mode == PlaybackMode::Once
+            && self.anim.current_frame + 1 >= self.anim.frames.len()
+    }
+
     pub fn time_until_next_frame(&self) -> Option<Duration> {
         if let Some(ref handle) = self.anim.video {
             if self.anim.animation_enabled {
-                return Some(handle.frame_duration);
+                // Wake up when the decoder's next frame is due, per its timestamp.
+                let Some(due) = handle.next_frame_due() else {
+                    return Some(handle.frame_duration());
+                };
+                let remaining = due.saturating_duration_since(std::time::Instant::now());
+                return Some(remaining.max(Duration::from_millis(1)));
+            }
+            if handle.is_seeking() {
+                return Some(handle.frame_duration());
             }
             return None;
         }
 
-        if !self.anim.animation_enabled || self.anim.frames.len() <= 1 {
+        if !self.anim.animation_enabled || self.anim.frames.len() <= 1 || self.animation_finished()
+        {
             return None;
         }
 
         let frame_duration = self.anim.frames[self.anim.current_frame].duration;
-        let remaining = frame_duration.saturating_sub(self.anim.frame_elapsed);
+        let remaining = frame_duration
+            .saturating_sub(self.anim.frame_elapsed)
+            .div_f32(self.playback_speed);
         if remaining.is_zero() {
             Some(Duration::from_millis(1))
         } else {
@@ -366,7 +671,7 @@ impl ImageBlock {
             self.anim.animation_enabled = !self.anim.animation_enabled;
             if self.anim.animation_enabled {
                 if let Some(ref handle) = self.anim.video {
-                    handle.cmd_tx.send(crate::video_stream::StreamCmd::Play).ok();
+                    handle.send(crate::video_stream::StreamCmd::Play);
                 }
             } else {
                 self.stop_animation();
@@ -377,6 +682,16 @@ impl ImageBlock {
         if self.anim.frames.len() <= 1 {
             return;
         }
+        if self.anim.animation_enabled && self.animation_finished() {
+            // Clicking a finished play-once animation plays it again.
+            self.anim.current_frame = 0;
+            self.anim.frame_elapsed = Duration::ZERO;
+            self.texture.set(
+                self.anim.frames[0].image.clone(),
+                egui::TextureOptions::LINEAR,
+            );
+            return;
+        }
         self.anim.animation_enabled = !self.anim.animation_enabled;
         if !self.anim.animation_enabled {
             self.stop_animation();
@@ -388,18 +703,459 @@ impl ImageBlock {
         self.anim.current_frame = 0;
         self.anim.frame_elapsed = Duration::ZERO;
         self.anim.video_seq = 0;
+        self.anim.backwards = false;
 
-        if let Some(ref handle) = self.anim.video {
-            handle.cmd_tx.send(crate::video_stream::StreamCmd::Pause).ok();
+        if !self.is_video() && self.anim.video.take().is_some() {
+            // A streamed animation starts over when played again; its stream is closed
+            // rather than kept paused.
+            self.is_full_sequence = false;
+        }
+
+        let start_position = self.video_start_position();
+        if let Some(ref mut handle) = self.anim.video {
+            handle.send(crate::video_stream::StreamCmd::Pause);
             self.texture
                 .set(handle.first_frame.clone(), egui::TextureOptions::LINEAR);
+            // Rewind so playback resumes from the chosen position (or the start).
+            handle.seek(start_position);
             return;
         }
 
-        if let Some(first) = self.anim.frames.first() {
-            self.texture
-                .set(first.image.clone(), egui::TextureOptions::LINEAR);
+        if let Some(still) = self.still_image() {
+            self.texture.set(still, egui::TextureOptions::LINEAR);
+        }
+    }
+
+    /// Returns true if the block offers choosing a poster frame (videos and animations).
+    pub fn has_poster_button(&self) -> bool {
+        !self.group.is_group && !self.is_missing && (self.is_video() || self.anim.has_animation)
+    }
+
+    /// Returns true if this is a single block showing a video file.
+    pub fn is_video(&self) -> bool {
+        !self.group.is_group && crate::video_stream::is_video_format(Path::new(&self.path))
+    }
+
+    /// Returns true if the block plays through a streaming decoder: videos, and
+    /// animations in `PlaybackMode::Loop`.
+    pub fn streams_playback(&self) -> bool {
+        self.is_video() || self.playback_mode == PlaybackMode::Loop
+    }
+
+    /// Starts the (paused) streaming decoder of a video or animation block, resuming a
+    /// video from `video_position` within the trim range. Returns false if the first frame
+    /// has not loaded yet.
+    pub fn start_video_decoder(&mut self) -> bool {
+        if self.anim.video.is_some() {
+            return true;
+        }
+        let Some(first_frame) = self.still_image() else {
+            return false;
+        };
+        let mut handle =
+            crate::video_stream::spawn_video_decoder(PathBuf::from(&self.path), first_frame);
+        if self.video_trim.is_some() {
+            handle.send(crate::video_stream::StreamCmd::Trim(self.video_trim));
+        }
+        handle.set_rate(f64::from(self.playback_speed));
+        if self.video_position.is_some() || self.video_trim.is_some() {
+            handle.seek(self.video_start_position());
         }
+        self.anim.video = Some(handle);
+        self.anim.has_animation = true;
+        self.is_full_sequence = true;
+        true
+    }
+
+    /// Returns true if the block is a video whose file has sound, as probed.
+    pub fn has_sound(&self) -> bool {
+        self.is_video()
+            && !self.is_missing
+            && self.video_metadata.as_ref().is_some_and(|m| m.has_audio)
+    }
+
+    /// Returns true while the block plays a video with sound that is not muted, at normal
+    /// speed (the sound is not time-stretched).
+    pub fn wants_sound(&self) -> bool {
+        self.has_sound()
+            && !self.audio_muted
+            && self.playback_speed == 1.0
+            && self.anim.animation_enabled
+            && self.anim.video.is_some()
+    }
+
+    /// Plays the sound of a playing video at the block's volume, or silences it.
+    pub fn set_audible(&mut self, audible: bool) {
+        let volume = audible.then_some(self.volume);
+        if let Some(handle) = self.anim.video.as_mut() {
+            handle.set_audio(volume);
+        }
+    }
+
+    /// Short description of the sound setting, shown on the sound button.
+    pub fn sound_details(&self) -> String {
+        if self.audio_muted {
+            "Sound off".to_string()
+        } else {
+            format!("Volume {:.0}%", self.volume * 100.0)
+        }
+    }
+
+    /// Segment (start, end) in seconds the clip button exports (see `clip::clip_range`).
+    pub fn clip_range(&self) -> (f64, f64) {
+        let duration = self.video_metadata.as_ref().and_then(|m| m.duration);
+        crate::clip::clip_range(self.video_trim, self.video_current_position(), duration)
+    }
+
+    /// Describes what the clip button exports, shown on hover.
+    pub fn clip_details(&self) -> String {
+        if self.exporting_clip {
+            return "Exporting clip…".to_string();
+        }
+        let (start, end) = self.clip_range();
+        format!(
+            "Save {}–{} as GIF (LMB) / WebP (RMB)",
+            crate::video_stream::format_timestamp(start),
+            crate::video_stream::format_timestamp(end)
+        )
+    }
+
+    /// Steps the speed up through `PLAYBACK_SPEEDS`, wrapping back to the slowest, and
+    /// passes it on to a playing stream.
+    pub fn cycle_speed(&mut self) {
+        self.playback_speed = PLAYBACK_SPEEDS
+            .iter()
+            .copied()
+            .find(|s| *s > self.playback_speed + 0.01)
+            .unwrap_or(PLAYBACK_SPEEDS[0]);
+        let rate = f64::from(self.playback_speed);
+        if let Some(handle) = self.anim.video.as_mut() {
+            handle.set_rate(rate);
+        }
+    }
+
+    /// Switches an animation to the next `PlaybackMode`; videos go back to normal speed.
+    pub fn cycle_playback_mode(&mut self) {
+        if self.is_video() {
+            self.playback_speed = 1.0;
+            if let Some(handle) = self.anim.video.as_mut() {
+                handle.set_rate(1.0);
+            }
+        } else {
+            let streamed = self.streams_playback();
+            self.playback_mode = self.playback_mode.next();
+            self.anim.backwards = false;
+            if self.streams_playback() != streamed {
+                // Neither a stream nor pre-decoded frames suit the other kind of playback.
+                self.purge_animation_frames();
+            }
+        }
+    }
+
+    /// Returns true if the speed or frame order differs from normal playback.
+    pub fn has_custom_playback(&self) -> bool {
+        self.playback_speed != 1.0 || self.playback_mode != PlaybackMode::Loop
+    }
+
+    /// Describes the playback settings, shown on the speed button.
+    pub fn playback_details(&self) -> String {
+        let speed = format!("Speed {}x", self.playback_speed);
+        if self.is_video() {
+            format!("{speed} (LMB: faster, RMB: normal speed)")
+        } else {
+            format!(
+                "{speed}, {} (LMB: faster, RMB: mode)",
+                self.playback_mode.label()
+            )
+        }
+    }
+
+    /// Steps the volume down through `VOLUME_STEPS`, wrapping back to full volume.
+    pub fn cycle_volume(&mut self) {
+        self.volume = VOLUME_STEPS
+            .iter()
+            .copied()
+            .find(|v| *v < self.volume - 0.01)
+            .unwrap_or(VOLUME_STEPS[0]);
+    }
+
+    /// Seeks a video block to `fraction` (0..=1) of its length and remembers the position.
+    /// Starts the decoder if needed; returns false if the video cannot be scrubbed.
+    /// Called every frame while scrubbing, so a seek is only sent for a new position and
+    /// once the previous seek has produced its frame; `finish_scrub` sends the last one.
+    pub fn scrub_video(&mut self, fraction: f32) -> bool {
+        if !self.start_video_decoder() {
+            return false;
+        }
+        let Some(duration) = self.video_duration() else {
+            return false;
+        };
+        let Some(handle) = self.anim.video.as_mut() else {
+            return false;
+        };
+        let position = duration * f64::from(fraction.clamp(0.0, 1.0));
+        let position = self.video_trim.map_or(position, |t| t.clamp(position));
+        if handle.last_seek() != Some(position) && !handle.is_seeking() {
+            handle.seek(position);
+        }
+        self.video_position = Some(position);
+        true
+    }
+
+    /// Seeks to the scrub position if it was skipped while an earlier seek was running.
+    pub fn finish_scrub(&mut self) {
+        let Some(position) = self.video_position else {
+            return;
+        };
+        if let Some(handle) = self.anim.video.as_mut() {
+            if handle.last_seek() != Some(position) {
+                handle.seek(position);
+            }
+        }
+    }
+
+    /// Position playback starts from: the scrub position, kept inside the trim range.
+    pub fn video_start_position(&self) -> f64 {
+        let position = self.video_position.unwrap_or(0.0);
+        self.video_trim.map_or(position, |t| t.clamp(position))
+    }
+
+    /// Position in seconds of the frame currently shown by a video block.
+    pub fn video_current_position(&self) -> f64 {
+        self.anim
+            .video
+            .as_ref()
+            .and_then(|h| h.position())
+            .or(self.video_position)
+            .unwrap_or(0.0)
+    }
+
+    /// Sets a trim point of a video block to `position`, or clears it for None; the range
+    /// is dropped once both ends are cleared. A point that would end up on the wrong side
+    /// of the other one clears the other one. Returns true if the poster frame changed
+    /// (see `poster_frame`), in which case it should be reloaded.
+    pub fn set_trim_point(&mut self, point: TrimPoint, position: Option<f64>) -> bool {
+        let old_poster = self.poster_frame();
+        let mut trim = self.video_trim.unwrap_or(crate::video_stream::TrimRange {
+            start: 0.0,
+            end: None,
+        });
+        match point {
+            TrimPoint::In => {
+                trim.start = position.unwrap_or(0.0).max(0.0);
+                if trim.end.is_some_and(|end| end <= trim.start) {
+                    trim.end = None;
+                }
+            }
+            TrimPoint::Out => {
+                trim.end = position.filter(|p| *p > 0.0);
+                if trim.end.is_some_and(|end| end <= trim.start) {
+                    trim.start = 0.0;
+                }
+            }
+        }
+        self.video_trim = (trim.start > 0.0 || trim.end.is_some()).then_some(trim);
+
+        if let Some(handle) = self.anim.video.as_mut() {
+            handle.send(crate::video_stream::StreamCmd::Trim(self.video_trim));
+        }
+        // Keep the saved position inside the segment.
+        if let Some(trim) = self.video_trim {
+            let position = trim.clamp(self.video_current_position());
+            if self.video_position != Some(position) {
+                self.video_position = Some(position);
+                if let Some(handle) = self.anim.video.as_mut() {
+                    handle.seek(position);
+                }
+            }
+        }
+        self.poster_frame() != old_poster
+    }
+
+    /// Frame shown while the block is not playing, if not the first one: the chosen
+    /// poster, or else the trim in point of a video.
+    pub fn poster_frame(&self) -> Option<PosterFrame> {
+        poster_frame_of(self.poster, self.video_trim)
+    }
+
+    /// Chooses the frame currently shown as the poster; returns true if it changed.
+    /// A paused animation shows its first frame, so this clears its poster.
+    pub fn choose_poster(&mut self) -> bool {
+        let old_poster = self.poster_frame();
+        self.poster = if self.is_video() {
+            Some(PosterFrame::Time(self.video_current_position()))
+        } else {
+            Some(self.anim.current_frame)
+                .filter(|index| *index > 0)
+                .map(PosterFrame::Index)
+        };
+        self.poster_frame() != old_poster
+    }
+
+    /// Clears the chosen poster; returns true if the shown poster frame changed.
+    pub fn clear_poster(&mut self) -> bool {
+        let old_poster = self.poster_frame();
+        self.poster = None;
+        self.poster_frame() != old_poster
+    }
+
+    /// Returns the poster image if it can be taken from the loaded animation frames.
+    pub fn poster_from_frames(&self) -> Option<egui::ColorImage> {
+        match self.poster_frame() {
+            Some(PosterFrame::Index(index)) if self.is_full_sequence => {
+                self.anim.frames.get(index).map(|f| f.image.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true while the poster frame is chosen but has not been decoded yet.
+    pub fn is_poster_pending(&self) -> bool {
+        self.poster_frame().is_some() && self.anim.poster.is_none()
+    }
+
+    /// Still image of the block: the poster frame, or else the first frame.
+    pub fn still_image(&self) -> Option<egui::ColorImage> {
+        self.anim
+            .poster
+            .clone()
+            .or_else(|| self.anim.frames.first().map(|f| f.image.clone()))
+    }
+
+    /// Sets the decoded poster frame (see `poster_frame`), or None to show the first frame.
+    pub fn set_poster(&mut self, image: Option<egui::ColorImage>) {
+        self.anim.poster = image;
+        self.detail = None;
+        let Some(still) = self.still_image() else {
+            return;
+        };
+        match self.anim.video.as_mut() {
+            Some(handle) => handle.first_frame = still,
+            None if !self.anim.animation_enabled => {
+                self.texture.set(still, egui::TextureOptions::LINEAR)
+            }
+            None => {}
+        }
+    }
+
+    /// Length of the video in seconds, from the open stream or the probed metadata.
+    pub fn video_duration(&self) -> Option<f64> {
+        self.anim
+            .video
+            .as_ref()
+            .and_then(|h| h.duration())
+            .or_else(|| self.video_metadata.as_ref().and_then(|m| m.duration))
+            .filter(|d| *d > 0.0)
+    }
+
+    /// Drops decoded animation frames (or the video decoder), keeping only the first frame.
+    pub fn purge_animation_frames(&mut self) {
+        if self.anim.video.is_some() {
+            // Dropping the handle closes the stream on its decode worker.
+            self.anim.video = None;
+            self.is_full_sequence = false;
+            self.stop_animation();
+        } else if self.is_full_sequence && self.anim.frames.len() > 1 {
+            self.anim.frames.truncate(1);
+            self.is_full_sequence = false;
+            self.stop_animation();
+        }
+    }
+
+    /// Approximate memory released by `purge_animation_frames`: the frames of a loaded
+    /// animation past the first, or the buffers of an open video stream.
+    pub fn animation_bytes(&self) -> usize {
+        if let Some(handle) = &self.anim.video {
+            return handle.memory_bytes();
+        }
+        if !self.is_full_sequence {
+            return 0;
+        }
+        self.anim
+            .frames
+            .iter()
+            .skip(1)
+            .map(|f| f.image.pixels.len() * 4)
+            .sum()
+    }
+
+    /// Approximate memory held by this block and its group children: textures plus
+    /// decoded frames.
+    pub fn memory_bytes(&self) -> usize {
+        self.texture.byte_size()
+            + self.detail_bytes()
+            + self
+                .anim
+                .frames
+                .iter()
+                .map(|f| f.image.pixels.len() * 4)
+                .sum::<usize>()
+            + self.anim.poster.as_ref().map_or(0, |p| p.pixels.len() * 4)
+            + self
+                .group
+                .children
+                .iter()
+                .map(ImageBlock::memory_bytes)
+                .sum::<usize>()
+    }
+
+    /// Detail tier to decode for a still image shown `screen_px` physical pixels wide
+    /// (longer side), or None if the current textures are sharp enough. Only images whose
+    /// regular texture was downsampled to `MAX_BLOCK_DIMENSION` get one.
+    pub fn wanted_detail_tier(&self, screen_px: f32) -> Option<u32> {
+        if self.group.is_group
+            || self.is_missing
+            || self.is_video()
+            || self.anim.animation_enabled
+            || self.anim.frames.is_empty()
+            || self.is_poster_pending()
+        {
+            return None;
+        }
+        let [w, h] = self.texture.size();
+        let base = w.max(h) as f32;
+        // `+ 1.0` allows for the rounding down in `downsample_frame`.
+        if base + 1.0 < MAX_BLOCK_DIMENSION || screen_px <= base {
+            return None;
+        }
+        let tier = DETAIL_TIERS
+            .iter()
+            .copied()
+            .find(|t| *t as f32 >= screen_px)
+            .unwrap_or(DETAIL_TIERS[DETAIL_TIERS.len() - 1]);
+        match &self.detail {
+            Some(detail) if detail.tier >= tier || detail.is_full_resolution() => None,
+            _ => Some(tier),
+        }
+    }
+
+    /// Drops detail textures (also of group children) that are no longer needed: the
+    /// block is off screen, playing, or shown no larger than its regular texture.
+    pub fn release_detail(&mut self, visible: bool, screen_px: f32) {
+        if self.detail.is_some() {
+            let [w, h] = self.texture.size();
+            if !visible || self.anim.animation_enabled || screen_px <= w.max(h) as f32 {
+                self.detail = None;
+            }
+        }
+        for child in &mut self.group.children {
+            child.release_detail(false, 0.0);
+        }
+    }
+
+    /// Shows `image`, decoded for `tier`, instead of the regular texture while paused.
+    pub fn set_detail(&mut self, ctx: &egui::Context, tier: u32, image: egui::ColorImage) {
+        let texture = ctx.load_texture(
+            format!("detail-{}", self.id),
+            image,
+            egui::TextureOptions::LINEAR,
+        );
+        self.detail = Some(DetailTexture { texture, tier });
+    }
+
+    /// Memory held by the detail texture, if any.
+    pub fn detail_bytes(&self) -> usize {
+        self.detail.as_ref().map_or(0, |d| d.texture.byte_size())
     }
 
     pub fn reset_counters_recursive(&mut self) {
@@ -418,6 +1174,8 @@ impl ImageBlock {
         frames: &mut Vec<crate::image_loader::AnimationFrame>,
         has_animation: bool,
         is_full: bool,
+        fingerprint: Option<u64>,
+        video_metadata: Option<&crate::video_stream::VideoMetadata>,
     ) -> (bool, Option<egui::TextureHandle>) {
         let mut updated = false;
         let mut first_texture = None;
@@ -430,12 +1188,20 @@ impl ImageBlock {
                 self.anim.has_animation = has_animation;
                 self.is_full_sequence = is_full;
 
-                if !self.anim.frames.is_empty() {
-                    self.texture.set(
-                        self.anim.frames[0].image.clone(),
-                        egui::TextureOptions::LINEAR,
-                    );
-                    first_texture = Some(self.texture.clone());
+                self.is_missing = false;
+                if fingerprint.is_some() {
+                    self.fingerprint = fingerprint;
+                }
+                if video_metadata.is_some() {
+                    self.video_metadata = video_metadata.cloned();
+                }
+
+                // A chosen poster replaces the placeholder once it is decoded.
+                if !self.is_poster_pending() {
+                    if let Some(still) = self.still_image() {
+                        self.texture.set(still, egui::TextureOptions::LINEAR);
+                        first_texture = Some(self.texture.clone());
+                    }
                 }
                 updated = true;
             } else if is_full && !self.is_full_sequence {
@@ -457,12 +1223,21 @@ impl ImageBlock {
                 } else {
                     Vec::new()
                 };
-                let (child_updated, child_tex) = child.populate_skeletons_by_path(
+                let (child_updated, mut child_tex) = child.populate_skeletons_by_path(
                     path,
                     &mut child_frames,
                     has_animation,
                     is_full,
+                    fingerprint,
+                    video_metadata,
                 );
+                // Boxed blocks are not shown on their own, so they do not wait for a poster.
+                if child_updated && child_tex.is_none() && child.is_poster_pending() {
+                    if let Some(still) = child.still_image() {
+                        child.texture.set(still, egui::TextureOptions::LINEAR);
+                        child_tex = Some(child.texture.clone());
+                    }
+                }
                 if child_updated {
                     updated = true;
                     if child_texture_for_representative.is_none() {
@@ -501,6 +1276,94 @@ impl ImageBlock {
         false
     }
 
+    /// Recursively marks skeleton blocks (no frames loaded yet) with the given path as missing.
+    /// Returns true if any block was newly marked.
+    pub fn mark_missing_by_path(&mut self, path: &str) -> bool {
+        let mut marked = false;
+        if !self.group.is_group && self.path == path && self.anim.frames.is_empty() {
+            marked = !self.is_missing;
+            self.is_missing = true;
+        }
+        for child in &mut self.group.children {
+            marked |= child.mark_missing_by_path(path);
+        }
+        marked
+    }
+
+    /// Recursively turns blocks showing the given path back into missing skeletons, e.g.
+    /// after their file was deleted, so a later load repopulates them.
+    /// Returns true if any block was newly marked.
+    pub fn mark_deleted_by_path(&mut self, path: &str) -> bool {
+        let mut marked = false;
+        if !self.group.is_group && self.path == path && !self.is_missing {
+            self.stop_animation();
+            self.anim.video = None;
+            self.anim.frames.clear();
+            self.detail = None;
+            self.is_full_sequence = false;
+            self.is_missing = true;
+            marked = true;
+        }
+        for child in &mut self.group.children {
+            marked |= child.mark_deleted_by_path(path);
+        }
+        marked
+    }
+
+    /// Returns true if this block or any of its group children shows the given path.
+    pub fn contains_path(&self, path: &str) -> bool {
+        (!self.group.is_group && self.path == path)
+            || self.group.children.iter().any(|c| c.contains_path(path))
+    }
+
+    /// Recursively collects the paths of this block and its group children.
+    pub fn collect_paths(&self, out: &mut Vec<String>) {
+        if !self.group.is_group {
+            out.push(self.path.clone());
+        }
+        for child in &self.group.children {
+            child.collect_paths(out);
+        }
+    }
+
+    /// Recursively collects the paths of skeleton blocks still waiting for their first frame.
+    pub fn collect_skeleton_paths(&self, out: &mut Vec<String>) {
+        if !self.group.is_group && self.anim.frames.is_empty() && !self.is_missing {
+            out.push(self.path.clone());
+        }
+        for child in &self.group.children {
+            child.collect_skeleton_paths(out);
+        }
+    }
+
+    /// Recursively collects the path and fingerprint of every missing block.
+    pub fn collect_missing(&self, out: &mut Vec<(String, Option<u64>)>) {
+        if self.is_missing {
+            out.push((self.path.clone(), self.fingerprint));
+        }
+        for child in &self.group.children {
+            child.collect_missing(out);
+        }
+    }
+
+    /// Recursively points missing blocks at their relinked paths.
+    /// Returns the new paths that need to be loaded.
+    pub fn relink_missing(&mut self, renames: &HashMap<String, PathBuf>) -> Vec<PathBuf> {
+        let mut relinked = Vec::new();
+        if self.is_missing {
+            if let Some(new_path) = renames.get(&self.path) {
+                self.path = new_path.to_string_lossy().into_owned();
+                self.file_size = std::fs::metadata(new_path).map(|m| m.len()).unwrap_or(0);
+                self.is_missing = false;
+                relinked.push(new_path.clone());
+            }
+        }
+        for child in &mut self.group.children {
+            relinked.extend(child.relink_missing(renames));
+        }
+        relinked
+    }
+
     pub fn cmp_layout(&self, other: &Self) -> Ordering {
         match (self.group.is_group, other.group.is_group) {
             (true, false) => Ordering::Less,
@@ -543,6 +1406,7 @@ impl ImageBlock {
     /// Renders the block and its controls to the UI.
     pub fn render(&self, ui: &mut egui::Ui, rect: Rect, config: BlockRenderConfig) {
         let painter = ui.painter_at(rect);
+        let show_scrub_bar = config.show_controls && self.is_video() && !self.is_missing;
 
         let image_rect = Rect::from_min_size(
             pos2(
@@ -605,10 +1469,27 @@ impl ImageBlock {
                 tag_shape.uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
                 painter.add(tag_shape);
             }
+        } else if self.is_missing {
+            painter.rect(
+                image_rect,
+                rounding,
+                COLOR_MISSING_BG,
+                egui::Stroke::new(MISSING_BORDER_WIDTH * config.zoom, COLOR_MISSING_BORDER),
+            );
+            painter.text(
+                image_rect.center(),
+                Align2::CENTER_CENTER,
+                "⚠ missing",
+                FontId::proportional(LABEL_FONT_SIZE * config.zoom),
+                COLOR_MISSING_BORDER,
+            );
         } else {
             let mut rect_shape =
                 egui::epaint::RectShape::filled(image_rect, rounding, Color32::WHITE);
-            rect_shape.fill_texture_id = self.texture.id();
+            rect_shape.fill_texture_id = match &self.detail {
+                Some(detail) if !self.anim.animation_enabled => detail.texture.id(),
+                _ => self.texture.id(),
+            };
             rect_shape.uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
             painter.add(rect_shape);
         }
@@ -670,6 +1551,118 @@ impl ImageBlock {
                     Color32::WHITE,
                 );
             }
+
+            if self.has_poster_button() {
+                let poster_rect = poster_button_rect(rect, config.zoom);
+                let color = if config.hover_state.poster_hovered {
+                    COLOR_POSTER_BUTTON_HOVER
+                } else if self.poster.is_some() {
+                    COLOR_POSTER_BUTTON_ACTIVE
+                } else {
+                    COLOR_POSTER_BUTTON
+                };
+                painter.circle_filled(poster_rect.center(), btn_size / 2.0, color);
+                painter.text(
+                    poster_rect.center(),
+                    Align2::CENTER_CENTER,
+                    "p",
+                    FontId::monospace(BUTTON_ICON_FONT_SIZE * config.zoom),
+                    Color32::WHITE,
+                );
+
+                let speed_rect = speed_button_rect(rect, config.zoom);
+                let color = if config.hover_state.speed_hovered {
+                    COLOR_SPEED_BUTTON_HOVER
+                } else if self.has_custom_playback() {
+                    COLOR_SPEED_BUTTON_ACTIVE
+                } else {
+                    COLOR_SPEED_BUTTON
+                };
+                painter.circle_filled(speed_rect.center(), btn_size / 2.0, color);
+                painter.text(
+                    speed_rect.center(),
+                    Align2::CENTER_CENTER,
+                    ">",
+                    FontId::monospace(BUTTON_ICON_FONT_SIZE * config.zoom),
+                    Color32::WHITE,
+                );
+            }
+
+            if show_scrub_bar {
+                let video_rects = VideoControlRects::new(rect, config.zoom, self.has_sound());
+                let trim = self.video_trim;
+                let buttons = [
+                    (
+                        video_rects.trim_in,
+                        "[",
+                        config.hover_state.trim_in_hovered,
+                        trim.is_some_and(|t| t.start > 0.0),
+                    ),
+                    (
+                        video_rects.trim_out,
+                        "]",
+                        config.hover_state.trim_out_hovered,
+                        trim.is_some_and(|t| t.end.is_some()),
+                    ),
+                ];
+                for (button_rect, icon, hovered, is_set) in buttons {
+                    let color = if hovered {
+                        COLOR_TRIM_BUTTON_HOVER
+                    } else if is_set {
+                        COLOR_TRIM_BUTTON_ACTIVE
+                    } else {
+                        COLOR_TRIM_BUTTON
+                    };
+                    painter.circle_filled(button_rect.center(), btn_size / 2.0, color);
+                    painter.text(
+                        button_rect.center(),
+                        Align2::CENTER_CENTER,
+                        icon,
+                        FontId::monospace(BUTTON_ICON_FONT_SIZE * config.zoom),
+                        Color32::WHITE,
+                    );
+                }
+
+                let clip_color = if config.hover_state.clip_hovered {
+                    COLOR_CLIP_BUTTON_HOVER
+                } else if self.exporting_clip {
+                    COLOR_CLIP_BUTTON_ACTIVE
+                } else {
+                    COLOR_CLIP_BUTTON
+                };
+                painter.circle_filled(video_rects.clip.center(), btn_size / 2.0, clip_color);
+                painter.text(
+                    video_rects.clip.center(),
+                    Align2::CENTER_CENTER,
+                    "g",
+                    FontId::monospace(BUTTON_ICON_FONT_SIZE * config.zoom),
+                    Color32::WHITE,
+                );
+
+                if let Some(sound_rect) = video_rects.sound {
+                    let color = if config.hover_state.sound_hovered {
+                        COLOR_SOUND_BUTTON_HOVER
+                    } else if self.audio_muted {
+                        COLOR_SOUND_BUTTON_MUTED
+                    } else if self.anim.video.as_ref().is_some_and(|h| h.is_audible()) {
+                        COLOR_SOUND_BUTTON_ACTIVE
+                    } else {
+                        COLOR_SOUND_BUTTON
+                    };
+                    painter.circle_filled(sound_rect.center(), btn_size / 2.0, color);
+                    painter.text(
+                        sound_rect.center(),
+                        Align2::CENTER_CENTER,
+                        "s",
+                        FontId::monospace(BUTTON_ICON_FONT_SIZE * config.zoom),
+                        Color32::WHITE,
+                    );
+                }
+            }
+        }
+
+        if show_scrub_bar {
+            self.render_scrub_bar(ui, &painter, scrub_bar_rect(rect, config.zoom), config);
         }
 
         if !self.group.is_group && self.counter > 0 {
@@ -702,7 +1695,10 @@ impl ImageBlock {
                     .and_then(|n| n.to_str())
                     .unwrap_or("unnamed");
                 let size_mb = self.file_size as f64 / 1_048_576.0;
-                format!("{}  {:.2} MB", name, size_mb)
+                match &self.video_metadata {
+                    Some(meta) => format!("{}  {:.2} MB  {}", name, size_mb, meta.summary()),
+                    None => format!("{}  {:.2} MB", name, size_mb),
+                }
             };
 
             let font_id = FontId::proportional(LABEL_FONT_SIZE * config.zoom);
@@ -712,8 +1708,13 @@ impl ImageBlock {
 
             let padding = LABEL_PADDING * config.zoom;
             let expansion = LABEL_BG_EXPANSION * config.zoom;
-            let text_pos =
-                image_rect.left_bottom() + vec2(padding, -(galley.size().y + padding + expansion));
+            // Keep the label clear of the scrub bar.
+            let bottom_left = if show_scrub_bar {
+                pos2(image_rect.min.x, scrub_bar_rect(rect, config.zoom).min.y)
+            } else {
+                image_rect.left_bottom()
+            };
+            let text_pos = bottom_left + vec2(padding, -(galley.size().y + padding + expansion));
             let text_rect = Rect::from_min_size(text_pos, galley.size());
 
             painter.rect_filled(
@@ -724,6 +1725,79 @@ impl ImageBlock {
             painter.galley(text_pos, galley, Color32::WHITE);
         }
     }
+
+    /// Draws a video block's scrub bar with the current position and the video length.
+    fn render_scrub_bar(
+        &self,
+        ui: &egui::Ui,
+        painter: &egui::Painter,
+        hit_rect: Rect,
+        config: BlockRenderConfig,
+    ) {
+        let duration = self.video_duration();
+        let position = self.video_current_position();
+
+        let track = Rect::from_center_size(
+            hit_rect.center(),
+            vec2(hit_rect.width(), SCRUB_BAR_HEIGHT * config.zoom),
+        );
+        let rounding = egui::Rounding::same(track.height() / 2.0);
+        painter.rect_filled(track, rounding, COLOR_SCRUB_TRACK);
+
+        if let Some(duration) = duration {
+            let fraction = (position / duration).clamp(0.0, 1.0) as f32;
+            let progress_x = track.min.x + track.width() * fraction;
+            let progress = Rect::from_min_max(track.min, pos2(progress_x, track.max.y));
+            painter.rect_filled(progress, rounding, COLOR_SCRUB_PROGRESS);
+            if let Some(trim) = self.video_trim {
+                let ends = [Some(trim.start).filter(|s| *s > 0.0), trim.end];
+                for seconds in ends.into_iter().flatten() {
+                    let x =
+                        track.min.x + track.width() * (seconds / duration).clamp(0.0, 1.0) as f32;
+                    painter.rect_filled(
+                        Rect::from_center_size(
+                            pos2(x, track.center().y),
+                            vec2(TRIM_MARKER_WIDTH * config.zoom, track.height() * 3.0),
+                        ),
+                        0.0,
+                        COLOR_TRIM_MARKER,
+                    );
+                }
+            }
+            if config.hover_state.scrub_hovered {
+                painter.circle_filled(
+                    pos2(progress_x, track.center().y),
+                    track.height() * 1.5,
+                    COLOR_SCRUB_PROGRESS,
+                );
+            }
+        }
+
+        let time_text = match duration {
+            Some(duration) => format!(
+                "{} / {}",
+                crate::video_stream::format_timestamp(position),
+                crate::video_stream::format_timestamp(duration)
+            ),
+            None => crate::video_stream::format_timestamp(position),
+        };
+        let galley = ui.painter().layout_no_wrap(
+            time_text,
+            FontId::proportional(SCRUB_TIME_FONT_SIZE * config.zoom),
+            Color32::WHITE,
+        );
+        let expansion = LABEL_BG_EXPANSION * config.zoom;
+        let text_pos = pos2(
+            hit_rect.max.x - galley.size().x - expansion,
+            hit_rect.min.y - galley.size().y - expansion,
+        );
+        painter.rect_filled(
+            Rect::from_min_size(text_pos, galley.size()).expand(expansion),
+            egui::Rounding::same(FOLDER_CORNER_RADIUS * config.zoom),
+            Color32::from_black_alpha(COLOR_LABEL_BG_ALPHA),
+        );
+        painter.galley(text_pos, galley, Color32::WHITE);
+    }
 }
 
 /// Returns the index of a block by its ID within a slice, or None if not found.
@@ -814,3 +1888,156 @@ fn color_from_uuid(id: Uuid) -> egui::Color32 {
     let l = UUID_COLOR_LIGHTNESS_MIN + (b[3] as f32 / 255.0) * UUID_COLOR_LIGHTNESS_RANGE;
     egui::Color32::from(egui::epaint::Hsva::new(h, s, l, 1.0))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(ctx: &egui::Context, count: usize) -> ImageBlock {
+        let frames: Vec<AnimationFrame> = (0..count)
+            .map(|_| AnimationFrame {
+                image: egui::ColorImage::new([2, 2], Color32::WHITE),
+                duration: Duration::from_millis(100),
+            })
+            .collect();
+        let texture = ctx.load_texture(
+            "anim",
+            frames[0].image.clone(),
+            egui::TextureOptions::LINEAR,
+        );
+        let mut block = ImageBlock::new(
+            "/i/anim.gif".to_string(),
+            texture,
+            frames,
+            vec2(100.0, 100.0),
+            true,
+            true,
+        );
+        block.toggle_animation();
+        block
+    }
+
+    /// Frames shown over `steps` updates of 100ms.
+    fn play(block: &mut ImageBlock, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                block.update_animation(0.1);
+                block.anim.current_frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_playback_modes() {
+        let ctx = egui::Context::default();
+        let mut block = animation(&ctx, 3);
+        assert_eq!(play(&mut block, 4), [1, 2, 0, 1]);
+
+        let mut block = animation(&ctx, 3);
+        block.playback_mode = PlaybackMode::Reverse;
+        assert_eq!(play(&mut block, 4), [2, 1, 0, 2]);
+
+        let mut block = animation(&ctx, 3);
+        block.playback_mode = PlaybackMode::PingPong;
+        assert_eq!(play(&mut block, 6), [1, 2, 1, 0, 1, 2]);
+
+        // Play-once stops on the last frame; clicking again restarts it.
+        let mut block = animation(&ctx, 3);
+        block.playback_mode = PlaybackMode::Once;
+        assert_eq!(play(&mut block, 4), [1, 2, 2, 2]);
+        assert_eq!(block.time_until_next_frame(), None);
+        block.toggle_animation();
+        assert!(block.anim.animation_enabled);
+        assert_eq!(play(&mut block, 1), [1]);
+    }
+
+    #[test]
+    fn test_loop_mode_streams() {
+        let ctx = egui::Context::default();
+        let mut block = animation(&ctx, 3);
+        block.playback_mode = PlaybackMode::Once;
+        assert!(!block.streams_playback());
+
+        // Looping streams the frames, so the pre-decoded ones are dropped.
+        block.cycle_playback_mode();
+        assert_eq!(block.playback_mode, PlaybackMode::Loop);
+        assert!(block.streams_playback());
+        assert_eq!(block.anim.frames.len(), 1);
+        assert!(!block.is_full_sequence);
+        assert!(!block.anim.animation_enabled);
+    }
+
+    #[test]
+    fn test_detail_tiers() {
+        let ctx = egui::Context::default();
+        let image = egui::ColorImage::new([420, 210], Color32::WHITE);
+        let texture = ctx.load_texture("still", image.clone(), egui::TextureOptions::LINEAR);
+        let frames = vec![AnimationFrame {
+            image,
+            duration: Duration::from_millis(100),
+        }];
+        let mut block = ImageBlock::new(
+            "/i/still.png".to_string(),
+            texture,
+            frames,
+            vec2(420.0, 210.0),
+            false,
+            false,
+        );
+
+        // The regular texture is sharp enough until the block outgrows it.
+        assert_eq!(block.wanted_detail_tier(400.0), None);
+        assert_eq!(block.wanted_detail_tier(900.0), Some(1024));
+        assert_eq!(block.wanted_detail_tier(1500.0), Some(2048));
+        assert_eq!(block.wanted_detail_tier(9000.0), Some(4096));
+
+        let sharp = egui::ColorImage::new([2048, 1024], Color32::WHITE);
+        block.set_detail(&ctx, 2048, sharp);
+        assert_eq!(block.wanted_detail_tier(1500.0), None);
+        assert_eq!(block.wanted_detail_tier(3000.0), Some(4096));
+
+        // A file smaller than the tier is already shown at full resolution.
+        let original = egui::ColorImage::new([1600, 800], Color32::WHITE);
+        block.set_detail(&ctx, 2048, original);
+        assert_eq!(block.wanted_detail_tier(3000.0), None);
+
+        block.release_detail(true, 1500.0);
+        assert!(block.detail.is_some());
+        block.release_detail(false, 1500.0);
+        assert!(block.detail.is_none());
+    }
+
+    #[test]
+    fn test_playback_speed() {
+        let ctx = egui::Context::default();
+        let mut block = animation(&ctx, 4);
+        block.playback_speed = 2.0;
+        assert_eq!(play(&mut block, 1), [2]);
+        assert_eq!(
+            block.time_until_next_frame(),
+            Some(Duration::from_millis(50))
+        );
+
+        block.playback_speed = 0.5;
+        assert_eq!(play(&mut block, 2), [2, 3]);
+
+        // The speed button steps up and wraps around to the slowest speed.
+        block.playback_speed = 1.0;
+        block.cycle_speed();
+        assert_eq!(block.playback_speed, 1.5);
+        block.playback_speed = 4.0;
+        block.cycle_speed();
+        assert_eq!(block.playback_speed, 0.25);
+
+        // Speeds read from a file snap to the offered ones.
+        assert_eq!(nearest_playback_speed(1.4), 1.5);
+        assert_eq!(nearest_playback_speed(f32::INFINITY), 4.0);
+        assert_eq!(nearest_playback_speed(0.0), 1.0);
+        assert_eq!(nearest_playback_speed(-2.0), 1.0);
+        assert_eq!(nearest_playback_speed(f32::NAN), 1.0);
+        assert_eq!(restored_volume(0.5), 0.5);
+        assert_eq!(restored_volume(3.0), 1.0);
+        assert_eq!(restored_volume(-1.0), 0.0);
+        assert_eq!(restored_volume(f32::NAN), 1.0);
+    }
+}
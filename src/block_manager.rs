@@ -5,22 +5,105 @@
 
 use crate::block::ImageBlock;
 use crate::constants::{
-    ALIGN_SPACING, BLOCK_PADDING, CANVAS_PADDING, COLOR_GROUP_PLACEHOLDER, MAX_CACHED_ANIMATIONS,
-    MIN_CANVAS_INNER_WIDTH, ROW_QUANTIZATION_HEIGHT,
+    ALIGN_SPACING, BLOCK_PADDING, CANVAS_PADDING, COLOR_GROUP_PLACEHOLDER,
+    DEFAULT_ANIMATION_BUDGET_MB, MIN_CANVAS_INNER_WIDTH, ROW_QUANTIZATION_HEIGHT,
 };
+use crate::history::{BlockLayout, BlockPool, Snapshot};
 use eframe::egui::{self, pos2, vec2, Pos2};
+use std::cmp::Ordering;
 use std::collections::HashSet;
 use uuid::Uuid;
 
 /// A set of block IDs representing a chain group.
 pub type ChainedIds = HashSet<Uuid>;
 
+/// Property used to order the non-group blocks on the canvas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    FileSize,
+    /// Video length; blocks without a known duration go last.
+    Duration,
+    /// Video pixel count; blocks without video metadata go last.
+    Resolution,
+}
+
+impl SortKey {
+    pub const ALL: [SortKey; 4] = [
+        SortKey::Name,
+        SortKey::FileSize,
+        SortKey::Duration,
+        SortKey::Resolution,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortKey::Name => "Name",
+            SortKey::FileSize => "File Size",
+            SortKey::Duration => "Duration",
+            SortKey::Resolution => "Resolution",
+        }
+    }
+
+    /// Compares two non-group blocks in ascending order of this key, by name on ties.
+    fn compare(self, a: &ImageBlock, b: &ImageBlock) -> Ordering {
+        let by_key = match self {
+            SortKey::Name => Ordering::Equal,
+            SortKey::FileSize => a.file_size.cmp(&b.file_size),
+            SortKey::Duration => {
+                let duration =
+                    |block: &ImageBlock| block.video_metadata.as_ref().and_then(|m| m.duration);
+                cmp_known_first(duration(a), duration(b), |x, y| x.total_cmp(y))
+            }
+            SortKey::Resolution => {
+                let pixels = |block: &ImageBlock| {
+                    block
+                        .video_metadata
+                        .as_ref()
+                        .map(|m| u64::from(m.width) * u64::from(m.height))
+                };
+                cmp_known_first(pixels(a), pixels(b), Ord::cmp)
+            }
+        };
+        by_key.then_with(|| sort_name(a).cmp(&sort_name(b)))
+    }
+}
+
+/// Orders present values with `cmp`, placing absent ones after them.
+fn cmp_known_first<T>(a: Option<T>, b: Option<T>, cmp: impl Fn(&T, &T) -> Ordering) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => cmp(&a, &b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Lowercased file name of a block, used for name ordering.
+fn sort_name(block: &ImageBlock) -> String {
+    std::path::Path::new(&block.path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Converts megabytes (MiB) to bytes.
+fn mb_to_bytes(mb: u32) -> usize {
+    mb as usize * 1024 * 1024
+}
+
 /// Manages the collection of blocks with operations for lookup, chaining, grouping, and layout.
 pub struct BlockManager {
     blocks: Vec<ImageBlock>,
     next_block_id: usize,
     remembered_chains: Vec<ChainedIds>,
     animation_access_order: Vec<Uuid>,
+    /// Bytes the animations in `animation_access_order` may take (see
+    /// `ImageBlock::animation_bytes`) before the least recently used are purged.
+    animation_budget: usize,
+    /// Blocks removed from the canvas since the last `take_detached` call. The undo
+    /// history parks them so removals can be reverted without reloading textures.
+    detached: Vec<ImageBlock>,
 }
 
 #[allow(dead_code)]
@@ -32,6 +115,8 @@ impl BlockManager {
             next_block_id: 0,
             remembered_chains: Vec::new(),
             animation_access_order: Vec::new(),
+            animation_budget: mb_to_bytes(DEFAULT_ANIMATION_BUDGET_MB),
+            detached: Vec::new(),
         }
     }
 
@@ -152,6 +237,7 @@ impl BlockManager {
             self.animation_access_order.retain(|&x| x != *id);
         }
 
+        self.detached.push(block);
         removed_ids
     }
 
@@ -185,6 +271,7 @@ impl BlockManager {
                 }
             }
             collect_child_ids(&block, &mut all_removed_ids);
+            self.detached.push(block);
         }
 
         // Clean up remembered chains that contain any removed blocks
@@ -204,6 +291,18 @@ impl BlockManager {
     pub fn clear(&mut self) {
         self.blocks.clear();
         self.animation_access_order.clear();
+        self.detached.clear();
+    }
+
+    /// Returns the blocks removed by cascade removal or unboxing since the last
+    /// `take_detached` call.
+    pub fn detached(&self) -> &[ImageBlock] {
+        &self.detached
+    }
+
+    /// Takes the blocks removed by cascade removal or unboxing since the last call.
+    pub fn take_detached(&mut self) -> Vec<ImageBlock> {
+        std::mem::take(&mut self.detached)
     }
 
     // ─────────────────────────────────────────────────────────────────────────────
@@ -220,10 +319,43 @@ impl BlockManager {
         // Remove if exists and push to back (most recent)
         self.animation_access_order.retain(|&x| x != id);
         self.animation_access_order.push(id);
+        self.enforce_animation_budget();
+    }
+
+    /// Returns the memory taken by the animations in the cache.
+    pub fn animation_memory_bytes(&self) -> usize {
+        // Called every frame; a set keeps it linear in the number of blocks.
+        let cached: HashSet<Uuid> = self.animation_access_order.iter().copied().collect();
+        self.blocks
+            .iter()
+            .filter(|b| cached.contains(&b.id))
+            .map(ImageBlock::animation_bytes)
+            .sum()
+    }
 
-        // If we exceed cache size, purge the oldest (front)
-        if self.animation_access_order.len() > MAX_CACHED_ANIMATIONS {
+    /// Returns the animation memory budget in MB.
+    pub fn animation_budget_mb(&self) -> u32 {
+        (self.animation_budget / mb_to_bytes(1)) as u32
+    }
+
+    /// Sets the animation memory budget in MB, purging animations beyond it.
+    pub fn set_animation_budget_mb(&mut self, mb: u32) {
+        self.animation_budget = mb_to_bytes(mb);
+        self.enforce_animation_budget();
+    }
+
+    /// Purges the least recently used animations (front) until the rest fit in the
+    /// budget. The most recent one is kept even if it alone exceeds the budget. Runs
+    /// every frame, so the total is computed once and reduced by what each purge frees.
+    pub fn enforce_animation_budget(&mut self) {
+        if self.animation_access_order.len() <= 1 {
+            return;
+        }
+        let mut total = self.animation_memory_bytes();
+        while self.animation_access_order.len() > 1 && total > self.animation_budget {
             let to_purge_id = self.animation_access_order.remove(0);
+            let freed = self.get(to_purge_id).map_or(0, ImageBlock::animation_bytes);
+            total = total.saturating_sub(freed);
             self.purge_animation_frames(to_purge_id);
         }
     }
@@ -231,16 +363,7 @@ impl BlockManager {
     /// Purges animation frames for a block, keeping only the first frame.
     fn purge_animation_frames(&mut self, id: Uuid) {
         if let Some(block) = self.get_mut(id) {
-            if block.anim.video.is_some() {
-                // Drop the handle → Sender disconnect → decoder thread shuts down.
-                block.anim.video = None;
-                block.is_full_sequence = false;
-                block.stop_animation();
-            } else if block.is_full_sequence && block.anim.frames.len() > 1 {
-                block.anim.frames.truncate(1);
-                block.is_full_sequence = false;
-                block.stop_animation();
-            }
+            block.purge_animation_frames();
         }
     }
 
@@ -388,7 +511,7 @@ impl BlockManager {
     /// Unboxes a group at the specified index, inserting its children back into the block list.
     /// Returns the IDs of the unboxed children.
     pub fn unbox_group(&mut self, index: usize) -> Vec<Uuid> {
-        let group = self.blocks.remove(index);
+        let mut group = self.blocks.remove(index);
         let mut unboxed_ids = Vec::new();
 
         if group.group.is_group {
@@ -398,13 +521,16 @@ impl BlockManager {
                 .position(|b| !b.group.is_group)
                 .unwrap_or(self.blocks.len());
 
-            for (i, mut child) in group.group.children.into_iter().enumerate() {
+            let children = std::mem::take(&mut group.group.children);
+            for (i, mut child) in children.into_iter().enumerate() {
                 unboxed_ids.push(child.id);
                 child.chained = false;
                 self.blocks.insert(insert_idx + i, child);
             }
         }
 
+        // Keep the emptied group so undo can restore it around its children again.
+        self.detached.push(group);
         unboxed_ids
     }
 
@@ -568,6 +694,18 @@ impl BlockManager {
         self.reflow(inner_width);
     }
 
+    /// Sorts the non-group blocks by `key` (Boxes stay first, in their order) and reflows.
+    pub fn sort_blocks(&mut self, key: SortKey, inner_width: f32) {
+        self.blocks
+            .sort_by(|a, b| match (a.group.is_group, b.group.is_group) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (false, false) => key.compare(a, b),
+            });
+        self.reflow(inner_width);
+    }
+
     /// Finds the insertion index for a block based on its position.
     fn find_insert_index(
         remaining: &[ImageBlock],
@@ -600,6 +738,71 @@ impl BlockManager {
         leader_y_q < block_y_q || (leader_y_q == block_y_q && leader_pos.x < block_pos.x)
     }
 
+    // ─────────────────────────────────────────────────────────────────────────────
+    // Undo Snapshots
+    // ─────────────────────────────────────────────────────────────────────────────
+
+    /// Captures the current layout (order, sizes, chains, groups, counters) for undo.
+    pub fn snapshot(&self) -> Snapshot {
+        fn layout_of(block: &ImageBlock) -> BlockLayout {
+            BlockLayout {
+                id: block.id,
+                preferred_image_size: block.preferred_image_size,
+                chained: block.chained,
+                counter: block.counter,
+                counter_start_day: block.counter_start_day,
+                group_name: block.group.group_name.clone(),
+                children: block.group.children.iter().map(layout_of).collect(),
+            }
+        }
+
+        Snapshot {
+            blocks: self.blocks.iter().map(layout_of).collect(),
+            remembered_chains: self.remembered_chains.clone(),
+        }
+    }
+
+    /// Rebuilds the block list to match `snapshot`. Every current block is moved into
+    /// `pool` first, then blocks are taken back out by ID, so textures and decoded frames
+    /// are reused. Blocks the snapshot does not mention stay in the pool. Positions are
+    /// not restored; callers reflow afterwards.
+    pub fn restore_snapshot(&mut self, snapshot: &Snapshot, pool: &mut BlockPool) {
+        fn rebuild(layout: &BlockLayout, pool: &mut BlockPool) -> Option<ImageBlock> {
+            let mut block = pool.take(layout.id)?;
+            block.set_preferred_size(layout.preferred_image_size);
+            block.pos.is_dragging = false;
+            block.chained = layout.chained;
+            block.counter = layout.counter;
+            block.counter_start_day = layout.counter_start_day;
+            if block.group.is_group {
+                block.group.group_name = layout.group_name.clone();
+                block.group.children = layout
+                    .children
+                    .iter()
+                    .filter_map(|child| rebuild(child, pool))
+                    .collect();
+                if let Some(first) = block.group.children.first() {
+                    block.group.representative_texture = Some(first.texture.clone());
+                }
+            }
+            Some(block)
+        }
+
+        for block in self.blocks.drain(..).chain(self.detached.drain(..)) {
+            pool.insert(block);
+        }
+        self.blocks = snapshot
+            .blocks
+            .iter()
+            .filter_map(|layout| rebuild(layout, pool))
+            .collect();
+        self.remembered_chains = snapshot.remembered_chains.clone();
+
+        let on_canvas: HashSet<Uuid> = self.block_ids().collect();
+        self.animation_access_order
+            .retain(|id| on_canvas.contains(id));
+    }
+
     // ─────────────────────────────────────────────────────────────────────────────
     // Utility Operations
     // ─────────────────────────────────────────────────────────────────────────────
@@ -626,6 +829,100 @@ impl Default for BlockManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::image_loader::AnimationFrame;
+    use crate::video_stream::VideoMetadata;
+
+    fn block(ctx: &egui::Context, name: &str, metadata: Option<(f64, u32)>) -> ImageBlock {
+        let texture = ctx.load_texture(
+            name,
+            egui::ColorImage::new([2, 2], egui::Color32::WHITE),
+            egui::TextureOptions::LINEAR,
+        );
+        let mut block = ImageBlock::new(
+            name.to_string(),
+            texture,
+            Vec::new(),
+            vec2(100.0, 50.0),
+            false,
+            false,
+        );
+        block.video_metadata = metadata.map(|(duration, height)| VideoMetadata {
+            duration: Some(duration),
+            width: height * 16 / 9,
+            height,
+            ..Default::default()
+        });
+        block
+    }
+
+    #[test]
+    fn test_sort_blocks() {
+        let ctx = egui::Context::default();
+        let mut manager = BlockManager::new();
+        manager.push(block(&ctx, "/v/b.webm", Some((30.0, 720))));
+        manager.push(block(&ctx, "/i/C.png", None));
+        manager.push(block(&ctx, "/v/a.mp4", Some((90.0, 360))));
+        manager.push(block(&ctx, "/v/d.mkv", Some((5.0, 1080))));
+        let names =
+            |m: &BlockManager| -> Vec<String> { m.blocks().iter().map(sort_name).collect() };
+
+        manager.sort_blocks(SortKey::Name, 1000.0);
+        assert_eq!(names(&manager), ["a.mp4", "b.webm", "c.png", "d.mkv"]);
+        manager.sort_blocks(SortKey::Duration, 1000.0);
+        assert_eq!(names(&manager), ["d.mkv", "b.webm", "a.mp4", "c.png"]);
+        manager.sort_blocks(SortKey::Resolution, 1000.0);
+        assert_eq!(names(&manager), ["a.mp4", "b.webm", "d.mkv", "c.png"]);
+        assert!(manager.blocks()[0].pos.position.x < manager.blocks()[1].pos.position.x);
+    }
+
+    #[test]
+    fn test_animation_budget() {
+        let ctx = egui::Context::default();
+        // Three 512×512 frames: 2 MB held beyond the first frame.
+        let animation = |name: &str| {
+            let frames: Vec<AnimationFrame> = (0..3)
+                .map(|_| AnimationFrame {
+                    image: egui::ColorImage::new([512, 512], egui::Color32::WHITE),
+                    duration: std::time::Duration::from_millis(100),
+                })
+                .collect();
+            let texture =
+                ctx.load_texture(name, frames[0].image.clone(), egui::TextureOptions::LINEAR);
+            ImageBlock::new(
+                name.to_string(),
+                texture,
+                frames,
+                vec2(512.0, 512.0),
+                true,
+                true,
+            )
+        };
+        let mut manager = BlockManager::new();
+        manager.set_animation_budget_mb(5);
+        let ids: Vec<Uuid> = ["a.gif", "b.gif", "c.gif"]
+            .into_iter()
+            .map(|name| {
+                let block = animation(name);
+                let id = block.id;
+                manager.push(block);
+                manager.mark_animation_used(id);
+                id
+            })
+            .collect();
+        let frame_counts = |m: &BlockManager| -> Vec<usize> {
+            m.blocks().iter().map(|b| b.anim.frames.len()).collect()
+        };
+
+        // The third animation pushes the total over 5 MB: the oldest is purged.
+        assert_eq!(frame_counts(&manager), [1, 3, 3]);
+        assert_eq!(manager.animation_memory_bytes(), mb_to_bytes(4));
+        assert_eq!(manager.animation_access_order(), &ids[1..]);
+
+        // The most recent animation is kept even when it alone exceeds the budget.
+        manager.set_animation_budget_mb(1);
+        assert_eq!(frame_counts(&manager), [1, 1, 3]);
+        assert_eq!(manager.animation_budget_mb(), 1);
+    }
 
     #[test]
     fn test_should_insert_before() {
@@ -0,0 +1,387 @@
+//! Portable session bundles: a single zip archive holding the session JSON and every
+//! referenced media file, so a board can be moved between machines.
+//!
+//! Layout of a bundle:
+//! - `session.json` — the session, with block paths rewritten to `media/<file>`
+//! - `media/<file>` — the original image/video bytes, stored uncompressed
+//!
+//! On import, media is extracted into `AppPaths.images` and the relative paths are
+//! rewritten back to absolute ones before the session is applied.
+
+use crate::session::{self, BlockData, Session};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Name of the session document inside a bundle.
+const SESSION_ENTRY: &str = "session.json";
+
+/// Directory prefix for media entries inside a bundle.
+const MEDIA_DIR: &str = "media";
+
+/// Packs `session` and all media it references (including group children) into a zip
+/// archive at `archive_path`. Returns the number of media files written.
+/// Files that no longer exist on disk are skipped and keep their original path.
+/// `progress` is called with the fraction (0..1) of the media bytes copied so far.
+pub fn export_bundle(
+    mut session: Session,
+    archive_path: &Path,
+    progress: &dyn Fn(f32),
+) -> Result<usize, String> {
+    let mut paths = Vec::new();
+    collect_paths(&session.blocks, &mut paths);
+    let total: u64 = paths
+        .iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter_map(|path| fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum();
+    let mut copied = 0;
+
+    let file = fs::File::create(archive_path)
+        .map_err(|err| format!("Failed to create {}: {err}", archive_path.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let mut taken = HashSet::new();
+    let mut renames = HashMap::new();
+    for path in paths {
+        if renames.contains_key(&path) {
+            continue;
+        }
+        let mut source = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                log::warn!("Skipping missing bundle media {path}: {err}");
+                continue;
+            }
+        };
+        let file_name = Path::new(&path)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unnamed");
+        let entry_name = format!("{MEDIA_DIR}/{}", unique_name(file_name, &taken));
+        taken.insert(entry_name.clone());
+
+        // Streamed, so large videos are never held in memory whole.
+        zip.start_file(entry_name.as_str(), options)
+            .map_err(Into::into)
+            .and_then(|_| copy_with_progress(&mut source, &mut zip, &mut copied, total, progress))
+            .map_err(|err| format!("Failed to write {entry_name}: {err}"))?;
+        renames.insert(path, entry_name);
+    }
+
+    rewrite_paths(&mut session.blocks, &renames);
+    // Watched folders are local to this machine and would not exist where it is imported.
+    session.watched_folders.clear();
+
+    let json = serde_json::to_vec_pretty(&session)
+        .map_err(|err| format!("Failed to serialize session: {err}"))?;
+    zip.start_file(SESSION_ENTRY, options)
+        .and_then(|_| zip.write_all(&json).map_err(Into::into))
+        .map_err(|err| format!("Failed to write {SESSION_ENTRY}: {err}"))?;
+    zip.finish()
+        .map_err(|err| format!("Failed to finish {}: {err}", archive_path.display()))?;
+
+    Ok(renames.len())
+}
+
+/// Copies `source` to `dest` in chunks, adding the bytes to `copied` and reporting
+/// `copied / total` after each chunk.
+fn copy_with_progress(
+    source: &mut impl Read,
+    dest: &mut impl Write,
+    copied: &mut u64,
+    total: u64,
+    progress: &dyn Fn(f32),
+) -> io::Result<()> {
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        dest.write_all(&buffer[..read])?;
+        *copied += read as u64;
+        progress((*copied as f64 / total.max(1) as f64).min(1.0) as f32);
+    }
+}
+
+/// Extracts the media of the bundle at `archive_path` into `images_dir` and returns its
+/// session with every bundled path rewritten to the extracted absolute location.
+/// Media identical to a file already present in `images_dir` reuses that file.
+pub fn import_bundle(archive_path: &Path, images_dir: &Path) -> Result<Session, String> {
+    let file = fs::File::open(archive_path)
+        .map_err(|err| format!("Failed to open {}: {err}", archive_path.display()))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|err| format!("Invalid bundle archive: {err}"))?;
+
+    let mut session = {
+        let entry = archive
+            .by_name(SESSION_ENTRY)
+            .map_err(|err| format!("Bundle has no {SESSION_ENTRY}: {err}"))?;
+        session::session_from_reader(entry)?
+    };
+
+    fs::create_dir_all(images_dir)
+        .map_err(|err| format!("Failed to create {}: {err}", images_dir.display()))?;
+
+    let mut renames = HashMap::new();
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|err| format!("Corrupt bundle entry #{index}: {err}"))?;
+        if entry.is_dir() || !entry.name().starts_with(&format!("{MEDIA_DIR}/")) {
+            continue;
+        }
+        // Only the file name is trusted; anything else in the entry path is dropped so
+        // a crafted archive cannot write outside the images directory.
+        let Some(file_name) = entry
+            .enclosed_name()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+        else {
+            continue;
+        };
+
+        // Streamed into a temporary file first: the size in the entry header is not
+        // trusted, and the content is compared against existing files afterwards.
+        let part = images_dir.join(format!(".{}.part", Uuid::new_v4()));
+        let dest = extract_entry(&mut entry, &part)
+            .and_then(|_| extraction_target(images_dir, &file_name, &part))
+            .and_then(|dest| {
+                if dest.exists() {
+                    fs::remove_file(&part)?;
+                } else {
+                    fs::rename(&part, &dest)?;
+                }
+                Ok(dest)
+            })
+            .map_err(|err| {
+                let _ = fs::remove_file(&part);
+                format!("Failed to extract {}: {err}", entry.name())
+            })?;
+        renames.insert(
+            entry.name().to_string(),
+            dest.to_string_lossy().into_owned(),
+        );
+    }
+
+    rewrite_paths(&mut session.blocks, &renames);
+    Ok(session)
+}
+
+/// Recursively collects the media paths of all blocks, including group children.
+fn collect_paths(blocks: &[BlockData], out: &mut Vec<String>) {
+    for block in blocks {
+        if !block.path.is_empty() {
+            out.push(block.path.clone());
+        }
+        collect_paths(&block.children, out);
+    }
+}
+
+/// Recursively replaces block paths found in `renames`; other paths are left untouched.
+fn rewrite_paths(blocks: &mut [BlockData], renames: &HashMap<String, String>) {
+    for block in blocks {
+        if let Some(new_path) = renames.get(&block.path) {
+            block.path = new_path.clone();
+        }
+        rewrite_paths(&mut block.children, renames);
+    }
+}
+
+/// Returns `file_name`, or `stem-N.ext` for the first N that is not yet in `taken`.
+fn unique_name(file_name: &str, taken: &HashSet<String>) -> String {
+    let is_taken = |name: &str| taken.contains(&format!("{MEDIA_DIR}/{name}"));
+    if !is_taken(file_name) {
+        return file_name.to_string();
+    }
+
+    let path = Path::new(file_name);
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unnamed");
+    let ext = path.extension().and_then(|e| e.to_str());
+    (1..)
+        .map(|n| match ext {
+            Some(ext) => format!("{stem}-{n}.{ext}"),
+            None => format!("{stem}-{n}"),
+        })
+        .find(|name| !is_taken(name))
+        .unwrap()
+}
+
+/// Copies a bundle entry into the new file `dest`.
+fn extract_entry(entry: &mut impl Read, dest: &Path) -> io::Result<()> {
+    let mut file = fs::File::create(dest)?;
+    io::copy(entry, &mut file)?;
+    file.flush()
+}
+
+/// Picks where the extracted media file `extracted` goes: an existing file with identical
+/// content is reused, otherwise the first free `stem-N.ext` name in `dir`.
+fn extraction_target(dir: &Path, file_name: &str, extracted: &Path) -> io::Result<PathBuf> {
+    let path = Path::new(file_name);
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("unnamed");
+    let ext = path.extension().and_then(|e| e.to_str());
+
+    for n in 0.. {
+        let candidate = match (n, ext) {
+            (0, _) => dir.join(file_name),
+            (n, Some(ext)) => dir.join(format!("{stem}-{n}.{ext}")),
+            (n, None) => dir.join(format!("{stem}-{n}")),
+        };
+        if !candidate.exists() {
+            return Ok(candidate);
+        }
+        if same_content(&candidate, extracted)? {
+            return Ok(candidate);
+        }
+    }
+    unreachable!()
+}
+
+/// Returns true if both files have the same bytes, comparing them chunk by chunk.
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut a = BufReader::new(fs::File::open(a)?);
+    let mut b = BufReader::new(fs::File::open(b)?);
+    let mut chunk_a = [0u8; 64 * 1024];
+    let mut chunk_b = [0u8; 64 * 1024];
+    loop {
+        let read = a.read(&mut chunk_a)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut chunk_b[..read])?;
+        if chunk_a[..read] != chunk_b[..read] {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::CURRENT_SCHEMA_VERSION;
+
+    fn block(path: &str, children: Vec<BlockData>) -> BlockData {
+        BlockData {
+            id: Uuid::new_v4(),
+            size: [100.0, 100.0],
+            path: path.to_string(),
+            is_group: !children.is_empty(),
+            children,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let root = std::env::temp_dir().join(format!("ma_blocks_bundle_{}", Uuid::new_v4()));
+        let source = root.join("source");
+        let nested = source.join("nested");
+        let images = root.join("images");
+        fs::create_dir_all(&nested).unwrap();
+
+        // Two different files sharing a name, one of them inside a group.
+        let a = source.join("pic.png");
+        let b = nested.join("pic.png");
+        fs::write(&a, b"first").unwrap();
+        fs::write(&b, b"second").unwrap();
+        let a = a.to_string_lossy().into_owned();
+        let b = b.to_string_lossy().into_owned();
+
+        let session = Session {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            blocks: vec![
+                block(&a, Vec::new()),
+                block("", vec![block(&b, Vec::new())]),
+            ],
+            remembered_chains: Vec::new(),
+            last_unboxed_ids: Vec::new(),
+            last_boxed_id: None,
+            zoom: 1.0,
+            show_file_names: false,
+            watched_folders: Vec::new(),
+            audio_muted: false,
+            exclusive_audio: true,
+            animation_budget_mb: 1024,
+        };
+
+        let archive = root.join("board.zip");
+        let reported = std::cell::Cell::new(0.0);
+        let progress = |done: f32| {
+            assert!(done >= reported.get());
+            reported.set(done);
+        };
+        assert_eq!(export_bundle(session, &archive, &progress).unwrap(), 2);
+        assert_eq!(reported.get(), 1.0);
+        fs::remove_dir_all(&source).unwrap();
+
+        let imported = import_bundle(&archive, &images).unwrap();
+        let top = &imported.blocks[0].path;
+        let child = &imported.blocks[1].children[0].path;
+        assert!(Path::new(top).starts_with(&images));
+        assert!(Path::new(child).starts_with(&images));
+        assert_eq!(fs::read(top).unwrap(), b"first");
+        assert_eq!(fs::read(child).unwrap(), b"second");
+
+        // Importing again reuses the already extracted files.
+        let again = import_bundle(&archive, &images).unwrap();
+        assert_eq!(&again.blocks[0].path, top);
+        assert_eq!(fs::read_dir(&images).unwrap().count(), 2);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_import_skips_entries_outside_media() {
+        let root = std::env::temp_dir().join(format!("ma_blocks_bundle_{}", Uuid::new_v4()));
+        let images = root.join("images");
+        fs::create_dir_all(&root).unwrap();
+
+        let archive = root.join("board.zip");
+        let mut zip = ZipWriter::new(fs::File::create(&archive).unwrap());
+        let options = SimpleFileOptions::default();
+        zip.start_file("mediafoo/evil.png", options).unwrap();
+        zip.write_all(b"evil").unwrap();
+        zip.start_file("media/pic.png", options).unwrap();
+        zip.write_all(b"pic").unwrap();
+        let json = r#"{"blocks": [{
+            "id": "6f1c9a52-7c1f-4b43-9d6e-0a3f7a0a1b01",
+            "position": [0, 0],
+            "size": [1, 1],
+            "path": "media/pic.png",
+            "chained": false,
+            "animation_enabled": false,
+            "counter": 0
+        }]}"#;
+        zip.start_file(SESSION_ENTRY, options).unwrap();
+        zip.write_all(json.as_bytes()).unwrap();
+        zip.finish().unwrap();
+
+        let imported = import_bundle(&archive, &images).unwrap();
+        assert_eq!(fs::read(&imported.blocks[0].path).unwrap(), b"pic");
+        let names: Vec<_> = fs::read_dir(&images)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["pic.png"]);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
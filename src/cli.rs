@@ -0,0 +1,270 @@
+//! Command-line interface: sessions and images to open at startup.
+//!
+//! ```text
+//! ma_blocks2 [--new] [--zoom <factor>] [SESSION.json] [IMAGE | DIR]...
+//! ma_blocks2 export [--scale <factor>] [--width <units>] [--file-names] SESSION.json OUT.png
+//! ```
+//!
+//! Directories are expanded recursively to every file with a supported image/video
+//! extension, in sorted order.
+
+use crate::image_loader;
+use crate::relink;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: ma_blocks2 [OPTIONS] [SESSION.json] [IMAGE | DIR]...
+       ma_blocks2 export [EXPORT OPTIONS] SESSION.json OUTPUT.png
+
+Opens a saved session and/or adds images to the board. Directories are
+searched recursively for supported images and videos.
+
+Options:
+  --new            Start with an empty board instead of restoring the last session
+  --zoom <FACTOR>  Initial zoom level (0.1 - 10.0)
+  -h, --help       Print this help and exit
+
+The export command renders a session to a PNG without opening a window.
+
+Export options:
+  --scale <FACTOR>  Output pixels per canvas unit (default 1.0)
+  --width <UNITS>   Canvas width used for the layout (default 1400)
+  --file-names      Draw file name labels even if the session hides them";
+
+/// Startup options parsed from the command line.
+#[derive(Debug, Default, PartialEq)]
+pub struct CliArgs {
+    /// Session file to open instead of the automatically restored one.
+    pub session: Option<PathBuf>,
+    /// Images to add to the board, with directories already expanded.
+    pub images: Vec<PathBuf>,
+    /// Skip restoring the previous session.
+    pub new_board: bool,
+    pub zoom: Option<f32>,
+}
+
+/// Options of the headless `export` command.
+#[derive(Debug, PartialEq)]
+pub struct ExportArgs {
+    pub session: PathBuf,
+    pub output: PathBuf,
+    pub scale: f32,
+    pub inner_width: Option<f32>,
+    pub show_file_names: bool,
+}
+
+/// What the process should do after parsing its arguments.
+#[derive(Debug, PartialEq)]
+pub enum CliAction {
+    Run(CliArgs),
+    Export(ExportArgs),
+    PrintHelp,
+}
+
+/// Parses the arguments following the program name.
+pub fn parse_args<I: IntoIterator<Item = OsString>>(args: I) -> Result<CliAction, String> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().and_then(|a| a.to_str()) == Some("export") {
+        args.next();
+        return parse_export_args(args);
+    }
+
+    let mut parsed = CliArgs::default();
+    let mut only_paths = false;
+
+    while let Some(arg) = args.next() {
+        if !only_paths {
+            match arg.to_str() {
+                Some("--") => {
+                    only_paths = true;
+                    continue;
+                }
+                Some("-h" | "--help") => return Ok(CliAction::PrintHelp),
+                Some("--new") => {
+                    parsed.new_board = true;
+                    continue;
+                }
+                Some("--zoom") => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| "--zoom requires a value".to_string())?;
+                    parsed.zoom = Some(parse_zoom(&value.to_string_lossy())?);
+                    continue;
+                }
+                Some(flag) if flag.starts_with("--zoom=") => {
+                    parsed.zoom = Some(parse_zoom(&flag["--zoom=".len()..])?);
+                    continue;
+                }
+                Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                    return Err(format!("Unknown option: {flag}"));
+                }
+                _ => {}
+            }
+        }
+        add_path(&mut parsed, PathBuf::from(arg))?;
+    }
+
+    Ok(CliAction::Run(parsed))
+}
+
+fn parse_export_args<I: Iterator<Item = OsString>>(mut args: I) -> Result<CliAction, String> {
+    let mut scale = 1.0;
+    let mut inner_width = None;
+    let mut show_file_names = false;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-h" | "--help") => return Ok(CliAction::PrintHelp),
+            Some("--file-names") => show_file_names = true,
+            Some(flag @ ("--scale" | "--width")) => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{flag} requires a value"))?;
+                let value = value.to_string_lossy();
+                let number = value
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|v| v.is_finite() && *v > 0.0)
+                    .ok_or_else(|| {
+                        format!("Invalid {flag} '{value}': expected a positive number")
+                    })?;
+                if flag == "--scale" {
+                    scale = number;
+                } else {
+                    inner_width = Some(number);
+                }
+            }
+            Some(flag) if flag.starts_with('-') && flag.len() > 1 => {
+                return Err(format!("Unknown export option: {flag}"));
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let [session, output]: [PathBuf; 2] = paths
+        .try_into()
+        .map_err(|_| "export expects a session file and an output PNG path".to_string())?;
+    if !session.is_file() {
+        return Err(format!("No such session file: {}", session.display()));
+    }
+    Ok(CliAction::Export(ExportArgs {
+        session,
+        output,
+        scale,
+        inner_width,
+        show_file_names,
+    }))
+}
+
+fn parse_zoom(value: &str) -> Result<f32, String> {
+    match value.parse::<f32>() {
+        Ok(zoom) if (0.1..=10.0).contains(&zoom) => Ok(zoom),
+        _ => Err(format!(
+            "Invalid zoom '{value}': expected a number from 0.1 to 10"
+        )),
+    }
+}
+
+fn add_path(parsed: &mut CliArgs, path: PathBuf) -> Result<(), String> {
+    if path.is_dir() {
+        parsed.images.extend(
+            relink::scan_folder(&path)
+                .into_iter()
+                .filter(|p| image_loader::has_supported_extension(p)),
+        );
+        return Ok(());
+    }
+    if !path.is_file() {
+        return Err(format!("No such file or directory: {}", path.display()));
+    }
+
+    if is_session_file(&path) {
+        if parsed.session.is_some() {
+            return Err("Only one session file can be opened".to_string());
+        }
+        parsed.session = Some(path);
+    } else if image_loader::has_supported_extension(&path) {
+        parsed.images.push(path);
+    } else {
+        return Err(format!("Unsupported file type: {}", path.display()));
+    }
+    Ok(())
+}
+
+fn is_session_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use uuid::Uuid;
+
+    fn parse(args: &[&str]) -> Result<CliAction, String> {
+        parse_args(args.iter().map(OsString::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let root = std::env::temp_dir().join(format!("ma_blocks_cli_{}", Uuid::new_v4()));
+        fs::create_dir_all(root.join("dir/nested")).unwrap();
+        for file in [
+            "board.json",
+            "a.png",
+            "dir/b.GIF",
+            "dir/notes.txt",
+            "dir/nested/c.mp4",
+        ] {
+            fs::write(root.join(file), b"").unwrap();
+        }
+        let path = |p: &str| root.join(p).to_string_lossy().into_owned();
+
+        let action = parse(&[
+            "--new",
+            "--zoom",
+            "1.5",
+            &path("board.json"),
+            &path("a.png"),
+            &path("dir"),
+        ])
+        .unwrap();
+        assert_eq!(
+            action,
+            CliAction::Run(CliArgs {
+                session: Some(root.join("board.json")),
+                images: vec![
+                    root.join("a.png"),
+                    root.join("dir/b.GIF"),
+                    root.join("dir/nested/c.mp4"),
+                ],
+                new_board: true,
+                zoom: Some(1.5),
+            })
+        );
+
+        assert_eq!(parse(&["-h"]).unwrap(), CliAction::PrintHelp);
+        assert!(parse(&["--zoom=50"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+        assert!(parse(&[&path("dir/notes.txt")]).is_err());
+        assert!(parse(&[&path("missing.png")]).is_err());
+
+        assert_eq!(
+            parse(&["export", "--scale", "2", &path("board.json"), "out.png"]).unwrap(),
+            CliAction::Export(ExportArgs {
+                session: root.join("board.json"),
+                output: PathBuf::from("out.png"),
+                scale: 2.0,
+                inner_width: None,
+                show_file_names: false,
+            })
+        );
+        assert!(parse(&["export", &path("board.json")]).is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,297 @@
+//! Short excerpts of video blocks saved as animated images.
+//!
+//! A segment is decoded with `video_stream::decode_video_segment`, which downsizes the
+//! frames like playback does (`scaled_output_dims`) and thins them to `CLIP_MAX_FPS`.
+//! The frames are then encoded to an animated GIF with the `image` crate, or to an
+//! animated WebP with ffmpeg's `libwebp_anim` encoder, and written to the app images
+//! folder so the board can reference the clip like any other file.
+
+use crate::constants::{CLIP_DEFAULT_SECS, CLIP_MAX_FPS, CLIP_MAX_SECS};
+use crate::image_loader::AnimationFrame;
+use crate::video_stream::TrimRange;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+/// File format of an exported clip.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipFormat {
+    Gif,
+    WebP,
+}
+
+impl ClipFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::WebP => "webp",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Gif => "GIF",
+            Self::WebP => "WebP",
+        }
+    }
+}
+
+/// Returns the segment (start, end) in seconds to export: the trim range if one is set,
+/// otherwise `CLIP_DEFAULT_SECS` from `position`. Clips end at the end of the video and
+/// are at most `CLIP_MAX_SECS` long.
+pub fn clip_range(trim: Option<TrimRange>, position: f64, duration: Option<f64>) -> (f64, f64) {
+    let start = trim.map_or(position, |t| t.start).max(0.0);
+    let end = trim
+        .and_then(|t| t.end)
+        .unwrap_or(start + CLIP_DEFAULT_SECS)
+        .min(start + CLIP_MAX_SECS);
+    (start, duration.map_or(end, |d| end.min(d)))
+}
+
+/// Returns `<stem>-clip-<start>s.<ext>` in `dir` for a clip of `source` starting at
+/// `start` seconds, with a `-N` suffix if that name is taken.
+pub fn clip_output_path(dir: &Path, source: &Path, start: f64, format: ClipFormat) -> PathBuf {
+    let stem = source
+        .file_stem()
+        .map_or_else(|| "video".into(), |s| s.to_string_lossy());
+    let base = format!("{stem}-clip-{start:.1}s");
+    let ext = format.extension();
+    std::iter::once(dir.join(format!("{base}.{ext}")))
+        .chain((1..).map(|n| dir.join(format!("{base}-{n}.{ext}"))))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+/// Decodes `start..end` of the video at `source` and writes it to `output` as an
+/// animated image. A partly written file is removed on failure.
+pub fn export_clip(
+    source: &Path,
+    start: f64,
+    end: f64,
+    format: ClipFormat,
+    output: &Path,
+) -> Result<(), String> {
+    let frames = crate::video_stream::decode_video_segment(source, start, end, CLIP_MAX_FPS)?;
+    let written = match format {
+        ClipFormat::Gif => write_gif(&frames, output),
+        ClipFormat::WebP => write_webp(&frames, output),
+    };
+    if written.is_err() {
+        let _ = std::fs::remove_file(output);
+    }
+    written
+}
+
+/// Unmultiplied RGBA bytes of a frame.
+fn frame_rgba(frame: &AnimationFrame) -> Vec<u8> {
+    frame
+        .image
+        .pixels
+        .iter()
+        .flat_map(|p| p.to_srgba_unmultiplied())
+        .collect()
+}
+
+/// Writes `frames` as a GIF that loops forever.
+fn write_gif(frames: &[AnimationFrame], output: &Path) -> Result<(), String> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+
+    let file = File::create(output)
+        .map_err(|err| format!("Failed to create {}: {err}", output.display()))?;
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+    encoder
+        .set_repeat(Repeat::Infinite)
+        .map_err(|err| format!("Failed to write {}: {err}", output.display()))?;
+    for frame in frames {
+        let [w, h] = frame.image.size;
+        let buffer = image::RgbaImage::from_raw(w as u32, h as u32, frame_rgba(frame))
+            .ok_or("Frame size does not match its pixels")?;
+        let delay = image::Delay::from_saturating_duration(frame.duration);
+        encoder
+            .encode_frame(image::Frame::from_parts(buffer, 0, 0, delay))
+            .map_err(|err| format!("Failed to write {}: {err}", output.display()))?;
+    }
+    Ok(())
+}
+
+/// Writes `frames` as an animated WebP that loops forever. Requires an ffmpeg built with
+/// libwebp.
+fn write_webp(frames: &[AnimationFrame], output: &Path) -> Result<(), String> {
+    use ff::format::Pixel;
+    use ffmpeg_next as ff;
+
+    let codec = ff::encoder::find_by_name("libwebp_anim")
+        .ok_or("Animated WebP is not supported by this ffmpeg build (no libwebp_anim)")?;
+    let [w, h] = frames.first().ok_or("No frames to encode")?.image.size;
+    let (w, h) = (w as u32, h as u32);
+    // Frame timestamps are in milliseconds, like WebP frame durations.
+    let time_base = ff::Rational::new(1, 1000);
+    let failed = |err: ff::Error| format!("Failed to write {}: {err}", output.display());
+
+    let mut muxer = ff::format::output_as(output, "webp").map_err(failed)?;
+    let mut encoder = ff::codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()
+        .map_err(failed)?;
+    encoder.set_width(w);
+    encoder.set_height(h);
+    encoder.set_format(Pixel::YUV420P);
+    encoder.set_time_base(time_base);
+    let mut encoder = encoder.open_as(codec).map_err(failed)?;
+    muxer
+        .add_stream(codec)
+        .map_err(failed)?
+        .set_parameters(&encoder);
+    let mut options = ff::Dictionary::new();
+    // The muxer plays the animation once unless told otherwise.
+    options.set("loop", "0");
+    muxer.write_header_with(options).map_err(failed)?;
+    let stream_time_base = muxer.stream(0).map_or(time_base, |s| s.time_base());
+
+    let mut scaler = ff::software::scaling::Context::get(
+        Pixel::RGBA,
+        w,
+        h,
+        Pixel::YUV420P,
+        w,
+        h,
+        ff::software::scaling::Flags::BILINEAR,
+    )
+    .map_err(failed)?;
+    let mut rgba = ff::frame::Video::new(Pixel::RGBA, w, h);
+    let mut yuv = ff::frame::Video::empty();
+    let mut pts = 0i64;
+    for frame in frames {
+        if frame.image.size != [w as usize, h as usize] {
+            return Err("Frames of a clip must have the same size".to_string());
+        }
+        // ffmpeg rows may be padded to their alignment.
+        let stride = rgba.stride(0);
+        let row_bytes = w as usize * 4;
+        let pixels = frame_rgba(frame);
+        for (dst, src) in rgba
+            .data_mut(0)
+            .chunks_mut(stride)
+            .zip(pixels.chunks_exact(row_bytes))
+        {
+            dst[..row_bytes].copy_from_slice(src);
+        }
+        scaler.run(&rgba, &mut yuv).map_err(failed)?;
+        yuv.set_pts(Some(pts));
+        pts += frame.duration.as_millis().max(1) as i64;
+        encoder.send_frame(&yuv).map_err(failed)?;
+        write_packets(&mut encoder, &mut muxer, time_base, stream_time_base).map_err(failed)?;
+    }
+    encoder.send_eof().map_err(failed)?;
+    write_packets(&mut encoder, &mut muxer, time_base, stream_time_base).map_err(failed)?;
+    muxer.write_trailer().map_err(failed)
+}
+
+/// Moves the packets the encoder has ready into the muxer.
+fn write_packets(
+    encoder: &mut ffmpeg_next::encoder::Video,
+    muxer: &mut ffmpeg_next::format::context::Output,
+    time_base: ffmpeg_next::Rational,
+    stream_time_base: ffmpeg_next::Rational,
+) -> Result<(), ffmpeg_next::Error> {
+    let mut packet = ffmpeg_next::Packet::empty();
+    while encoder.receive_packet(&mut packet).is_ok() {
+        packet.set_stream(0);
+        packet.rescale_ts(time_base, stream_time_base);
+        packet.write_interleaved(muxer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_clip_range() {
+        // Without a trim range: the default length from the shown position.
+        assert_eq!(
+            clip_range(None, 2.0, Some(60.0)),
+            (2.0, 2.0 + CLIP_DEFAULT_SECS)
+        );
+        // ...cut at the end of the video.
+        assert_eq!(clip_range(None, 59.0, Some(60.0)), (59.0, 60.0));
+
+        let trim = |start, end| Some(TrimRange { start, end });
+        assert_eq!(clip_range(trim(1.0, Some(4.5)), 30.0, None), (1.0, 4.5));
+        assert_eq!(
+            clip_range(trim(5.0, None), 30.0, Some(60.0)),
+            (5.0, 5.0 + CLIP_DEFAULT_SECS)
+        );
+        // Long ranges are cut to the maximum clip length.
+        assert_eq!(
+            clip_range(trim(0.0, Some(100.0)), 0.0, Some(120.0)),
+            (0.0, CLIP_MAX_SECS)
+        );
+    }
+
+    #[test]
+    fn test_gif_clip_round_trip() {
+        let root = std::env::temp_dir().join(format!("ma_blocks_clip_{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&root).unwrap();
+        let source = Path::new("/videos/holiday.mp4");
+
+        let output = clip_output_path(&root, source, 1.5, ClipFormat::Gif);
+        assert_eq!(output, root.join("holiday-clip-1.5s.gif"));
+
+        let frames: Vec<AnimationFrame> = [egui::Color32::RED, egui::Color32::BLUE]
+            .into_iter()
+            .map(|color| AnimationFrame {
+                image: egui::ColorImage::new([6, 4], color),
+                duration: Duration::from_millis(100),
+            })
+            .collect();
+        write_gif(&frames, &output).unwrap();
+        assert_eq!(
+            clip_output_path(&root, source, 1.5, ClipFormat::Gif),
+            root.join("holiday-clip-1.5s-1.gif")
+        );
+
+        let loaded = crate::image_loader::load_image_frames_scaled(&output, None, false).unwrap();
+        assert!(loaded.has_animation);
+        assert_eq!(loaded.frames.len(), 2);
+        assert_eq!(loaded.frames[0].image.size, [6, 4]);
+        assert_eq!(loaded.frames[1].duration, Duration::from_millis(100));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_webp_clip_round_trip() {
+        ffmpeg_next::init().unwrap();
+        // Only ffmpeg builds with libwebp can write WebP clips.
+        if ffmpeg_next::encoder::find_by_name("libwebp_anim").is_none() {
+            return;
+        }
+        let output = std::env::temp_dir().join(format!("ma_blocks_clip_{}.webp", Uuid::new_v4()));
+
+        let frames: Vec<AnimationFrame> = [egui::Color32::RED, egui::Color32::BLUE]
+            .into_iter()
+            .map(|color| AnimationFrame {
+                image: egui::ColorImage::new([8, 6], color),
+                duration: Duration::from_millis(100),
+            })
+            .collect();
+        write_webp(&frames, &output).unwrap();
+
+        let loaded = crate::image_loader::load_image_frames_scaled(&output, None, false).unwrap();
+        assert!(loaded.has_animation);
+        assert_eq!(loaded.frames.len(), 2);
+        assert_eq!(loaded.frames[0].image.size, [8, 6]);
+        assert_eq!(loaded.frames[0].duration, Duration::from_millis(100));
+        // The encoding is lossy, but the colors survive.
+        let [r, _, b, _] = loaded.frames[0].image.pixels[0].to_array();
+        assert!(r > 200 && b < 60, "{r} {b}");
+        let [r, _, b, _] = loaded.frames[1].image.pixels[0].to_array();
+        assert!(b > 200 && r < 60, "{r} {b}");
+
+        std::fs::remove_file(&output).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+//! Pasting from the system clipboard: files copied in a file manager, or raw bitmaps such
+//! as screenshots, which are saved as PNG files so the board can reference them.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Something on the clipboard that can be added to the board.
+pub enum Pasted {
+    Files(Vec<PathBuf>),
+    /// A bitmap as unmultiplied RGBA pixels, to be written with [`save_image`].
+    Image {
+        size: [usize; 2],
+        rgba: Vec<u8>,
+    },
+}
+
+/// Reads the clipboard, preferring file lists over bitmaps. Returns None if the clipboard
+/// holds neither.
+pub fn read_clipboard() -> Result<Option<Pasted>, String> {
+    let mut clipboard =
+        arboard::Clipboard::new().map_err(|err| format!("Clipboard unavailable: {err}"))?;
+
+    if let Ok(files) = clipboard.get().file_list() {
+        if !files.is_empty() {
+            return Ok(Some(Pasted::Files(files)));
+        }
+    }
+    // Some file managers only offer the copied paths as text.
+    if let Ok(text) = clipboard.get_text() {
+        let files = paths_from_text(&text);
+        if !files.is_empty() {
+            return Ok(Some(Pasted::Files(files)));
+        }
+    }
+
+    let image = match clipboard.get_image() {
+        Ok(image) => image,
+        Err(arboard::Error::ContentNotAvailable) => return Ok(None),
+        Err(err) => return Err(format!("Failed to read clipboard image: {err}")),
+    };
+    Ok(Some(Pasted::Image {
+        size: [image.width, image.height],
+        rgba: image.bytes.into_owned(),
+    }))
+}
+
+/// Saves a pasted bitmap as PNG in `images_dir` and returns its path. Encoding large
+/// screenshots is slow, so callers run this off the UI thread.
+pub fn save_image(images_dir: &Path, size: [usize; 2], rgba: &[u8]) -> Result<PathBuf, String> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let path = unique_png_path(images_dir, stamp);
+    image::save_buffer(
+        &path,
+        rgba,
+        size[0] as u32,
+        size[1] as u32,
+        image::ColorType::Rgba8,
+    )
+    .map_err(|err| format!("Failed to save {}: {err}", path.display()))?;
+    Ok(path)
+}
+
+/// Interprets text as one existing path (or `file://` URI) per line. Returns an empty
+/// list if any line is not an existing path.
+fn paths_from_text(text: &str) -> Vec<PathBuf> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .collect();
+    let paths: Vec<PathBuf> = lines
+        .iter()
+        .map(|line| match line.strip_prefix("file://") {
+            Some(uri) => PathBuf::from(percent_decode(uri)),
+            None => PathBuf::from(line),
+        })
+        .filter(|p| p.is_absolute() && p.exists())
+        .collect();
+    if paths.len() == lines.len() {
+        paths
+    } else {
+        Vec::new()
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Returns `pasted-<stamp>.png` in `dir`, with a `-N` suffix if that name is taken.
+fn unique_png_path(dir: &Path, stamp: u64) -> PathBuf {
+    let stem = format!("pasted-{stamp}");
+    std::iter::once(dir.join(format!("{stem}.png")))
+        .chain((1..).map(|n| dir.join(format!("{stem}-{n}.png"))))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_paste_paths_and_names() {
+        let root = std::env::temp_dir().join(format!("ma_blocks_paste_{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let image = root.join("my shot.png");
+        fs::write(&image, b"").unwrap();
+
+        let uri = format!("file://{}", image.to_string_lossy().replace(' ', "%20"));
+        assert_eq!(paths_from_text(&uri), vec![image.clone()]);
+        assert_eq!(
+            paths_from_text(&format!("{}\n", image.display())),
+            vec![image.clone()]
+        );
+        assert!(paths_from_text("just some text").is_empty());
+
+        let first = unique_png_path(&root, 42);
+        assert_eq!(first, root.join("pasted-42.png"));
+        fs::write(&first, b"").unwrap();
+        assert_eq!(unique_png_path(&root, 42), root.join("pasted-42-1.png"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -40,8 +40,133 @@ pub const MAX_BLOCK_DIMENSION: f32 = 420.0;
 /// Minimum width the canvas can take, ensuring at least one block plus padding can be displayed.
 pub const MIN_CANVAS_INNER_WIDTH: f32 = MIN_BLOCK_SIZE + BLOCK_PADDING * 2.0;
 
-/// Maximum number of animations to keep in memory simultaneously.
-pub const MAX_CACHED_ANIMATIONS: usize = 10;
+/// Memory (MB) the frames of played animations and videos may take before the least
+/// recently played are purged back to their first frame, unless changed in the Memory menu.
+pub const DEFAULT_ANIMATION_BUDGET_MB: u32 = 1024;
+
+/// Animation memory budgets offered in the Memory menu, in MB.
+pub const ANIMATION_BUDGET_CHOICES_MB: [u32; 5] = [256, 512, 1024, 2048, 4096];
+
+/// Number of worker threads decoding image files in the background.
+pub const IMAGE_LOAD_WORKERS: usize = 4;
+
+/// Number of worker threads decoding poster frames of videos and animations.
+pub const POSTER_LOAD_WORKERS: usize = 2;
+
+// =============================================================================
+// UNDO HISTORY CONSTANTS
+// =============================================================================
+
+/// Maximum number of undo steps kept in the history.
+pub const MAX_UNDO_STEPS: usize = 200;
+
+/// Approximate memory budget for the undo history, including the textures and frames of
+/// removed blocks kept alive so they can be restored. Oldest steps are dropped beyond it.
+pub const UNDO_MEMORY_BUDGET_BYTES: usize = 256 * 1024 * 1024;
+
+// =============================================================================
+// AUTOSAVE JOURNAL CONSTANTS
+// =============================================================================
+
+/// Seconds between checks for unjournaled changes (zoom, relinks, layout).
+pub const JOURNAL_SYNC_INTERVAL_SECS: f64 = 2.0;
+
+/// Number of journal entries after which the journal is compacted into a snapshot.
+pub const JOURNAL_COMPACT_ENTRIES: usize = 500;
+
+/// Seconds after which pending journal entries are compacted into a snapshot.
+pub const JOURNAL_COMPACT_INTERVAL_SECS: u64 = 300;
+
+// =============================================================================
+// WATCHED FOLDER CONSTANTS
+// =============================================================================
+
+/// Milliseconds a new file's size must stay unchanged before it is imported, so files
+/// still being written by another program are not loaded half-finished.
+pub const WATCH_SETTLE_MILLIS: u64 = 750;
+
+// =============================================================================
+// THUMBNAIL CACHE CONSTANTS
+// =============================================================================
+
+/// Disk space the thumbnail cache is trimmed to; least recently used entries are removed
+/// first.
+pub const THUMBNAIL_CACHE_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Number of thumbnails written between trims of the cache, besides the one at startup.
+pub const THUMBNAIL_CACHE_TRIM_INTERVAL: usize = 200;
+
+/// Images smaller than this are not cached, as decoding them takes about as long as
+/// reading their thumbnail. Videos are always cached.
+pub const THUMBNAIL_CACHE_MIN_FILE_BYTES: u64 = 256 * 1024;
+
+// =============================================================================
+// DETAIL TEXTURE CONSTANTS
+// =============================================================================
+
+/// Longer-side caps (physical pixels) of the sharper textures decoded for blocks shown
+/// larger than `MAX_BLOCK_DIMENSION`; the smallest tier covering the block is used.
+pub const DETAIL_TIERS: [u32; 3] = [1024, 2048, 4096];
+
+/// Memory the detail textures of all blocks may take together (bytes).
+pub const DETAIL_TEXTURE_BUDGET_BYTES: usize = 512 * 1024 * 1024;
+
+/// Number of worker threads decoding detail textures.
+pub const DETAIL_DECODE_LIMIT: usize = 2;
+
+// =============================================================================
+// VIDEO PLAYBACK CONSTANTS
+// =============================================================================
+
+/// Milliseconds a video frame may be overdue before playback is rescheduled from the
+/// current time instead of catching up.
+pub const VIDEO_MAX_LATENESS_MILLIS: u64 = 100;
+
+/// Number of worker threads shared by all playing videos and streamed animations.
+pub const VIDEO_DECODE_WORKERS: usize = 4;
+
+/// Playback rates the speed button steps through (LMB) for videos and animations.
+pub const PLAYBACK_SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 1.5, 2.0, 4.0];
+
+// =============================================================================
+// AUDIO PLAYBACK CONSTANTS
+// =============================================================================
+
+/// Seconds of audio decoded ahead of what is being heard.
+pub const AUDIO_BUFFER_SECS: f64 = 0.5;
+
+/// Buffered seconds of audio below which more is decoded.
+pub const AUDIO_LOW_WATER_SECS: f64 = 0.2;
+
+/// Maximum number of video packets held while reading ahead for audio, bounding memory
+/// for files with sparse or badly interleaved audio.
+pub const AUDIO_READ_AHEAD_PACKETS: usize = 256;
+
+/// Gap or overlap (seconds) between consecutive audio chunks that is ignored rather than
+/// padded with silence or cut.
+pub const AUDIO_SYNC_TOLERANCE_SECS: f64 = 0.01;
+
+/// Sample rate of the null and file sinks, and of the fallback when no device opens.
+pub const AUDIO_FALLBACK_RATE: u32 = 48_000;
+
+/// Interval at which the null and file sinks render the mix.
+pub const AUDIO_SINK_PERIOD_MILLIS: u64 = 10;
+
+/// Volume levels the sound button steps through (RMB), from full volume down.
+pub const VOLUME_STEPS: [f32; 4] = [1.0, 0.75, 0.5, 0.25];
+
+// =============================================================================
+// VIDEO CLIP EXPORT CONSTANTS
+// =============================================================================
+
+/// Length in seconds of a clip taken from the shown position when no trim range is set.
+pub const CLIP_DEFAULT_SECS: f64 = 3.0;
+
+/// Longest clip in seconds; longer ranges are cut, keeping the clip's memory bounded.
+pub const CLIP_MAX_SECS: f64 = 10.0;
+
+/// Frames per second kept in a clip; faster videos are thinned out.
+pub const CLIP_MAX_FPS: f64 = 15.0;
 
 // =============================================================================
 // WINDOW CONSTANTS
@@ -120,6 +245,25 @@ pub const LABEL_PADDING: f32 = 4.0;
 /// Expansion for label background rectangle.
 pub const LABEL_BG_EXPANSION: f32 = 2.0;
 
+// =============================================================================
+// VIDEO SCRUB BAR CONSTANTS
+// =============================================================================
+
+/// Height of the scrub bar track.
+pub const SCRUB_BAR_HEIGHT: f32 = 4.0;
+
+/// Height of the area around the track that responds to the pointer.
+pub const SCRUB_BAR_HIT_HEIGHT: f32 = 16.0;
+
+/// Inset of the scrub bar from the edges of the video.
+pub const SCRUB_BAR_MARGIN: f32 = 6.0;
+
+/// Font size for the position / length text above the scrub bar.
+pub const SCRUB_TIME_FONT_SIZE: f32 = 11.0;
+
+/// Width of the trim in/out markers on the scrub bar.
+pub const TRIM_MARKER_WIDTH: f32 = 2.0;
+
 // =============================================================================
 // TOOLBAR CONSTANTS
 // =============================================================================
@@ -133,6 +277,9 @@ pub const TOOLBAR_ICON_SIZE: f32 = 24.0;
 /// Minimum size for toolbar buttons.
 pub const TOOLBAR_BUTTON_SIZE: f32 = 32.0;
 
+/// Width of the progress bar shown in place of the Export PNG button while exporting.
+pub const EXPORT_PROGRESS_WIDTH: f32 = 80.0;
+
 // =============================================================================
 // COLORS - BLOCK BACKGROUNDS
 // =============================================================================
@@ -188,6 +335,19 @@ pub const COUNTER_BADGE_DAY_COLORS: [Color32; 7] = [
     Color32::from_rgba_premultiplied(140, 30, 110, 200), // Sun — magenta
 ];
 
+// =============================================================================
+// COLORS - MISSING FILES
+// =============================================================================
+
+/// Fill color for blocks whose source file could not be found.
+pub const COLOR_MISSING_BG: Color32 = Color32::from_rgb(70, 30, 30);
+
+/// Border and text color for blocks whose source file could not be found.
+pub const COLOR_MISSING_BORDER: Color32 = Color32::from_rgb(230, 90, 90);
+
+/// Border width (before zoom scaling) for blocks whose source file could not be found.
+pub const MISSING_BORDER_WIDTH: f32 = 2.0;
+
 // =============================================================================
 // COLORS - TEXT AND LABELS
 // =============================================================================
@@ -195,6 +355,67 @@ pub const COUNTER_BADGE_DAY_COLORS: [Color32; 7] = [
 /// Background color for file name labels (semi-transparent black).
 pub const COLOR_LABEL_BG_ALPHA: u8 = 180;
 
+// =============================================================================
+// COLORS - PLAYBACK CONTROLS
+// =============================================================================
+
+/// Scrub bar track color (semi-transparent black).
+pub const COLOR_SCRUB_TRACK: Color32 = Color32::from_black_alpha(150);
+
+/// Color of the played part of the scrub bar.
+pub const COLOR_SCRUB_PROGRESS: Color32 = Color32::from_rgb(230, 230, 230);
+
+/// Color of the trim in/out markers on the scrub bar.
+pub const COLOR_TRIM_MARKER: Color32 = Color32::from_rgb(255, 200, 0);
+
+/// Trim button color when hovered.
+pub const COLOR_TRIM_BUTTON_HOVER: Color32 = Color32::from_rgb(90, 90, 200);
+
+/// Trim button color in normal state.
+pub const COLOR_TRIM_BUTTON: Color32 = Color32::from_rgb(60, 60, 140);
+
+/// Trim button color when its point is set.
+pub const COLOR_TRIM_BUTTON_ACTIVE: Color32 = Color32::from_rgb(200, 150, 0);
+
+/// Poster button color when hovered.
+pub const COLOR_POSTER_BUTTON_HOVER: Color32 = Color32::from_rgb(170, 90, 190);
+
+/// Poster button color in normal state.
+pub const COLOR_POSTER_BUTTON: Color32 = Color32::from_rgb(110, 50, 130);
+
+/// Poster button color when a poster frame is chosen.
+pub const COLOR_POSTER_BUTTON_ACTIVE: Color32 = Color32::from_rgb(200, 150, 0);
+
+/// Sound button color when hovered.
+pub const COLOR_SOUND_BUTTON_HOVER: Color32 = Color32::from_rgb(60, 170, 200);
+
+/// Sound button color in normal state.
+pub const COLOR_SOUND_BUTTON: Color32 = Color32::from_rgb(30, 110, 140);
+
+/// Sound button color while the video is heard.
+pub const COLOR_SOUND_BUTTON_ACTIVE: Color32 = Color32::from_rgb(200, 150, 0);
+
+/// Sound button color when the video is muted.
+pub const COLOR_SOUND_BUTTON_MUTED: Color32 = Color32::from_rgb(80, 80, 80);
+
+/// Speed button color when hovered.
+pub const COLOR_SPEED_BUTTON_HOVER: Color32 = Color32::from_rgb(200, 120, 70);
+
+/// Speed button color at normal speed, playing forward in a loop.
+pub const COLOR_SPEED_BUTTON: Color32 = Color32::from_rgb(130, 70, 40);
+
+/// Speed button color when the speed or playback mode is changed.
+pub const COLOR_SPEED_BUTTON_ACTIVE: Color32 = Color32::from_rgb(200, 150, 0);
+
+/// Clip button color when hovered.
+pub const COLOR_CLIP_BUTTON_HOVER: Color32 = Color32::from_rgb(80, 190, 110);
+
+/// Clip button color in normal state.
+pub const COLOR_CLIP_BUTTON: Color32 = Color32::from_rgb(40, 120, 60);
+
+/// Clip button color while a clip of the video is being exported.
+pub const COLOR_CLIP_BUTTON_ACTIVE: Color32 = Color32::from_rgb(200, 150, 0);
+
 // =============================================================================
 // COLORS - TOOLBAR
 // =============================================================================
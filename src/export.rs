@@ -0,0 +1,501 @@
+//! Headless export of a board to a single PNG image.
+//!
+//! The board is rebuilt from a `Session` without opening a window: every block's still
+//! frame (its poster frame like on the board, or else the first frame) is decoded with
+//! `image_loader::load_still_image`, blocks are laid out
+//! with `BlockManager::reorder_and_reflow`, and each one is drawn with
+//! `ImageBlock::render` inside an offscreen egui pass. The resulting meshes are then
+//! rasterized on the CPU, so the PNG matches what the canvas shows (minus the hover
+//! controls).
+
+use crate::block::{self, BlockControlHover, BlockRenderConfig, ImageBlock};
+use crate::block_manager::BlockManager;
+use crate::constants::{CANVAS_PADDING, COLOR_GROUP_PLACEHOLDER, MIN_CANVAS_INNER_WIDTH};
+use crate::image_loader::{self, PosterFrame};
+use crate::session::{BlockData, Session};
+use eframe::egui::{self, epaint, pos2, vec2, Color32, ColorImage, Pos2, Rect, TextureId};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Largest width or height of an exported image, in pixels.
+const MAX_EXPORT_DIMENSION: f32 = 16384.0;
+
+/// Share of the reported progress taken by decoding the files; drawing takes the rest.
+const DECODE_PROGRESS_SHARE: f32 = 0.5;
+
+/// How a board is rendered to PNG.
+#[derive(Clone, Copy)]
+pub struct ExportOptions {
+    /// Output pixels per canvas unit (1.0 matches the canvas at 100% zoom).
+    pub scale: f32,
+    /// Inner canvas width used for the layout, in canvas units.
+    pub inner_width: f32,
+    pub show_file_names: bool,
+}
+
+/// Renders `session` to a PNG at `output`. Returns the image size in pixels.
+/// `progress` is called with the completed fraction (0..1), possibly from several threads.
+pub fn export_png(
+    session: Session,
+    output: &Path,
+    options: ExportOptions,
+    progress: &(dyn Fn(f32) + Sync),
+) -> Result<[usize; 2], String> {
+    let image = render_session(session, options, progress)?;
+    let size = image.size;
+    let rgba: Vec<u8> = image.pixels.iter().flat_map(|p| p.to_array()).collect();
+    image::save_buffer(
+        output,
+        &rgba,
+        size[0] as u32,
+        size[1] as u32,
+        image::ColorType::Rgba8,
+    )
+    .map_err(|err| format!("Failed to write {}: {err}", output.display()))?;
+    Ok(size)
+}
+
+/// Lays out and draws the session into an opaque image.
+fn render_session(
+    session: Session,
+    options: ExportOptions,
+    progress: &(dyn Fn(f32) + Sync),
+) -> Result<ColorImage, String> {
+    let ctx = egui::Context::default();
+    let mut manager = BlockManager::new();
+
+    let images = load_still_frames(&session.blocks, options.scale, &|done| {
+        progress(done * DECODE_PROGRESS_SHARE)
+    });
+    for data in session.blocks {
+        if let Some(block) = build_block(&ctx, &mut manager, data, &images) {
+            manager.push(block);
+        }
+    }
+    let inner_width = options.inner_width.max(MIN_CANVAS_INNER_WIDTH);
+    manager.reorder_and_reflow(None, inner_width);
+
+    let content_height = manager
+        .blocks()
+        .iter()
+        .map(|b| b.pos.position.y + b.outer_size().y)
+        .fold(0.0, f32::max);
+    let canvas_size = vec2(
+        inner_width + CANVAS_PADDING * 2.0,
+        content_height + CANVAS_PADDING,
+    ) * options.scale;
+    if canvas_size.max_elem() > MAX_EXPORT_DIMENSION {
+        return Err(format!(
+            "Export would be {:.0}x{:.0} px; reduce the scale (limit {MAX_EXPORT_DIMENSION} px)",
+            canvas_size.x, canvas_size.y
+        ));
+    }
+    let pixel_size = [
+        canvas_size.x.ceil().max(1.0) as usize,
+        canvas_size.y.ceil().max(1.0) as usize,
+    ];
+
+    let raw_input = egui::RawInput {
+        screen_rect: Some(Rect::from_min_size(Pos2::ZERO, canvas_size)),
+        ..Default::default()
+    };
+    let background = ctx.style().visuals.panel_fill;
+    let config = BlockRenderConfig {
+        zoom: options.scale,
+        show_controls: false,
+        show_file_names: options.show_file_names,
+        can_chain: false,
+        is_drop_target: false,
+        hover_state: BlockControlHover::default(),
+    };
+    let output = ctx.run(raw_input, |ctx| {
+        egui::CentralPanel::default()
+            .frame(egui::Frame::none().fill(background))
+            .show(ctx, |ui| {
+                for block in manager.blocks() {
+                    let rect = Rect::from_min_size(
+                        block.pos.position * options.scale,
+                        block.outer_size() * options.scale,
+                    );
+                    block.render(ui, rect, config);
+                }
+            });
+    });
+
+    let mut raster = Rasterizer::new(pixel_size, background);
+    raster.update_textures(&output.textures_delta);
+    let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+    for (index, clipped) in primitives.iter().enumerate() {
+        if let epaint::Primitive::Mesh(mesh) = &clipped.primitive {
+            raster.draw_mesh(mesh, clipped.clip_rect);
+        }
+        let drawn = (index + 1) as f32 / primitives.len() as f32;
+        progress(DECODE_PROGRESS_SHARE + drawn * (1.0 - DECODE_PROGRESS_SHARE));
+    }
+    Ok(raster.target)
+}
+
+/// Still frames decoded for export: per path, each poster frame shown by a block
+/// (None for the first frame) with its image.
+type StillFrames = HashMap<String, Vec<(Option<PosterFrame>, ColorImage)>>;
+
+/// Decodes the still frame of every block in parallel, once per file and poster. Frames
+/// are downsampled to what the largest block needs at `scale`. `progress` gets the
+/// fraction of frames decoded.
+fn load_still_frames(
+    blocks: &[BlockData],
+    scale: f32,
+    progress: &(dyn Fn(f32) + Sync),
+) -> StillFrames {
+    fn collect<'a>(
+        blocks: &'a [BlockData],
+        stills: &mut Vec<(&'a str, Option<PosterFrame>)>,
+        max_side: &mut f32,
+    ) {
+        for block in blocks {
+            *max_side = max_side.max(block.size[0]).max(block.size[1]);
+            let still = (
+                block.path.as_str(),
+                block::poster_frame_of(block.poster, block.video_trim),
+            );
+            if !block.path.is_empty() && !stills.contains(&still) {
+                stills.push(still);
+            }
+            collect(&block.children, stills, max_side);
+        }
+    }
+
+    let mut stills = Vec::new();
+    let mut max_side = 1.0f32;
+    collect(blocks, &mut stills, &mut max_side);
+    let max_dimension = (max_side * scale).ceil() as u32;
+
+    let decoded = AtomicUsize::new(0);
+    let frames: Vec<(&str, Option<PosterFrame>, ColorImage)> = stills
+        .par_iter()
+        .filter_map(|&(path, poster)| {
+            let file = Path::new(path);
+            // Like on the board, a poster that fails to decode falls back to the first frame.
+            let mut result = image_loader::load_still_image(file, poster, max_dimension);
+            if let (Err(err), Some(_)) = (&result, poster) {
+                log::warn!("Exporting the first frame of {path}: {err}");
+                result = image_loader::load_still_image(file, None, max_dimension);
+            }
+            let done = decoded.fetch_add(1, Ordering::Relaxed) + 1;
+            progress(done as f32 / stills.len() as f32);
+            match result {
+                Ok(image) => Some((path, poster, image)),
+                Err(err) => {
+                    log::warn!("Exporting {path} as missing: {err}");
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let mut images = StillFrames::new();
+    for (path, poster, image) in frames {
+        images
+            .entry(path.to_string())
+            .or_default()
+            .push((poster, image));
+    }
+    images
+}
+
+/// Recreates a block (and its group children) from session data with its still frame
+/// as texture. Files that failed to load are drawn as missing.
+fn build_block(
+    ctx: &egui::Context,
+    manager: &mut BlockManager,
+    data: BlockData,
+    images: &StillFrames,
+) -> Option<ImageBlock> {
+    let color =
+        Color32::from_rgba_unmultiplied(data.color[0], data.color[1], data.color[2], data.color[3]);
+
+    let mut block = if data.is_group {
+        let children: Vec<ImageBlock> = data
+            .children
+            .into_iter()
+            .filter_map(|c| build_block(ctx, manager, c, images))
+            .collect();
+        let texture = ctx.load_texture(
+            format!("group-texture-{}", manager.allocate_block_id()),
+            ColorImage::new([1, 1], COLOR_GROUP_PLACEHOLDER),
+            egui::TextureOptions::LINEAR,
+        );
+        let representative_texture = children
+            .iter()
+            .find(|c| !c.is_missing)
+            .map(|c| c.texture.clone());
+        ImageBlock::new_group(data.group_name, children, texture, representative_texture)
+    } else {
+        if data.path.is_empty() {
+            return None;
+        }
+        let still = block::poster_frame_of(data.poster, data.video_trim);
+        let frame = images
+            .get(&data.path)
+            .and_then(|stills| stills.iter().find(|(poster, _)| *poster == still))
+            .map(|(_, image)| image);
+        let texture = ctx.load_texture(
+            format!("block-texture-{}", manager.allocate_block_id()),
+            frame
+                .cloned()
+                .unwrap_or_else(|| ColorImage::new([1, 1], Color32::from_gray(40))),
+            egui::TextureOptions::LINEAR,
+        );
+        let mut block = ImageBlock::new(
+            data.path.clone(),
+            texture,
+            Vec::new(),
+            vec2(data.size[0], data.size[1]),
+            false,
+            false,
+        );
+        block.is_missing = frame.is_none();
+        block.counter = data.counter;
+        block.counter_start_day = data.counter_start_day;
+        block.file_size = std::fs::metadata(&data.path).map(|m| m.len()).unwrap_or(0);
+        block
+    };
+
+    block.id = data.id;
+    block.color = color;
+    block.pos.position = pos2(data.position[0], data.position[1]);
+    block.set_preferred_size(vec2(data.size[0], data.size[1]));
+    Some(block)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// CPU rasterizer
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Minimal software renderer for tessellated egui meshes: textured, vertex-colored
+/// triangles with premultiplied-alpha blending in gamma space, like the glow backend.
+struct Rasterizer {
+    target: ColorImage,
+    textures: HashMap<TextureId, ColorImage>,
+}
+
+impl Rasterizer {
+    fn new(size: [usize; 2], background: Color32) -> Self {
+        Self {
+            target: ColorImage::new(size, background),
+            textures: HashMap::new(),
+        }
+    }
+
+    fn update_textures(&mut self, delta: &egui::TexturesDelta) {
+        for (id, image_delta) in &delta.set {
+            let image = match &image_delta.image {
+                egui::ImageData::Color(image) => (**image).clone(),
+                egui::ImageData::Font(font) => ColorImage {
+                    size: font.size,
+                    pixels: font.srgba_pixels(None).collect(),
+                },
+            };
+            match (image_delta.pos, self.textures.get_mut(id)) {
+                (Some([x0, y0]), Some(texture)) => {
+                    for y in 0..image.size[1] {
+                        for x in 0..image.size[0] {
+                            texture[(x0 + x, y0 + y)] = image[(x, y)];
+                        }
+                    }
+                }
+                _ => {
+                    self.textures.insert(*id, image);
+                }
+            }
+        }
+    }
+
+    fn draw_mesh(&mut self, mesh: &epaint::Mesh, clip_rect: Rect) {
+        let Some(texture) = self.textures.get(&mesh.texture_id) else {
+            return;
+        };
+        let clip = clip_rect.intersect(Rect::from_min_size(
+            Pos2::ZERO,
+            vec2(self.target.size[0] as f32, self.target.size[1] as f32),
+        ));
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| &mesh.vertices[triangle[i] as usize]);
+            fill_triangle(&mut self.target, texture, clip, a, b, c);
+        }
+    }
+}
+
+fn fill_triangle(
+    target: &mut ColorImage,
+    texture: &ColorImage,
+    clip: Rect,
+    a: &epaint::Vertex,
+    b: &epaint::Vertex,
+    c: &epaint::Vertex,
+) {
+    // Edge function: positive when `p` is to the right of a→b in y-down space.
+    let edge = |a: Pos2, b: Pos2, p: Pos2| (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
+
+    let area = edge(a.pos, b.pos, c.pos);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+    // Normalize the winding so that inside means all edge functions are positive.
+    let (b, c) = if area < 0.0 { (c, b) } else { (b, c) };
+    let area = area.abs();
+
+    // Pixels exactly on a shared edge belong to only one of the two triangles, so
+    // translucent shapes are not blended twice along their seams.
+    let owns_edge = |from: Pos2, to: Pos2| {
+        let d = to - from;
+        d.y > 0.0 || (d.y == 0.0 && d.x < 0.0)
+    };
+    let owned = [
+        owns_edge(b.pos, c.pos),
+        owns_edge(c.pos, a.pos),
+        owns_edge(a.pos, b.pos),
+    ];
+
+    let min_x = a.pos.x.min(b.pos.x).min(c.pos.x).max(clip.min.x).floor() as i64;
+    let max_x = a.pos.x.max(b.pos.x).max(c.pos.x).min(clip.max.x).ceil() as i64;
+    let min_y = a.pos.y.min(b.pos.y).min(c.pos.y).max(clip.min.y).floor() as i64;
+    let max_y = a.pos.y.max(b.pos.y).max(c.pos.y).min(clip.max.y).ceil() as i64;
+
+    for y in min_y.max(0)..max_y.min(target.size[1] as i64) {
+        for x in min_x.max(0)..max_x.min(target.size[0] as i64) {
+            let p = pos2(x as f32 + 0.5, y as f32 + 0.5);
+            if !clip.contains(p) {
+                continue;
+            }
+            let w = [
+                edge(b.pos, c.pos, p),
+                edge(c.pos, a.pos, p),
+                edge(a.pos, b.pos, p),
+            ];
+            let inside = w
+                .iter()
+                .zip(owned)
+                .all(|(&w, owned)| w > 0.0 || (w == 0.0 && owned));
+            if !inside {
+                continue;
+            }
+
+            let [wa, wb, wc] = w.map(|w| w / area);
+            let uv = pos2(
+                a.uv.x * wa + b.uv.x * wb + c.uv.x * wc,
+                a.uv.y * wa + b.uv.y * wb + c.uv.y * wc,
+            );
+            let texel = sample_bilinear(texture, uv);
+            let src: [f32; 4] = std::array::from_fn(|i| {
+                let color =
+                    a.color[i] as f32 * wa + b.color[i] as f32 * wb + c.color[i] as f32 * wc;
+                color * texel[i] / 255.0
+            });
+
+            let dst = &mut target[(x as usize, y as usize)];
+            let inverse_alpha = 1.0 - src[3] / 255.0;
+            *dst = Color32::from_rgba_premultiplied(
+                (src[0] + dst[0] as f32 * inverse_alpha).round() as u8,
+                (src[1] + dst[1] as f32 * inverse_alpha).round() as u8,
+                (src[2] + dst[2] as f32 * inverse_alpha).round() as u8,
+                (src[3] + dst[3] as f32 * inverse_alpha).round() as u8,
+            );
+        }
+    }
+}
+
+/// Samples a premultiplied texture with bilinear filtering and clamped edges.
+fn sample_bilinear(texture: &ColorImage, uv: Pos2) -> [f32; 4] {
+    let [width, height] = texture.size;
+    let x = (uv.x * width as f32 - 0.5).clamp(0.0, (width - 1) as f32);
+    let y = (uv.y * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let corners = [
+        (texture[(x0, y0)], (1.0 - fx) * (1.0 - fy)),
+        (texture[(x1, y0)], fx * (1.0 - fy)),
+        (texture[(x0, y1)], (1.0 - fx) * fy),
+        (texture[(x1, y1)], fx * fy),
+    ];
+    std::array::from_fn(|i| {
+        corners
+            .iter()
+            .map(|(color, weight)| color[i] as f32 * weight)
+            .sum()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_still_frames_use_posters() {
+        use image::codecs::gif::GifEncoder;
+
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_export_{}.gif", uuid::Uuid::new_v4()));
+        {
+            let mut encoder = GifEncoder::new(std::fs::File::create(&path).unwrap());
+            for color in [[255, 0, 0, 255], [0, 0, 255, 255]] {
+                let frame = image::RgbaImage::from_pixel(4, 4, image::Rgba(color));
+                encoder.encode_frame(image::Frame::new(frame)).unwrap();
+            }
+        }
+        let block = |poster| BlockData {
+            path: path.to_string_lossy().into_owned(),
+            size: [4.0, 4.0],
+            poster,
+            ..Default::default()
+        };
+        let blocks = [block(None), block(Some(PosterFrame::Index(1))), block(None)];
+
+        let images = load_still_frames(&blocks, 1.0, &|_| {});
+        let stills = &images[&blocks[0].path];
+        assert_eq!(stills.len(), 2);
+        let color_of = |poster| {
+            let (_, image) = stills.iter().find(|(p, _)| *p == poster).unwrap();
+            image.pixels[0]
+        };
+        assert_eq!(color_of(None), Color32::RED);
+        assert_eq!(color_of(Some(PosterFrame::Index(1))), Color32::BLUE);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rasterizer_blends_translucent_quad_once() {
+        let mut raster = Rasterizer::new([8, 8], Color32::BLACK);
+        let mut delta = egui::TexturesDelta::default();
+        delta.set.push((
+            TextureId::Managed(0),
+            egui::epaint::ImageDelta::full(
+                ColorImage::new([1, 1], Color32::WHITE),
+                egui::TextureOptions::LINEAR,
+            ),
+        ));
+        raster.update_textures(&delta);
+
+        // Two triangles sharing a diagonal, half-transparent red over black.
+        let mut mesh = epaint::Mesh::default();
+        mesh.add_colored_rect(
+            Rect::from_min_max(pos2(2.0, 2.0), pos2(6.0, 6.0)),
+            Color32::from_rgba_premultiplied(128, 0, 0, 128),
+        );
+        raster.draw_mesh(&mesh, Rect::EVERYTHING);
+
+        let target = &raster.target;
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = (2..6).contains(&x) && (2..6).contains(&y);
+                let expected = if inside { 128 } else { 0 };
+                assert_eq!(target[(x, y)].r(), expected, "pixel ({x}, {y})");
+            }
+        }
+    }
+}
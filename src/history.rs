@@ -0,0 +1,293 @@
+//! Undo/redo history for canvas operations.
+//!
+//! Instead of recording individual commands, the history stores lightweight layout
+//! snapshots of the `BlockManager`: block order, sizes, chain state, group membership and
+//! counters. Blocks that leave the canvas (deleted, or re-added by a step that was undone)
+//! are parked in a `BlockPool` with their textures intact, so undo and redo never reload
+//! media from disk.
+//!
+//! Mutations are bracketed by `begin` (before the first change) and `commit` (once the
+//! gesture is finished); a commit that left the layout unchanged records nothing.
+
+use crate::block::ImageBlock;
+use crate::block_manager::{BlockManager, ChainedIds};
+use crate::constants::{MAX_UNDO_STEPS, UNDO_MEMORY_BUDGET_BYTES};
+use eframe::egui::Vec2;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+use uuid::Uuid;
+
+/// Layout state of a single block, recorded recursively for group children.
+#[derive(Clone, PartialEq)]
+pub struct BlockLayout {
+    pub id: Uuid,
+    pub preferred_image_size: Vec2,
+    pub chained: bool,
+    pub counter: i32,
+    pub counter_start_day: u32,
+    pub group_name: String,
+    pub children: Vec<BlockLayout>,
+}
+
+/// Layout state of the whole canvas at one point in time.
+#[derive(Clone, PartialEq)]
+pub struct Snapshot {
+    pub blocks: Vec<BlockLayout>,
+    pub remembered_chains: Vec<ChainedIds>,
+}
+
+impl Snapshot {
+    /// Approximate heap and inline size of the snapshot.
+    fn byte_size(&self) -> usize {
+        fn layout_size(layout: &BlockLayout) -> usize {
+            size_of::<BlockLayout>()
+                + layout.group_name.len()
+                + layout.children.iter().map(layout_size).sum::<usize>()
+        }
+        size_of::<Self>()
+            + self.blocks.iter().map(layout_size).sum::<usize>()
+            + self
+                .remembered_chains
+                .iter()
+                .map(|chain| size_of::<ChainedIds>() + chain.len() * size_of::<Uuid>())
+                .sum::<usize>()
+    }
+
+    fn collect_ids(&self, out: &mut HashSet<Uuid>) {
+        fn walk(layout: &BlockLayout, out: &mut HashSet<Uuid>) {
+            out.insert(layout.id);
+            for child in &layout.children {
+                walk(child, out);
+            }
+        }
+        for layout in &self.blocks {
+            walk(layout, out);
+        }
+    }
+}
+
+/// Blocks that are currently off the canvas, keyed by ID. Group children are stored as
+/// separate entries so that any snapshot can re-nest them.
+#[derive(Default)]
+pub struct BlockPool {
+    blocks: HashMap<Uuid, ImageBlock>,
+}
+
+impl BlockPool {
+    /// Adds a block and, recursively, its group children. Only their first frame is
+    /// kept: playback resources are released.
+    pub fn insert(&mut self, mut block: ImageBlock) {
+        for child in std::mem::take(&mut block.group.children) {
+            self.insert(child);
+        }
+        block.purge_animation_frames();
+        // Parked blocks are not shown; a sharper texture is decoded again if needed.
+        block.detail = None;
+        self.blocks.insert(block.id, block);
+    }
+
+    /// Removes and returns the block with the given ID.
+    pub fn take(&mut self, id: Uuid) -> Option<ImageBlock> {
+        self.blocks.remove(&id)
+    }
+
+    /// Drops every parked block that is not in `ids`.
+    fn retain(&mut self, ids: &HashSet<Uuid>) {
+        self.blocks.retain(|id, _| ids.contains(id));
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.blocks.values().map(ImageBlock::memory_bytes).sum()
+    }
+}
+
+/// Bounded undo/redo stacks of canvas layout snapshots.
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+    /// Layout captured by `begin`, waiting for the matching `commit`.
+    pending: Option<Snapshot>,
+    parked: BlockPool,
+}
+
+impl History {
+    /// Captures the layout before a mutation. Repeated calls before the next `commit`
+    /// keep the first capture, so a whole gesture becomes a single undo step.
+    pub fn begin(&mut self, manager: &BlockManager) {
+        if self.pending.is_none() {
+            self.pending = Some(manager.snapshot());
+        }
+    }
+
+    /// Finishes the current step: records it if the layout changed since `begin`, and
+    /// parks any blocks removed in the meantime. Returns true if a step was recorded.
+    /// Called every frame, so it does nothing unless a step was begun or blocks removed.
+    pub fn commit(&mut self, manager: &mut BlockManager) -> bool {
+        let parked = self.park_detached(manager);
+        let Some(before) = self.pending.take() else {
+            if parked {
+                self.prune();
+            }
+            return false;
+        };
+        if before == manager.snapshot() {
+            self.prune();
+            return false;
+        }
+
+        self.undo.push_back(before);
+        self.redo.clear();
+        while self.undo.len() > MAX_UNDO_STEPS {
+            self.undo.pop_front();
+        }
+        self.prune();
+        while self.memory_bytes() > UNDO_MEMORY_BUDGET_BYTES && self.undo.pop_front().is_some() {
+            self.prune();
+        }
+        true
+    }
+
+    /// Reverts the last recorded step. Returns false if there was nothing to undo.
+    pub fn undo(&mut self, manager: &mut BlockManager) -> bool {
+        self.commit(manager);
+        let Some(previous) = self.undo.pop_back() else {
+            return false;
+        };
+        self.redo.push(manager.snapshot());
+        self.apply(manager, &previous);
+        true
+    }
+
+    /// Re-applies the last undone step. Returns false if there was nothing to redo.
+    pub fn redo(&mut self, manager: &mut BlockManager) -> bool {
+        self.commit(manager);
+        let Some(next) = self.redo.pop() else {
+            return false;
+        };
+        self.undo.push_back(manager.snapshot());
+        self.apply(manager, &next);
+        true
+    }
+
+    /// Forgets all steps and parked blocks, e.g. when another session is loaded.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.pending = None;
+        self.parked = BlockPool::default();
+    }
+
+    fn apply(&mut self, manager: &mut BlockManager, snapshot: &Snapshot) {
+        manager.restore_snapshot(snapshot, &mut self.parked);
+        self.prune();
+    }
+
+    /// Parks the blocks removed from the canvas; returns true if there were any.
+    fn park_detached(&mut self, manager: &mut BlockManager) -> bool {
+        let detached = manager.take_detached();
+        let parked = !detached.is_empty();
+        for block in detached {
+            self.parked.insert(block);
+        }
+        parked
+    }
+
+    /// Drops parked blocks that no remaining snapshot refers to.
+    fn prune(&mut self) {
+        let mut referenced = HashSet::new();
+        for snapshot in self.undo.iter().chain(&self.redo).chain(&self.pending) {
+            snapshot.collect_ids(&mut referenced);
+        }
+        self.parked.retain(&referenced);
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.undo
+            .iter()
+            .chain(&self.redo)
+            .map(Snapshot::byte_size)
+            .sum::<usize>()
+            + self.parked.memory_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eframe::egui::{self, vec2};
+
+    fn block(ctx: &egui::Context, name: &str) -> ImageBlock {
+        let texture = ctx.load_texture(
+            name,
+            egui::ColorImage::new([2, 2], egui::Color32::WHITE),
+            egui::TextureOptions::LINEAR,
+        );
+        ImageBlock::new(
+            name.to_string(),
+            texture,
+            Vec::new(),
+            vec2(100.0, 50.0),
+            false,
+            false,
+        )
+    }
+
+    fn paths(manager: &BlockManager) -> Vec<&str> {
+        manager.blocks().iter().map(|b| b.path.as_str()).collect()
+    }
+
+    #[test]
+    fn test_undo_redo_remove_and_box() {
+        let ctx = egui::Context::default();
+        let mut manager = BlockManager::new();
+        let mut history = History::default();
+        for name in ["a", "b", "c"] {
+            manager.push(block(&ctx, name));
+        }
+        let texture_id = manager.blocks()[0].texture.id();
+
+        history.begin(&manager);
+        manager.get_by_index_mut(1).unwrap().counter = 5;
+        manager.remove_cascade(0);
+        assert!(history.commit(&mut manager));
+        assert_eq!(paths(&manager), ["b", "c"]);
+
+        history.begin(&manager);
+        manager.toggle_chain(0);
+        manager.toggle_chain(1);
+        let group_id = manager.box_chained(&ctx);
+        assert!(history.commit(&mut manager));
+        assert_eq!(manager.len(), 1);
+
+        assert!(history.undo(&mut manager));
+        assert_eq!(paths(&manager), ["b", "c"]);
+        assert!(manager.blocks().iter().all(|b| !b.chained));
+
+        assert!(history.undo(&mut manager));
+        assert_eq!(paths(&manager), ["a", "b", "c"]);
+        assert_eq!(manager.blocks()[0].texture.id(), texture_id);
+        assert_eq!(manager.blocks()[1].counter, 0);
+        assert!(!history.undo(&mut manager));
+
+        assert!(history.redo(&mut manager));
+        assert!(history.redo(&mut manager));
+        assert_eq!(manager.blocks()[0].id, group_id);
+        assert_eq!(manager.blocks()[0].group.children.len(), 2);
+        assert_eq!(manager.blocks()[0].group.children[0].counter, 5);
+        assert!(!history.redo(&mut manager));
+    }
+
+    #[test]
+    fn test_unchanged_layout_records_nothing() {
+        let ctx = egui::Context::default();
+        let mut manager = BlockManager::new();
+        let mut history = History::default();
+        manager.push(block(&ctx, "a"));
+
+        history.begin(&manager);
+        manager.clear_chain_group();
+        assert!(!history.commit(&mut manager));
+        assert!(!history.undo(&mut manager));
+    }
+}
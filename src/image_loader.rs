@@ -1,15 +1,32 @@
 use egui::ColorImage;
 use image::codecs::gif::GifDecoder;
 use image::codecs::webp::WebPDecoder;
-use image::{AnimationDecoder, DynamicImage, Frame, ImageFormat};
+use image::{AnimationDecoder, DynamicImage, Frame, Frames, ImageDecoder, ImageFormat};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::io::Cursor;
 use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 
 /// Maximum number of frames to load for an animation to prevent excessive memory usage.
 pub const MAX_ANIMATION_FRAMES: usize = 1024;
 
+/// File extensions offered by the "Add Image" dialog and accepted when expanding folders.
+pub const SUPPORTED_EXTENSIONS: &[&str] =
+    &["png", "jpg", "jpeg", "gif", "webp", "avif", "webm", "mp4"];
+
+/// Returns true if the path has one of the `SUPPORTED_EXTENSIONS` (case-insensitive).
+pub fn has_supported_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| {
+            SUPPORTED_EXTENSIONS
+                .iter()
+                .any(|supported| ext.eq_ignore_ascii_case(supported))
+        })
+}
+
 /// A single frame of an animated image, including its pixel data and display duration.
 #[derive(Clone)]
 pub struct AnimationFrame {
@@ -17,17 +34,32 @@ pub struct AnimationFrame {
     pub duration: Duration,
 }
 
+/// Frame shown while a video or animation is not playing, instead of the first one.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PosterFrame {
+    /// Position in seconds, for videos.
+    Time(f64),
+    /// Frame index, for GIF/WebP/AVIF animations.
+    Index(usize),
+}
+
 /// Holds all frames and metadata for a loaded image, supporting both static and animated formats.
 pub struct LoadedImage {
     pub frames: Vec<AnimationFrame>,
     pub original_size: egui::Vec2,
     pub has_animation: bool,
+    /// Content fingerprint of the source file, used to relink it if it is moved later.
+    pub fingerprint: Option<u64>,
+    /// Probed properties of a video file; None for images.
+    pub video_metadata: Option<crate::video_stream::VideoMetadata>,
 }
 
 /// Result of an image load operation, containing the path, loaded data, and a flag indicating if it's a full sequence.
 pub type ImageLoadResult = (std::path::PathBuf, LoadedImage, bool);
-/// Response type for image loading, wrapping the result in a Result with a string error.
-pub type ImageLoadResponse = Result<ImageLoadResult, String>;
+/// Failure of an image load operation: the path that failed and a description of the error.
+pub type ImageLoadFailure = (std::path::PathBuf, String);
+/// Response type for image loading, wrapping the result in a Result with the failed path.
+pub type ImageLoadResponse = Result<ImageLoadResult, ImageLoadFailure>;
 
 impl LoadedImage {
     /// Creates a new LoadedImage from a sequence of frames and animation metadata.
@@ -43,6 +75,8 @@ impl LoadedImage {
             frames,
             original_size,
             has_animation,
+            fingerprint: None,
+            video_metadata: None,
         }
     }
 }
@@ -96,6 +130,185 @@ pub fn load_image_frames_scaled(
     Ok(loaded)
 }
 
+/// Decodes the poster frame of a file, scaled like its first frame. Indices past the
+/// end of an animation select its last frame.
+pub fn load_poster_frame(
+    path: &Path,
+    poster: PosterFrame,
+    max_dimension: Option<u32>,
+) -> Result<ColorImage, String> {
+    match poster {
+        PosterFrame::Time(seconds) => crate::video_stream::load_video_frame_at(path, seconds),
+        PosterFrame::Index(index) => {
+            // Streamed, so frames past `MAX_ANIMATION_FRAMES` can be posters too.
+            let mut stream = AnimationStream::open(path, max_dimension)?;
+            let mut poster = None;
+            for _ in 0..=index {
+                match stream.next_frame()? {
+                    Some(frame) => poster = Some(frame),
+                    None => break,
+                }
+            }
+            poster
+                .map(|frame| frame.image)
+                .ok_or_else(|| format!("{} has no frames", path.display()))
+        }
+    }
+}
+
+/// Decodes the still image of a file (the poster frame if given, else the first frame)
+/// at up to `max_dimension` pixels, for sharper textures when zoomed in.
+pub fn load_still_image(
+    path: &Path,
+    poster: Option<PosterFrame>,
+    max_dimension: u32,
+) -> Result<ColorImage, String> {
+    if let Some(poster) = poster {
+        return load_poster_frame(path, poster, Some(max_dimension));
+    }
+    load_image_frames_scaled(path, Some(max_dimension), true)?
+        .frames
+        .into_iter()
+        .next()
+        .map(|frame| frame.image)
+        .ok_or_else(|| format!("{} has no frames", path.display()))
+}
+
+/// Decodes the frames of an animated GIF, WebP or AVIF file one at a time, downsampled
+/// like loaded frames, so playing it holds a single frame and is not limited to
+/// `MAX_ANIMATION_FRAMES`. Used by the streaming decoder in `video_stream`.
+pub struct AnimationStream {
+    reader: FrameReader,
+    max_dimension: Option<u32>,
+    /// Width × height of the frames as decoded, before downsampling.
+    source_pixels: usize,
+}
+
+enum FrameReader {
+    /// GIF or WebP frames from the image crate, which cannot rewind: the bytes are kept
+    /// to start a new decoder for the next pass.
+    Frames {
+        bytes: Arc<[u8]>,
+        format: ImageFormat,
+        frames: Frames<'static>,
+    },
+    /// AVIF frames from libavif, whose decoder stays open across passes.
+    Avif(avif_support::AvifFrames<Vec<u8>>),
+}
+
+impl AnimationStream {
+    pub fn open(path: &Path, max_dimension: Option<u32>) -> Result<Self, String> {
+        let bytes =
+            fs::read(path).map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+        let format = image::guess_format(&bytes)
+            .or_else(|_| ImageFormat::from_path(path))
+            .map_err(|err| format!("Failed to determine format for {}: {err}", path.display()))?;
+
+        let (reader, source_pixels) = match format {
+            ImageFormat::Gif | ImageFormat::WebP => {
+                let bytes: Arc<[u8]> = bytes.into();
+                let (frames, source_pixels) = open_frames(&bytes, format)?;
+                let reader = FrameReader::Frames {
+                    bytes,
+                    format,
+                    frames,
+                };
+                (reader, source_pixels)
+            }
+            ImageFormat::Avif => {
+                let frames = avif_support::AvifFrames::new(bytes)?;
+                let source_pixels = frames.source_pixels();
+                (FrameReader::Avif(frames), source_pixels)
+            }
+            _ => return Err(format!("{} is not an animation", path.display())),
+        };
+        Ok(Self {
+            reader,
+            max_dimension,
+            source_pixels,
+        })
+    }
+
+    /// Width × height of the frames as decoded, before downsampling.
+    pub fn source_pixels(&self) -> usize {
+        self.source_pixels
+    }
+
+    /// Decodes the next frame, or returns None after the last one.
+    pub fn next_frame(&mut self) -> Result<Option<AnimationFrame>, String> {
+        let mut frame = match &mut self.reader {
+            FrameReader::Frames { frames, .. } => match frames.next() {
+                Some(frame) => {
+                    animation_frame(frame.map_err(|err| format!("Animation frame error: {err}"))?)
+                }
+                None => return Ok(None),
+            },
+            FrameReader::Avif(frames) => match frames.next_frame()? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            },
+        };
+        if let Some(max_dim) = self.max_dimension {
+            downsample_frame(&mut frame, max_dim);
+        }
+        Ok(Some(frame))
+    }
+
+    /// Starts over at the first frame.
+    pub fn rewind(&mut self) -> Result<(), String> {
+        match &mut self.reader {
+            FrameReader::Frames {
+                bytes,
+                format,
+                frames,
+            } => {
+                *frames = open_frames(bytes, *format)?.0;
+                Ok(())
+            }
+            FrameReader::Avif(frames) => frames.rewind(),
+        }
+    }
+}
+
+/// Starts decoding the frames of a GIF or WebP file. Returns them with the width ×
+/// height of the image.
+fn open_frames(bytes: &Arc<[u8]>, format: ImageFormat) -> Result<(Frames<'static>, usize), String> {
+    let cursor = Cursor::new(Arc::clone(bytes));
+    let pixels = |(w, h): (u32, u32)| w as usize * h as usize;
+    if format == ImageFormat::Gif {
+        let decoder = GifDecoder::new(cursor).map_err(|err| format!("GIF decode error: {err}"))?;
+        let source_pixels = pixels(decoder.dimensions());
+        Ok((decoder.into_frames(), source_pixels))
+    } else {
+        let decoder =
+            WebPDecoder::new(cursor).map_err(|err| format!("WebP decode error: {err}"))?;
+        let source_pixels = pixels(decoder.dimensions());
+        Ok((decoder.into_frames(), source_pixels))
+    }
+}
+
+/// Wraps already decoded pixels (e.g. pasted from the clipboard) as a static image,
+/// downsampled the same way as loaded files.
+pub fn loaded_image_from_rgba(
+    size: [usize; 2],
+    rgba: &[u8],
+    max_dimension: Option<u32>,
+) -> LoadedImage {
+    let mut loaded = LoadedImage::from_frames(
+        vec![AnimationFrame {
+            image: egui::ColorImage::from_rgba_unmultiplied(size, rgba),
+            duration: Duration::from_millis(1000),
+        }],
+        false,
+    );
+    if let Some(max_dim) = max_dimension {
+        for frame in &mut loaded.frames {
+            downsample_frame(frame, max_dim);
+        }
+    }
+    loaded
+}
+
 /// Decodes a static image using the specified format.
 fn decode_static(bytes: &[u8], format: ImageFormat) -> Result<LoadedImage, String> {
     let image = image::load_from_memory_with_format(bytes, format)
@@ -160,22 +373,22 @@ fn frames_to_loaded_image(frames: Vec<Frame>, has_animation: bool) -> Result<Loa
         return Err("Image did not contain frames".to_string());
     }
 
-    let mut converted = Vec::with_capacity(frames.len());
-    for frame in frames {
-        let delay = duration_from_delay(frame.delay());
-        let buffer = frame.into_buffer();
-        let size = [buffer.width() as usize, buffer.height() as usize];
-        let pixels = buffer.into_raw();
-        let image = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
-        converted.push(AnimationFrame {
-            image,
-            duration: sanitize_duration(delay),
-        });
-    }
-
+    let converted = frames.into_iter().map(animation_frame).collect();
     Ok(LoadedImage::from_frames(converted, has_animation))
 }
 
+/// Converts a decoded image frame into an AnimationFrame.
+fn animation_frame(frame: Frame) -> AnimationFrame {
+    let delay = duration_from_delay(frame.delay());
+    let buffer = frame.into_buffer();
+    let size = [buffer.width() as usize, buffer.height() as usize];
+    let pixels = buffer.into_raw();
+    AnimationFrame {
+        image: egui::ColorImage::from_rgba_unmultiplied(size, &pixels),
+        duration: sanitize_duration(delay),
+    }
+}
+
 /// Helper to convert a DynamicImage into an egui ColorImage.
 fn color_image_from_dynamic(image: DynamicImage) -> egui::ColorImage {
     let rgba = image.to_rgba8();
@@ -225,62 +438,123 @@ mod avif_support {
     use std::time::Duration;
 
     pub fn decode(bytes: &[u8], first_frame_only: bool) -> Result<LoadedImage, String> {
-        let decoder =
-            DecoderGuard::new().ok_or_else(|| "Failed to create AVIF decoder".to_string())?;
+        let mut reader = AvifFrames::new(bytes)?;
+        let limit = if first_frame_only {
+            1
+        } else {
+            MAX_ANIMATION_FRAMES
+        };
 
-        // SAFETY: decoder.decoder is a valid pointer from DecoderGuard::new(),
-        // and bytes.as_ptr() is valid for bytes.len() as it comes from a slice.
-        unsafe {
-            let result =
-                libavif_sys::avifDecoderSetIOMemory(decoder.decoder, bytes.as_ptr(), bytes.len());
-            if result != libavif_sys::AVIF_RESULT_OK {
-                return Err(format!("avifDecoderSetIOMemory failed: {}", result as i32));
+        let mut frames = Vec::new();
+        while frames.len() < limit {
+            match reader.next_frame()? {
+                Some(frame) => frames.push(frame),
+                None => break,
             }
+        }
 
-            let result = libavif_sys::avifDecoderParse(decoder.decoder);
-            if result != libavif_sys::AVIF_RESULT_OK {
-                return Err(format!("avifDecoderParse failed: {}", result as i32));
-            }
+        if frames.is_empty() {
+            return Err("AVIF decode produced no frames".to_string());
         }
 
-        // SAFETY: decoder.decoder is a valid pointer to an initialized avifDecoder.
-        let image_count = unsafe { (*decoder.decoder).imageCount as usize };
-        // SAFETY: decoder.decoder is a valid pointer to an initialized avifDecoder.
-        let total_duration = unsafe { (*decoder.decoder).duration };
-        let fallback_duration = if image_count > 1 && total_duration > 0.0 {
-            total_duration / image_count as f64
-        } else if image_count > 1 {
-            0.1
-        } else {
-            0.0
-        };
+        Ok(LoadedImage::from_frames(frames, reader.image_count > 1))
+    }
 
-        let mut frames = Vec::new();
-        let mut frame_index: u32 = 0;
+    /// An AVIF decoder kept open over the file's bytes, decoding one frame at a time.
+    /// `B` must keep the bytes in place when moved (a Vec or a slice).
+    pub struct AvifFrames<B: AsRef<[u8]>> {
+        // Declared (and so dropped) before `_bytes`, which it reads from.
+        decoder: DecoderGuard,
         // Fallback RGB buffer kept for formats the SIMD path doesn't support.
-        let mut rgb_fallback = RgbImageGuard::new();
+        rgb_fallback: RgbImageGuard,
+        image_count: usize,
+        /// Frame duration in seconds used when the file has no timing for a frame.
+        fallback_duration: f64,
+        /// Index of the frame the next `next_frame` call decodes.
+        frame_index: u32,
+        _bytes: B,
+    }
 
-        let limit = if first_frame_only {
-            1
-        } else {
-            MAX_ANIMATION_FRAMES
-        };
+    impl<B: AsRef<[u8]>> AvifFrames<B> {
+        pub fn new(bytes: B) -> Result<Self, String> {
+            let decoder =
+                DecoderGuard::new().ok_or_else(|| "Failed to create AVIF decoder".to_string())?;
+
+            // SAFETY: decoder.decoder is a valid pointer from DecoderGuard::new(), and the
+            // slice stays valid for as long as the decoder: `bytes` is stored alongside it
+            // and its contents do not move with it.
+            unsafe {
+                let data = bytes.as_ref();
+                let result =
+                    libavif_sys::avifDecoderSetIOMemory(decoder.decoder, data.as_ptr(), data.len());
+                if result != libavif_sys::AVIF_RESULT_OK {
+                    return Err(format!("avifDecoderSetIOMemory failed: {}", result as i32));
+                }
 
-        while (frame_index as usize) < limit {
+                let result = libavif_sys::avifDecoderParse(decoder.decoder);
+                if result != libavif_sys::AVIF_RESULT_OK {
+                    return Err(format!("avifDecoderParse failed: {}", result as i32));
+                }
+            }
+
+            // SAFETY: decoder.decoder is a valid pointer to an initialized avifDecoder.
+            let image_count = unsafe { (*decoder.decoder).imageCount as usize };
             // SAFETY: decoder.decoder is a valid pointer to an initialized avifDecoder.
-            let result = unsafe { libavif_sys::avifDecoderNextImage(decoder.decoder) };
-            if result == libavif_sys::AVIF_RESULT_OK {
+            let total_duration = unsafe { (*decoder.decoder).duration };
+            let fallback_duration = if image_count > 1 && total_duration > 0.0 {
+                total_duration / image_count as f64
+            } else if image_count > 1 {
+                0.1
+            } else {
+                0.0
+            };
+
+            Ok(Self {
+                decoder,
+                rgb_fallback: RgbImageGuard::new(),
+                image_count,
+                fallback_duration,
+                frame_index: 0,
+                _bytes: bytes,
+            })
+        }
+
+        /// Width × height of the frames, known once the file is parsed.
+        pub fn source_pixels(&self) -> usize {
+            // SAFETY: decoder.image is set by avifDecoderParse and owned by the decoder.
+            unsafe {
+                let image = (*self.decoder.decoder).image;
+                if image.is_null() {
+                    0
+                } else {
+                    (*image).width as usize * (*image).height as usize
+                }
+            }
+        }
+
+        /// Decodes the next frame, or returns None after the last one.
+        pub fn next_frame(&mut self) -> Result<Option<AnimationFrame>, String> {
+            loop {
+                // SAFETY: decoder.decoder is a valid pointer to an initialized avifDecoder.
+                let result = unsafe { libavif_sys::avifDecoderNextImage(self.decoder.decoder) };
+                if result == libavif_sys::AVIF_RESULT_NO_IMAGES_REMAINING {
+                    return Ok(None);
+                }
+                if result != libavif_sys::AVIF_RESULT_OK {
+                    return Err(format!("avifDecoderNextImage failed: {}", result as i32));
+                }
+                let frame_index = self.frame_index;
+                self.frame_index += 1;
+
                 // SAFETY: decoder.decoder is a valid pointer to an initialized avifDecoder.
-                let image = unsafe { (*decoder.decoder).image };
+                let image = unsafe { (*self.decoder.decoder).image };
                 if image.is_null() {
-                    frame_index += 1;
                     continue;
                 }
 
                 // SAFETY: image is checked for null before dereferencing and is owned by the decoder.
                 let (width, height) = unsafe { ((*image).width, (*image).height) };
                 if width == 0 || height == 0 {
-                    frame_index += 1;
                     continue;
                 }
 
@@ -289,9 +563,9 @@ mod avif_support {
                 let pixels = match convert_yuv_to_rgba_simd(image) {
                     Ok(rgba) => rgba,
                     Err(_) => {
-                        rgb_fallback.ensure_allocated(image);
-                        rgb_fallback.convert_from_yuv(image);
-                        rgb_fallback.extract_pixels()
+                        self.rgb_fallback.ensure_allocated(image);
+                        self.rgb_fallback.convert_from_yuv(image);
+                        self.rgb_fallback.extract_pixels()
                     }
                 };
 
@@ -300,18 +574,18 @@ mod avif_support {
                 let duration_secs = unsafe {
                     let mut timing: libavif_sys::avifImageTiming = std::mem::zeroed();
                     let timing_result = libavif_sys::avifDecoderNthImageTiming(
-                        decoder.decoder,
+                        self.decoder.decoder,
                         frame_index,
                         &mut timing,
                     );
                     if timing_result == libavif_sys::AVIF_RESULT_OK && timing.duration > 0.0 {
                         timing.duration
                     } else {
-                        let img_timing = (*decoder.decoder).imageTiming.duration;
+                        let img_timing = (*self.decoder.decoder).imageTiming.duration;
                         if img_timing > 0.0 {
                             img_timing
-                        } else if image_count > 1 {
-                            fallback_duration.max(0.0)
+                        } else if self.image_count > 1 {
+                            self.fallback_duration.max(0.0)
                         } else {
                             0.0
                         }
@@ -321,24 +595,23 @@ mod avif_support {
                 // Build AnimationFrame directly — no intermediate struct needed.
                 let size = [width as usize, height as usize];
                 let color_image = ColorImage::from_rgba_unmultiplied(size, &pixels);
-                frames.push(AnimationFrame {
+                return Ok(Some(AnimationFrame {
                     image: color_image,
                     duration: sanitize_duration(Duration::from_secs_f64(duration_secs.max(0.0))),
-                });
-
-                frame_index += 1;
-            } else if result == libavif_sys::AVIF_RESULT_NO_IMAGES_REMAINING {
-                break;
-            } else {
-                return Err(format!("avifDecoderNextImage failed: {}", result as i32));
+                }));
             }
         }
 
-        if frames.is_empty() {
-            return Err("AVIF decode produced no frames".to_string());
+        /// Starts over at the first frame, keeping the decoder and its buffers.
+        pub fn rewind(&mut self) -> Result<(), String> {
+            // SAFETY: decoder.decoder is a valid pointer to a parsed avifDecoder.
+            let result = unsafe { libavif_sys::avifDecoderReset(self.decoder.decoder) };
+            if result != libavif_sys::AVIF_RESULT_OK {
+                return Err(format!("avifDecoderReset failed: {}", result as i32));
+            }
+            self.frame_index = 0;
+            Ok(())
         }
-
-        Ok(LoadedImage::from_frames(frames, image_count > 1))
     }
 
     // ─────────────────────────────────────────────────────────────────────────────
@@ -940,3 +1213,78 @@ mod avif_support {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Rgba, RgbaImage};
+
+    #[test]
+    fn test_load_poster_frame_by_index() {
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_poster_{}.gif", uuid::Uuid::new_v4()));
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            for shade in [0u8, 120, 240] {
+                let image = RgbaImage::from_pixel(4, 4, Rgba([shade, shade, shade, 255]));
+                let delay = Delay::from_numer_denom_ms(100, 1);
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, delay))
+                    .unwrap();
+            }
+        }
+        fs::write(&path, gif).unwrap();
+
+        let shade = |index| {
+            let image = load_poster_frame(&path, PosterFrame::Index(index), None).unwrap();
+            assert_eq!(image.size, [4, 4]);
+            image.pixels[0].r()
+        };
+        assert_eq!(shade(1), 120);
+        // Out-of-range indices fall back to the last frame.
+        assert_eq!(shade(7), 240);
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_animation_stream_plays_past_frame_limit() {
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_stream_{}.gif", uuid::Uuid::new_v4()));
+        let frame_count = MAX_ANIMATION_FRAMES + 6;
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            for index in 0..frame_count {
+                let shade = if index + 1 == frame_count { 255 } else { 0 };
+                let image = RgbaImage::from_pixel(2, 2, Rgba([shade, shade, shade, 255]));
+                let delay = Delay::from_numer_denom_ms(20, 1);
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, delay))
+                    .unwrap();
+            }
+        }
+        fs::write(&path, gif).unwrap();
+
+        let mut stream = AnimationStream::open(&path, None).unwrap();
+        assert_eq!(stream.source_pixels(), 4);
+        let mut last = None;
+        let mut decoded = 0;
+        while let Some(frame) = stream.next_frame().unwrap() {
+            assert_eq!(frame.duration, Duration::from_millis(20));
+            last = Some(frame);
+            decoded += 1;
+        }
+        assert_eq!(decoded, frame_count);
+        assert_eq!(last.unwrap().image.pixels[0].r(), 255);
+
+        // After a rewind the animation plays again from its first frame.
+        stream.rewind().unwrap();
+        let first = stream.next_frame().unwrap().unwrap();
+        assert_eq!(first.image.pixels[0].r(), 0);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,564 @@
+//! Crash-safe autosave journal.
+//!
+//! While the app runs, every change to the canvas is appended as a small entry to
+//! `journal.jsonl` (one JSON object per line, synced to disk after each write). The journal
+//! is periodically compacted into a full `snapshot.json` session and truncated. The file
+//! I/O happens on a writer thread, so the UI never waits for the disk.
+//!
+//! A `running.lock` marker exists for the lifetime of a run and is removed on clean
+//! shutdown. The running instance holds an OS lock on it, so a second instance can tell it
+//! apart from a leftover of a crash and does not touch the journal. If an unlocked marker
+//! is present on startup, the previous run crashed: the snapshot is loaded and the journal
+//! replayed on top of it to recover the unsaved changes. Before a new run overwrites
+//! them, the files of a crashed run are moved to a `<dir>.crashed-<time>` folder together
+//! with the replayed `session.json`, so nothing is lost when the run starts from another
+//! board.
+//!
+//! Entries carry absolute values (a whole block, the full order, all settings), so
+//! replaying a journal onto a snapshot that already contains its changes is harmless.
+
+use crate::constants::{JOURNAL_COMPACT_ENTRIES, JOURNAL_COMPACT_INTERVAL_SECS};
+use crate::session::{self, BlockData, Session, WatchedFolder};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Full session written at every compaction.
+const SNAPSHOT_FILE: &str = "snapshot.json";
+
+/// Append-only log of changes since the last compaction.
+const JOURNAL_FILE: &str = "journal.jsonl";
+
+/// Present while a run is in progress; left behind by a crash.
+const RUNNING_MARKER: &str = "running.lock";
+
+/// Replayed session of a crashed run, written next to its moved snapshot and log.
+const CRASHED_SESSION_FILE: &str = "session.json";
+
+/// Session-level state outside the block list.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct SessionSettings {
+    remembered_chains: Vec<Vec<String>>,
+    last_unboxed_ids: Vec<Uuid>,
+    last_boxed_id: Option<Uuid>,
+    zoom: f32,
+    show_file_names: bool,
+    #[serde(default)]
+    watched_folders: Vec<WatchedFolder>,
+    #[serde(default)]
+    audio_muted: bool,
+    #[serde(default = "session::default_exclusive_audio")]
+    exclusive_audio: bool,
+    #[serde(default = "session::default_animation_budget_mb")]
+    animation_budget_mb: u32,
+}
+
+impl SessionSettings {
+    fn of(session: &Session) -> Self {
+        Self {
+            remembered_chains: session.remembered_chains.clone(),
+            last_unboxed_ids: session.last_unboxed_ids.clone(),
+            last_boxed_id: session.last_boxed_id,
+            zoom: session.zoom,
+            show_file_names: session.show_file_names,
+            watched_folders: session.watched_folders.clone(),
+            audio_muted: session.audio_muted,
+            exclusive_audio: session.exclusive_audio,
+            animation_budget_mb: session.animation_budget_mb,
+        }
+    }
+
+    fn apply_to(self, session: &mut Session) {
+        session.remembered_chains = self.remembered_chains;
+        session.last_unboxed_ids = self.last_unboxed_ids;
+        session.last_boxed_id = self.last_boxed_id;
+        session.zoom = self.zoom;
+        session.show_file_names = self.show_file_names;
+        session.watched_folders = self.watched_folders;
+        session.audio_muted = self.audio_muted;
+        session.exclusive_audio = self.exclusive_audio;
+        session.animation_budget_mb = self.animation_budget_mb;
+    }
+}
+
+/// A single journaled mutation of the session.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum JournalEntry {
+    /// Inserts a top-level block (with its group children) or replaces the one with its ID.
+    PutBlock { block: BlockData },
+    /// Removes a top-level block and its group children.
+    RemoveBlock { id: Uuid },
+    /// Sets the order of the top-level blocks.
+    Order { ids: Vec<Uuid> },
+    /// Replaces the session-level settings.
+    Settings(SessionSettings),
+}
+
+impl JournalEntry {
+    fn apply(self, session: &mut Session) {
+        match self {
+            JournalEntry::PutBlock { block } => {
+                match session.blocks.iter_mut().find(|b| b.id == block.id) {
+                    Some(existing) => *existing = block,
+                    None => session.blocks.push(block),
+                }
+            }
+            JournalEntry::RemoveBlock { id } => session.blocks.retain(|b| b.id != id),
+            JournalEntry::Order { ids } => {
+                let rank: HashMap<Uuid, usize> =
+                    ids.into_iter().enumerate().map(|(i, id)| (id, i)).collect();
+                session
+                    .blocks
+                    .sort_by_key(|b| rank.get(&b.id).copied().unwrap_or(usize::MAX));
+            }
+            JournalEntry::Settings(settings) => settings.apply_to(session),
+        }
+    }
+}
+
+/// Computes the entries that turn `prev` into `next`.
+fn diff(prev: &Session, next: &Session) -> Vec<JournalEntry> {
+    let mut entries = Vec::new();
+
+    let prev_blocks: HashMap<Uuid, &BlockData> = prev.blocks.iter().map(|b| (b.id, b)).collect();
+    for block in &next.blocks {
+        if prev_blocks.get(&block.id).copied() != Some(block) {
+            entries.push(JournalEntry::PutBlock {
+                block: block.clone(),
+            });
+        }
+    }
+
+    let next_ids: HashSet<Uuid> = next.blocks.iter().map(|b| b.id).collect();
+    for block in &prev.blocks {
+        if !next_ids.contains(&block.id) {
+            entries.push(JournalEntry::RemoveBlock { id: block.id });
+        }
+    }
+
+    // Replaying puts and removes keeps surviving blocks in their old order and appends new
+    // ones; only record the order when that is not already the result.
+    let replayed_order: Vec<Uuid> = prev
+        .blocks
+        .iter()
+        .map(|b| b.id)
+        .filter(|id| next_ids.contains(id))
+        .chain(
+            next.blocks
+                .iter()
+                .map(|b| b.id)
+                .filter(|id| !prev_blocks.contains_key(id)),
+        )
+        .collect();
+    let next_order: Vec<Uuid> = next.blocks.iter().map(|b| b.id).collect();
+    if replayed_order != next_order {
+        entries.push(JournalEntry::Order { ids: next_order });
+    }
+
+    let settings = SessionSettings::of(next);
+    if SessionSettings::of(prev) != settings {
+        entries.push(JournalEntry::Settings(settings));
+    }
+
+    entries
+}
+
+/// Autosave journal stored in a dedicated directory.
+pub struct Journal {
+    dir: PathBuf,
+    /// Locked `RUNNING_MARKER` of the run being journaled.
+    marker: Option<File>,
+    writer: Option<Writer>,
+    /// Session state described by the snapshot plus the current log.
+    last: Option<Session>,
+    entries: usize,
+    last_compaction: Instant,
+}
+
+impl Journal {
+    /// Creates a journal rooted at `dir`. Nothing is written until `start`.
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            marker: None,
+            writer: None,
+            last: None,
+            entries: 0,
+            last_compaction: Instant::now(),
+        }
+    }
+
+    /// If the previous run did not shut down cleanly, rebuilds its session from the last
+    /// snapshot plus the replayed journal. Returns the session and the number of replayed
+    /// entries, or None after a clean shutdown or while another instance is running.
+    pub fn recover(&self) -> Option<Result<(Session, usize), String>> {
+        let marker = self.dir.join(RUNNING_MARKER);
+        if !marker.exists() {
+            return None;
+        }
+        if is_locked(&marker) {
+            log::info!("Autosave journal is in use by another instance");
+            return None;
+        }
+        Some(self.replay())
+    }
+
+    fn replay(&self) -> Result<(Session, usize), String> {
+        let snapshot_path = self.dir.join(SNAPSHOT_FILE);
+        let file = File::open(&snapshot_path)
+            .map_err(|err| format!("Failed to open {}: {err}", snapshot_path.display()))?;
+        let mut session = session::session_from_reader(BufReader::new(file))?;
+
+        let mut replayed = 0;
+        if let Ok(log) = File::open(self.dir.join(JOURNAL_FILE)) {
+            for (line_no, line) in BufReader::new(log).lines().enumerate() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                // A crash mid-write leaves a torn last line; everything before it is valid.
+                match serde_json::from_str::<JournalEntry>(&line) {
+                    Ok(entry) => {
+                        entry.apply(&mut session);
+                        replayed += 1;
+                    }
+                    Err(err) => {
+                        log::warn!("Stopping journal replay at line {}: {err}", line_no + 1);
+                        break;
+                    }
+                }
+            }
+        }
+        Ok((session, replayed))
+    }
+
+    /// Starts journaling a run from `session`: marks the run as in progress, then writes
+    /// a fresh snapshot and empties the log. Fails if another instance holds the journal.
+    /// If the previous run crashed, its files are moved aside first; returns the path of
+    /// its replayed session in that case.
+    pub fn start(&mut self, session: Session) -> Result<Option<PathBuf>, String> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|err| format!("Failed to create {}: {err}", self.dir.display()))?;
+        let marker = self.dir.join(RUNNING_MARKER);
+        let crashed = marker.exists();
+        let marker = lock_marker(&marker)?;
+        let preserved = if crashed {
+            Some(self.preserve_crashed_run()?)
+        } else {
+            None
+        };
+        self.marker = Some(marker);
+        self.writer = Some(Writer::spawn(self.dir.clone()));
+        self.compact(session)?;
+        Ok(preserved)
+    }
+
+    /// Moves the snapshot and log of a crashed run to a new `<dir>.crashed-<time>` folder
+    /// and writes its replayed session there. Returns the path of that session file, or of
+    /// the folder if the run could not be replayed.
+    fn preserve_crashed_run(&self) -> Result<PathBuf, String> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let name = self
+            .dir
+            .file_name()
+            .map_or_else(|| "autosave".into(), |n| n.to_string_lossy());
+        let backup = self.dir.with_file_name(format!("{name}.crashed-{stamp}"));
+        fs::create_dir_all(&backup)
+            .map_err(|err| format!("Failed to create {}: {err}", backup.display()))?;
+
+        let kept = match self.replay() {
+            Ok((session, _)) => {
+                let session_path = backup.join(CRASHED_SESSION_FILE);
+                let json = serde_json::to_vec_pretty(&session)
+                    .map_err(|err| format!("Failed to serialize session: {err}"))?;
+                fs::write(&session_path, json)
+                    .map_err(|err| format!("Failed to write {}: {err}", session_path.display()))?;
+                session_path
+            }
+            Err(err) => {
+                log::warn!("Could not replay the crashed run: {err}");
+                backup.clone()
+            }
+        };
+        for file in [SNAPSHOT_FILE, JOURNAL_FILE] {
+            let from = self.dir.join(file);
+            if from.exists() {
+                fs::rename(&from, backup.join(file))
+                    .map_err(|err| format!("Failed to move {}: {err}", from.display()))?;
+            }
+        }
+        log::info!("Kept the files of the crashed run in {}", backup.display());
+        Ok(kept)
+    }
+
+    /// Queues the changes between the last recorded state and `session` for appending,
+    /// compacting instead when enough entries or time have accumulated.
+    pub fn record(&mut self, session: Session) -> Result<(), String> {
+        let Some(last) = &self.last else {
+            return Err("Journal was not started".to_string());
+        };
+        let entries = diff(last, &session);
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let compaction_due = self.entries + entries.len() > JOURNAL_COMPACT_ENTRIES
+            || self.last_compaction.elapsed() > Duration::from_secs(JOURNAL_COMPACT_INTERVAL_SECS);
+        if compaction_due {
+            return self.compact(session);
+        }
+
+        let mut buffer = Vec::new();
+        for entry in &entries {
+            serde_json::to_writer(&mut buffer, entry)
+                .map_err(|err| format!("Failed to serialize journal entry: {err}"))?;
+            buffer.push(b'\n');
+        }
+        self.send(WriteOp::Append(buffer))?;
+
+        self.entries += entries.len();
+        self.last = Some(session);
+        Ok(())
+    }
+
+    /// Compacts the journal and removes the in-progress marker on clean shutdown. Waits
+    /// for all queued writes.
+    pub fn finish(&mut self, session: Session) -> Result<(), String> {
+        self.compact(session)?;
+        if let Some(writer) = self.writer.take() {
+            writer.finish()?;
+        }
+        let marker = self.dir.join(RUNNING_MARKER);
+        fs::remove_file(&marker)
+            .map_err(|err| format!("Failed to remove {}: {err}", marker.display()))?;
+        drop(self.marker.take());
+        Ok(())
+    }
+
+    /// Queues replacing the snapshot with `session` and truncating the log.
+    fn compact(&mut self, session: Session) -> Result<(), String> {
+        self.send(WriteOp::Compact(session.clone()))?;
+        self.last = Some(session);
+        self.entries = 0;
+        self.last_compaction = Instant::now();
+        Ok(())
+    }
+
+    fn send(&self, op: WriteOp) -> Result<(), String> {
+        let writer = self
+            .writer
+            .as_ref()
+            .ok_or_else(|| "Journal was not started".to_string())?;
+        writer
+            .tx
+            .send(op)
+            .map_err(|_| "Journal writer has stopped".to_string())
+    }
+}
+
+impl Drop for Journal {
+    /// Flushes queued writes. The marker stays, as after a crash, unless `finish` ran.
+    fn drop(&mut self) {
+        if let Some(writer) = self.writer.take() {
+            if let Err(err) = writer.finish() {
+                log::error!("{err}");
+            }
+        }
+    }
+}
+
+/// Work for the journal writer thread.
+enum WriteOp {
+    /// Appends serialized entries to the log and syncs it.
+    Append(Vec<u8>),
+    /// Atomically replaces the snapshot and truncates the log.
+    Compact(Session),
+}
+
+/// Thread performing the journal's file I/O in order.
+struct Writer {
+    tx: Sender<WriteOp>,
+    /// Returns the result of the last write.
+    thread: JoinHandle<Result<(), String>>,
+}
+
+impl Writer {
+    fn spawn(dir: PathBuf) -> Self {
+        let (tx, rx) = channel();
+        let thread = std::thread::spawn(move || write_loop(&dir, rx));
+        Self { tx, thread }
+    }
+
+    /// Waits until every queued write is done.
+    fn finish(self) -> Result<(), String> {
+        drop(self.tx);
+        self.thread
+            .join()
+            .map_err(|_| "Journal writer panicked".to_string())?
+    }
+}
+
+fn write_loop(dir: &Path, rx: Receiver<WriteOp>) -> Result<(), String> {
+    let mut log = None;
+    let mut result = Ok(());
+    for op in rx {
+        result = match op {
+            WriteOp::Append(buffer) => append(log.as_mut(), &buffer),
+            WriteOp::Compact(session) => write_snapshot(dir, &session).map(|file| {
+                log = Some(file);
+            }),
+        };
+        if let Err(err) = &result {
+            log::error!("Failed to write autosave journal: {err}");
+        }
+    }
+    result
+}
+
+fn append(log: Option<&mut File>, buffer: &[u8]) -> Result<(), String> {
+    let log = log.ok_or_else(|| "Journal log is not open".to_string())?;
+    log.write_all(buffer)
+        .and_then(|_| log.sync_data())
+        .map_err(|err| format!("Failed to append to journal: {err}"))
+}
+
+/// Atomically replaces the snapshot in `dir` with `session` and returns the truncated log.
+fn write_snapshot(dir: &Path, session: &Session) -> Result<File, String> {
+    let snapshot_path = dir.join(SNAPSHOT_FILE);
+    let temp_path = dir.join(format!("{SNAPSHOT_FILE}.tmp"));
+    let json =
+        serde_json::to_vec(session).map_err(|err| format!("Failed to serialize session: {err}"))?;
+    File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(&json)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, &snapshot_path))
+        .map_err(|err| format!("Failed to write {}: {err}", snapshot_path.display()))?;
+
+    let log_path = dir.join(JOURNAL_FILE);
+    File::create(&log_path)
+        .map_err(|err| format!("Failed to truncate {}: {err}", log_path.display()))
+}
+
+/// Opens and locks the in-progress marker, recording this process ID for diagnostics.
+fn lock_marker(path: &Path) -> Result<File, String> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(|err| format!("Failed to open {}: {err}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            return Err(format!("{} is in use by another instance", path.display()));
+        }
+        Err(TryLockError::Error(err)) => {
+            return Err(format!("Failed to lock {}: {err}", path.display()));
+        }
+    }
+    file.set_len(0)
+        .and_then(|_| file.write_all(std::process::id().to_string().as_bytes()))
+        .map_err(|err| format!("Failed to write {}: {err}", path.display()))?;
+    Ok(file)
+}
+
+/// Returns true if a running instance holds the lock on the marker at `path`.
+fn is_locked(path: &Path) -> bool {
+    File::open(path).is_ok_and(|file| matches!(file.try_lock(), Err(TryLockError::WouldBlock)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::CURRENT_SCHEMA_VERSION;
+
+    fn block(path: &str) -> BlockData {
+        BlockData {
+            id: Uuid::new_v4(),
+            size: [100.0, 100.0],
+            path: path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn session(blocks: Vec<BlockData>) -> Session {
+        Session {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            blocks,
+            remembered_chains: Vec::new(),
+            last_unboxed_ids: Vec::new(),
+            last_boxed_id: None,
+            zoom: 1.0,
+            show_file_names: false,
+            watched_folders: Vec::new(),
+            audio_muted: false,
+            exclusive_audio: true,
+            animation_budget_mb: 1024,
+        }
+    }
+
+    #[test]
+    fn test_journal_recovers_after_crash() {
+        let dir = std::env::temp_dir().join(format!("ma_blocks_journal_{}", Uuid::new_v4()));
+        let mut state = session(vec![block("a.png"), block("b.png"), block("c.png")]);
+
+        let mut journal = Journal::new(dir.clone());
+        assert!(journal.recover().is_none());
+        journal.start(state.clone()).unwrap();
+
+        state.blocks[1].counter = 4;
+        state.blocks.swap(0, 2);
+        journal.record(state.clone()).unwrap();
+        state.blocks.remove(1);
+        state.blocks.push(block("d.png"));
+        state.zoom = 2.0;
+        journal.record(state.clone()).unwrap();
+
+        // Another instance neither recovers nor takes over the running journal.
+        let other = Journal::new(dir.clone());
+        assert!(other.recover().is_none());
+        assert!(Journal::new(dir.clone()).start(state.clone()).is_err());
+
+        // Simulate a crash that tore the last write.
+        drop(journal);
+        let mut log = fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(JOURNAL_FILE))
+            .unwrap();
+        log.write_all(b"{\"op\":\"put_bl").unwrap();
+
+        let (recovered, replayed) = Journal::new(dir.clone()).recover().unwrap().unwrap();
+        assert!(recovered == state);
+        assert!(replayed > 0);
+
+        // Starting over keeps the crashed run aside.
+        let mut journal = Journal::new(dir.clone());
+        let preserved = journal.start(session(Vec::new())).unwrap().unwrap();
+        let file = File::open(&preserved).unwrap();
+        assert!(session::session_from_reader(file).unwrap() == state);
+
+        // A clean shutdown leaves nothing to recover.
+        journal.finish(state).unwrap();
+        assert!(Journal::new(dir.clone()).recover().is_none());
+        assert!(Journal::new(dir.clone())
+            .start(recovered)
+            .unwrap()
+            .is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(preserved.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,324 @@
+//! Prioritized background loading of image files on a fixed pool of worker threads.
+//! Besides whole images, a queue can run other per-file jobs, such as poster frames.
+//!
+//! Requests wait in a queue until a worker is free. The most urgent request goes first:
+//! full animations the user asked to play, then first frames of blocks in the viewport,
+//! then the rest, each in the order they were queued. The canvas re-ranks queued first
+//! frames as it scrolls (`reprioritize`), and loads for blocks deleted in the meantime
+//! are dropped (`cancel`), including ones already running.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
+
+/// Urgency of a load; higher loads first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LoadPriority {
+    /// First frame of a block outside the viewport.
+    Offscreen,
+    /// First frame of a block in the viewport, or of a newly added file.
+    Visible,
+    /// Full animation requested by clicking a block.
+    Full,
+}
+
+/// Decodes a file: (path, job) to the response sent to the app. For image loads the job
+/// is whether to decode the first frame only.
+type LoadFn<J, R> = dyn Fn(&Path, J) -> R + Send + Sync;
+
+struct LoadRequest<J> {
+    path: PathBuf,
+    job: J,
+    priority: LoadPriority,
+    seq: u64,
+}
+
+/// Requests not yet collected with `finish` for one path.
+#[derive(Default)]
+struct Outstanding {
+    count: usize,
+    /// How many of them were cancelled after they started.
+    cancelled: usize,
+}
+
+struct QueueState<J> {
+    pending: Vec<LoadRequest<J>>,
+    next_seq: u64,
+    outstanding: HashMap<PathBuf, Outstanding>,
+    closed: bool,
+}
+
+impl<J> Default for QueueState<J> {
+    fn default() -> Self {
+        Self {
+            pending: Vec::new(),
+            next_seq: 0,
+            outstanding: HashMap::new(),
+            closed: false,
+        }
+    }
+}
+
+impl<J> QueueState<J> {
+    fn push(&mut self, path: PathBuf, job: J, priority: LoadPriority) {
+        self.outstanding.entry(path.clone()).or_default().count += 1;
+        self.pending.push(LoadRequest {
+            path,
+            job,
+            priority,
+            seq: self.next_seq,
+        });
+        self.next_seq += 1;
+    }
+
+    /// Takes the most urgent request, oldest first among equals.
+    fn pop(&mut self) -> Option<LoadRequest<J>> {
+        let index = self
+            .pending
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, r)| (r.priority, std::cmp::Reverse(r.seq)))?
+            .0;
+        Some(self.pending.swap_remove(index))
+    }
+
+    fn cancel(&mut self, path: &Path) {
+        let Some(outstanding) = self.outstanding.get_mut(path) else {
+            return;
+        };
+        let before = self.pending.len();
+        self.pending.retain(|r| r.path != path);
+        outstanding.count -= before - self.pending.len();
+        outstanding.cancelled = outstanding.count;
+        if outstanding.count == 0 {
+            self.outstanding.remove(path);
+        }
+    }
+
+    /// Returns false if the finished load was cancelled.
+    fn finish(&mut self, path: &Path) -> bool {
+        let Some(outstanding) = self.outstanding.get_mut(path) else {
+            // Not queued here (e.g. a pasted image).
+            return true;
+        };
+        outstanding.count -= 1;
+        let wanted = if outstanding.cancelled > 0 {
+            outstanding.cancelled -= 1;
+            false
+        } else {
+            true
+        };
+        if outstanding.count == 0 {
+            self.outstanding.remove(path);
+        }
+        wanted
+    }
+
+    fn is_queued(&self, path: &Path) -> bool {
+        self.outstanding
+            .get(path)
+            .is_some_and(|o| o.count > o.cancelled)
+    }
+}
+
+struct Shared<J> {
+    state: Mutex<QueueState<J>>,
+    available: Condvar,
+}
+
+/// Queue of loads shared with its worker threads, which stop when it is dropped. `J` is
+/// the job run for a path; by default, whether an image load decodes the first frame only.
+pub struct LoadQueue<J = bool> {
+    shared: Arc<Shared<J>>,
+}
+
+impl<J> LoadQueue<J> {
+    /// Starts `workers` threads that run `load` for queued requests and send the results
+    /// to `tx`.
+    pub fn new<R: Send + 'static>(
+        workers: usize,
+        tx: Sender<R>,
+        load: impl Fn(&Path, J) -> R + Send + Sync + 'static,
+    ) -> Self
+    where
+        J: Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState::default()),
+            available: Condvar::new(),
+        });
+        let load: Arc<LoadFn<J, R>> = Arc::new(load);
+        for index in 0..workers.max(1) {
+            let shared = Arc::clone(&shared);
+            let load = Arc::clone(&load);
+            let tx = tx.clone();
+            std::thread::Builder::new()
+                .name(format!("image-load-{index}"))
+                .spawn(move || load_worker(&shared, &*load, &tx))
+                .expect("failed to spawn image load worker");
+        }
+        Self { shared }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, QueueState<J>> {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Queues a load of `path`; its result arrives on the channel given to `new`.
+    pub fn push(&self, path: PathBuf, job: J, priority: LoadPriority) {
+        self.state().push(path, job, priority);
+        self.shared.available.notify_one();
+    }
+
+    /// Returns true if requests are waiting for a worker.
+    pub fn has_pending(&self) -> bool {
+        !self.state().pending.is_empty()
+    }
+
+    /// Returns true if a load of `path` is waiting, running, or not yet collected.
+    pub fn is_queued(&self, path: &Path) -> bool {
+        self.state().is_queued(path)
+    }
+
+    /// Re-ranks waiting first-frame loads: `priority_of` returns the new priority of a
+    /// path, or None to keep it. Full animation loads stay first.
+    pub fn reprioritize(&self, priority_of: impl Fn(&Path) -> Option<LoadPriority>) {
+        for request in &mut self.state().pending {
+            if request.priority == LoadPriority::Full {
+                continue;
+            }
+            if let Some(priority) = priority_of(&request.path) {
+                request.priority = priority.min(LoadPriority::Visible);
+            }
+        }
+    }
+
+    /// Drops waiting loads of `path`; the results of running ones are discarded by
+    /// `finish`.
+    pub fn cancel(&self, path: &Path) {
+        self.state().cancel(path);
+    }
+
+    /// Cancels every load, e.g. when another board is opened.
+    pub fn cancel_all(&self) {
+        let mut state = self.state();
+        let paths: Vec<PathBuf> = state.outstanding.keys().cloned().collect();
+        for path in paths {
+            state.cancel(&path);
+        }
+    }
+
+    /// Marks a received result for `path` as handled. Returns false if the load was
+    /// cancelled, in which case the result should be ignored.
+    pub fn finish(&self, path: &Path) -> bool {
+        self.state().finish(path)
+    }
+}
+
+impl<J> Drop for LoadQueue<J> {
+    fn drop(&mut self) {
+        self.state().closed = true;
+        self.shared.available.notify_all();
+    }
+}
+
+/// Runs one worker: waits for the most urgent request, loads it, and sends the result.
+fn load_worker<J, R>(shared: &Shared<J>, load: &LoadFn<J, R>, tx: &Sender<R>) {
+    loop {
+        let request = {
+            let mut state = shared
+                .state
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            loop {
+                if state.closed {
+                    return;
+                }
+                if let Some(request) = state.pop() {
+                    break request;
+                }
+                state = shared
+                    .available
+                    .wait(state)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+            }
+        };
+        if tx.send(load(&request.path, request.job)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_loader::ImageLoadResponse;
+
+    fn popped(state: &mut QueueState<bool>) -> Vec<String> {
+        std::iter::from_fn(|| state.pop())
+            .map(|r| r.path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_queue_order() {
+        let mut state = QueueState::default();
+        state.push("a".into(), true, LoadPriority::Offscreen);
+        state.push("b".into(), true, LoadPriority::Visible);
+        state.push("c".into(), true, LoadPriority::Offscreen);
+        state.push("d".into(), false, LoadPriority::Full);
+        state.push("e".into(), true, LoadPriority::Visible);
+        assert_eq!(popped(&mut state), ["d", "b", "e", "a", "c"]);
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut state = QueueState::default();
+        state.push("a".into(), true, LoadPriority::Visible);
+        state.push("b".into(), true, LoadPriority::Visible);
+        state.push("b".into(), false, LoadPriority::Full);
+
+        // One load of "b" is running when it is cancelled; the other never starts.
+        let running = state.pop().unwrap();
+        assert_eq!(running.path, Path::new("b"));
+        state.cancel(Path::new("b"));
+        assert!(!state.is_queued(Path::new("b")));
+        assert_eq!(popped(&mut state), ["a"]);
+        assert!(!state.finish(Path::new("b")));
+        assert!(state.finish(Path::new("a")));
+
+        // Queued again later, it is delivered normally.
+        state.push("b".into(), true, LoadPriority::Visible);
+        assert!(state.is_queued(Path::new("b")));
+        assert!(state.pop().is_some());
+        assert!(state.finish(Path::new("b")));
+        assert!(state.outstanding.is_empty());
+
+        // Results that did not go through the queue are always wanted.
+        assert!(state.finish(Path::new("pasted.png")));
+    }
+
+    #[test]
+    fn test_workers_deliver_results() {
+        let (tx, rx) = std::sync::mpsc::channel::<ImageLoadResponse>();
+        let queue = LoadQueue::new(2, tx, |path, _| Err((path.to_path_buf(), "test".into())));
+        for name in ["a", "b", "c"] {
+            queue.push(name.into(), true, LoadPriority::Visible);
+        }
+        let mut loaded: Vec<PathBuf> = (0..3)
+            .map(|_| match rx.recv().unwrap() {
+                Ok((path, ..)) | Err((path, _)) => path,
+            })
+            .collect();
+        loaded.sort();
+        assert_eq!(loaded, ["a", "b", "c"].map(PathBuf::from));
+        for path in &loaded {
+            assert!(queue.finish(path));
+        }
+        assert!(!queue.is_queued(Path::new("a")));
+    }
+}
@@ -1,38 +1,81 @@
+mod audio;
 mod block;
 mod block_manager;
+mod bundle;
+mod cli;
+mod clip;
+mod clipboard;
 mod constants;
+mod export;
+mod history;
 mod image_loader;
+mod journal;
+mod load_queue;
 mod paths;
+mod relink;
+mod session;
+mod thumbnail_cache;
 mod video_stream;
+mod watch;
 
 use block::{
-    block_control_rects, handle_blocks_resizing, BlockControlHover, BlockRenderConfig, ImageBlock,
-    InteractionState, ResizeHandle,
+    block_control_rects, handle_blocks_resizing, poster_button_rect, speed_button_rect,
+    BlockControlHover, BlockRenderConfig, ImageBlock, InteractionState, ResizeHandle, TrimPoint,
+    VideoControlRects,
 };
-use block_manager::{BlockManager, ChainedIds};
+use block_manager::{BlockManager, ChainedIds, SortKey};
 use constants::{
-    CANVAS_PADDING, CANVAS_WORKING_WIDTH, COLOR_GROUP_PLACEHOLDER, COLOR_TOOLBAR_BG,
-    INITIAL_WINDOW_HEIGHT, INITIAL_WINDOW_WIDTH, MAX_BLOCK_DIMENSION, MIN_CANVAS_INNER_WIDTH,
+    ANIMATION_BUDGET_CHOICES_MB, CANVAS_PADDING, CANVAS_WORKING_WIDTH, COLOR_GROUP_PLACEHOLDER,
+    COLOR_TOOLBAR_BG, DETAIL_DECODE_LIMIT, DETAIL_TEXTURE_BUDGET_BYTES, EXPORT_PROGRESS_WIDTH,
+    IMAGE_LOAD_WORKERS, INITIAL_WINDOW_HEIGHT, INITIAL_WINDOW_WIDTH, JOURNAL_SYNC_INTERVAL_SECS,
+    MAX_BLOCK_DIMENSION, MIN_CANVAS_INNER_WIDTH, POSTER_LOAD_WORKERS, THUMBNAIL_CACHE_MAX_BYTES,
     TOOLBAR_BUTTON_SIZE, TOOLBAR_ICON_SIZE, TOOLBAR_START_SPACING,
 };
 use eframe::egui::{self, Color32, Pos2, Rect, RichText, Sense, UiBuilder, Vec2};
 use egui::{pos2, vec2};
+use history::History;
+use journal::Journal;
+use load_queue::{LoadPriority, LoadQueue};
 use paths::AppPaths;
-use serde::{Deserialize, Serialize};
+use session::{BlockData, Session, CURRENT_SCHEMA_VERSION};
 
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::time::Duration;
+use thumbnail_cache::ThumbnailCache;
 use uuid::Uuid;
+use watch::{FolderWatcher, WatchEvent};
 
 fn main() -> eframe::Result<()> {
     env_logger::init();
+    let action = cli::parse_args(std::env::args_os().skip(1));
+
     ffmpeg_next::init().expect("ffmpeg init failed");
     // Suppress ffmpeg's noisy stderr (e.g. Opus audio parser warnings from
-    // WebM files — we ignore audio tracks entirely).
+    // WebM files, which are harmless for playback).
     ffmpeg_next::util::log::set_level(ffmpeg_next::util::log::Level::Fatal);
 
+    let args = match action {
+        Ok(cli::CliAction::Run(args)) => args,
+        Ok(cli::CliAction::Export(export_args)) => {
+            if let Err(err) = run_export(export_args) {
+                eprintln!("ma_blocks2: {err}");
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Ok(cli::CliAction::PrintHelp) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Err(err) => {
+            eprintln!("ma_blocks2: {err}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([INITIAL_WINDOW_WIDTH, INITIAL_WINDOW_HEIGHT])
@@ -45,58 +88,35 @@ fn main() -> eframe::Result<()> {
         options,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::new(MaBlocksApp::new(cc)))
+            Ok(Box::new(MaBlocksApp::new(cc, args)))
         }),
     )
 }
 
-/// Represents a saved application session, containing blocks and their relationships.
-#[derive(Serialize, Deserialize)]
-struct Session {
-    blocks: Vec<BlockData>,
-    #[serde(default)]
-    remembered_chains: Vec<Vec<String>>,
-    #[serde(default)]
-    last_unboxed_ids: Vec<Uuid>,
-    #[serde(default)]
-    last_boxed_id: Option<Uuid>,
-    #[serde(default = "default_zoom")]
-    zoom: f32,
-    #[serde(default)]
-    show_file_names: bool,
-}
-
-fn default_zoom() -> f32 {
-    1.0
-}
-
-/// Serialized form of an ImageBlock for persistence.
-#[derive(Serialize, Deserialize)]
-struct BlockData {
-    id: Uuid,
-    position: [f32; 2],
-    size: [f32; 2],
-    path: String,
-    chained: bool,
-    animation_enabled: bool,
-    counter: i32,
-    #[serde(default)]
-    counter_start_day: u32,
-    #[serde(default)]
-    is_group: bool,
-    #[serde(default)]
-    group_name: String,
-    #[serde(default)]
-    color: [u8; 4],
-    #[serde(default)]
-    children: Vec<BlockData>,
+/// Renders a saved session to PNG for the `export` command, without opening a window.
+fn run_export(args: cli::ExportArgs) -> Result<(), String> {
+    let file = std::fs::File::open(&args.session)
+        .map_err(|err| format!("Failed to open {}: {err}", args.session.display()))?;
+    let session = session::session_from_reader(std::io::BufReader::new(file))?;
+    let options = export::ExportOptions {
+        scale: args.scale,
+        inner_width: args.inner_width.unwrap_or(CANVAS_WORKING_WIDTH),
+        show_file_names: args.show_file_names || session.show_file_names,
+    };
+    let [width, height] = export::export_png(session, &args.output, options, &|_| {})?;
+    println!("Exported {width}x{height} px to {}", args.output.display());
+    Ok(())
 }
 
 /// Captures pointer and modifier state for a single frame.
 struct InputSnapshot {
     hover_pos: Option<Pos2>,
     interact_pos: Option<Pos2>,
+    /// Last known pointer position; kept while files are dragged in from the desktop.
+    latest_pos: Option<Pos2>,
     primary_clicked: bool,
+    primary_pressed: bool,
+    primary_down: bool,
     secondary_clicked: bool,
     secondary_pressed: bool,
     secondary_released: bool,
@@ -105,6 +125,8 @@ struct InputSnapshot {
     zoom_delta: f32,
     ctrl: bool,
     shift: bool,
+    files_hovered: bool,
+    dropped_files: Vec<PathBuf>,
 }
 
 impl InputSnapshot {
@@ -112,7 +134,10 @@ impl InputSnapshot {
         ui.input(|i| Self {
             hover_pos: i.pointer.hover_pos(),
             interact_pos: i.pointer.interact_pos(),
+            latest_pos: i.pointer.latest_pos(),
             primary_clicked: i.pointer.button_clicked(egui::PointerButton::Primary),
+            primary_pressed: i.pointer.button_pressed(egui::PointerButton::Primary),
+            primary_down: i.pointer.primary_down(),
             secondary_clicked: i.pointer.button_clicked(egui::PointerButton::Secondary),
             secondary_pressed: i.pointer.button_pressed(egui::PointerButton::Secondary),
             secondary_released: i.pointer.button_released(egui::PointerButton::Secondary),
@@ -121,14 +146,73 @@ impl InputSnapshot {
             zoom_delta: i.zoom_delta(),
             ctrl: i.modifiers.ctrl,
             shift: i.modifiers.shift,
+            files_hovered: !i.raw.hovered_files.is_empty(),
+            dropped_files: i
+                .raw
+                .dropped_files
+                .iter()
+                .filter_map(|f| f.path.clone())
+                .collect(),
         })
     }
 }
 
+/// Where a file dropped from the desktop goes once it has loaded.
+#[derive(Clone, Copy)]
+enum DropTarget {
+    /// Canvas position (in canvas units) the block is placed at before the reflow.
+    Canvas(Pos2),
+    /// Box block the new block is added to.
+    Box(Uuid),
+}
+
+/// A decoded poster frame for a block, with the file and poster it was decoded for.
+type PosterLoad = (
+    PathBuf,
+    Uuid,
+    image_loader::PosterFrame,
+    Option<egui::ColorImage>,
+);
+
+/// A sharper still image for a block: the file, detail tier and poster it was decoded for.
+type DetailLoad = (
+    PathBuf,
+    Uuid,
+    u32,
+    Option<image_loader::PosterFrame>,
+    Option<egui::ColorImage>,
+);
+
+/// A finished clip export: the source video block and the written file.
+type ClipExport = (Uuid, Result<PathBuf, String>);
+
+/// Progress of an export running in the background.
+enum ExportUpdate<T> {
+    /// Completed fraction (0..1).
+    Progress(f32),
+    /// The written file and what was written: a PNG's size in pixels, or the number of
+    /// media files in a bundle.
+    Done(PathBuf, Result<T, String>),
+}
+
+/// An export running in the background, with its last reported progress.
+type ExportJob<T> = (Receiver<ExportUpdate<T>>, f32);
+
+/// Media files found under dropped or pasted paths, with the Box and canvas position
+/// they were dropped on.
+type ScannedDrop = (Vec<PathBuf>, Option<Uuid>, Option<Pos2>);
+
+/// A finished relink scan: the chosen folder, the number of missing files it was run
+/// for, and the new path of each relinked one.
+type RelinkPlan = (PathBuf, usize, HashMap<String, PathBuf>);
+
 /// The main application state holding all blocks, UI interaction states, and resource management.
 struct MaBlocksApp {
     block_manager: BlockManager,
+    history: History,
     resizing_state: Option<InteractionState>,
+    /// Video block whose scrub bar is being dragged.
+    scrubbing: Option<Uuid>,
     skip_chain_cancel: bool,
     working_inner_width: f32,
     session_file: Option<PathBuf>,
@@ -139,8 +223,52 @@ struct MaBlocksApp {
     hovered_box_id: Option<Uuid>,
     image_rx: Option<Receiver<image_loader::ImageLoadResponse>>,
     image_tx: Sender<image_loader::ImageLoadResponse>,
+    /// Files waiting to be decoded by the image load workers (results go to `image_tx`).
+    load_queue: LoadQueue,
+    /// Poster frames waiting to be decoded, by file (results go to `poster_rx`).
+    poster_queue: LoadQueue<(Uuid, image_loader::PosterFrame)>,
+    /// Poster frames decoded in the background, by block (None if decoding failed).
+    poster_rx: Receiver<PosterLoad>,
+    /// Detail textures waiting to be decoded for zoomed-in blocks, by file (results go to
+    /// `detail_rx`).
+    detail_queue: LoadQueue<(Uuid, u32, Option<image_loader::PosterFrame>)>,
+    detail_rx: Receiver<DetailLoad>,
+    /// Detail decodes queued or running, by block, with their file and estimated texture
+    /// size in bytes.
+    detail_pending: HashMap<Uuid, (PathBuf, usize)>,
+    /// Blocks whose detail decode failed, with the file it failed for; retried once the
+    /// block shows another file or its file is loaded again.
+    detail_failed: HashMap<Uuid, String>,
+    /// Clips of video blocks exported in the background.
+    clip_rx: Receiver<ClipExport>,
+    clip_tx: Sender<ClipExport>,
+    /// Dropped or watched files still loading that go to a specific place, by path.
+    pending_drops: HashMap<PathBuf, DropTarget>,
+    /// Dropped or pasted paths expanded in the background.
+    scan_rx: Receiver<ScannedDrop>,
+    scan_tx: Sender<ScannedDrop>,
     paths: Option<AppPaths>,
-    last_auto_save_time: f64,
+    journal: Option<Journal>,
+    last_journal_sync_time: f64,
+    recovery_notice: Option<String>,
+    show_missing_panel: bool,
+    relink_by_fingerprint: bool,
+    /// Relink scan running in the background, if any.
+    relink_rx: Option<Receiver<RelinkPlan>>,
+    /// PNG export running in the background, if any.
+    png_export: Option<ExportJob<[usize; 2]>>,
+    /// Bundle export running in the background, if any.
+    bundle_export: Option<ExportJob<usize>>,
+    watcher: FolderWatcher,
+    show_watch_panel: bool,
+    /// Global mute of all video sound.
+    audio_muted: bool,
+    /// Only the video started (or unmuted) last plays its sound.
+    exclusive_audio: bool,
+    /// Video block heard while `exclusive_audio` is on.
+    audible_block: Option<Uuid>,
+    /// Shows the memory taken by animations in a corner of the canvas.
+    show_memory_overlay: bool,
 }
 
 impl MaBlocksApp {
@@ -178,18 +306,70 @@ impl MaBlocksApp {
     // ─────────────────────────────────────────────────────────────────────────────
 
     /// Initializes the application state, sets up channels, and discovers project directories.
-    fn new(cc: &eframe::CreationContext<'_>) -> Self {
+    /// Command-line sessions and images are opened on top of (or instead of) the restored one.
+    fn new(cc: &eframe::CreationContext<'_>, args: cli::CliArgs) -> Self {
         let (tx, rx) = channel();
+        let (poster_tx, poster_rx) = channel();
+        let (detail_tx, detail_rx) = channel();
+        let (clip_tx, clip_rx) = channel();
+        let (scan_tx, scan_rx) = channel();
         let paths = AppPaths::from_project_dirs();
         if let Some(ref p) = paths {
             if let Err(err) = p.ensure_dirs_exist() {
                 log::error!("Failed to create default directories: {err}");
             }
         }
+        let journal = paths
+            .as_ref()
+            .map(|p| Journal::new(p.sessions.join("autosave")));
+        let thumbnails = paths
+            .as_ref()
+            .map(|p| ThumbnailCache::new(p.thumbnails.clone(), THUMBNAIL_CACHE_MAX_BYTES));
+        if let Some(cache) = thumbnails.clone() {
+            std::thread::spawn(move || match cache.trim() {
+                Ok(0) => {}
+                Ok(removed) => log::info!("Removed {removed} old thumbnails from the cache"),
+                Err(err) => log::error!("Failed to trim the thumbnail cache: {err}"),
+            });
+        }
+        let load_queue = LoadQueue::new(
+            IMAGE_LOAD_WORKERS,
+            tx.clone(),
+            move |path, first_frame_only| {
+                load_image_file(thumbnails.as_ref(), path, first_frame_only)
+            },
+        );
+        let ctx = cc.egui_ctx.clone();
+        let poster_queue = LoadQueue::new(
+            POSTER_LOAD_WORKERS,
+            poster_tx,
+            move |path, (id, poster): (Uuid, image_loader::PosterFrame)| {
+                let image =
+                    image_loader::load_poster_frame(path, poster, Some(MAX_BLOCK_DIMENSION as u32))
+                        .map_err(|err| log::error!("Failed to load poster frame: {err}"))
+                        .ok();
+                ctx.request_repaint();
+                (path.to_path_buf(), id, poster, image)
+            },
+        );
+        let ctx = cc.egui_ctx.clone();
+        let detail_queue = LoadQueue::new(
+            DETAIL_DECODE_LIMIT,
+            detail_tx,
+            move |path, (id, tier, poster): (Uuid, u32, Option<image_loader::PosterFrame>)| {
+                let image = image_loader::load_still_image(path, poster, tier)
+                    .map_err(|err| log::error!("Failed to load detail texture: {err}"))
+                    .ok();
+                ctx.request_repaint();
+                (path.to_path_buf(), id, tier, poster, image)
+            },
+        );
 
         let mut app = Self {
             block_manager: BlockManager::new(),
+            history: History::default(),
             resizing_state: None,
+            scrubbing: None,
             skip_chain_cancel: false,
             working_inner_width: CANVAS_WORKING_WIDTH,
             session_file: None,
@@ -200,13 +380,92 @@ impl MaBlocksApp {
             hovered_box_id: None,
             image_rx: Some(rx),
             image_tx: tx,
+            load_queue,
+            poster_queue,
+            poster_rx,
+            detail_queue,
+            detail_rx,
+            detail_pending: HashMap::new(),
+            detail_failed: HashMap::new(),
+            clip_rx,
+            clip_tx,
+            pending_drops: HashMap::new(),
+            scan_rx,
+            scan_tx,
             paths,
-            last_auto_save_time: 0.0,
+            journal,
+            last_journal_sync_time: 0.0,
+            recovery_notice: None,
+            show_missing_panel: false,
+            relink_by_fingerprint: false,
+            relink_rx: None,
+            png_export: None,
+            bundle_export: None,
+            watcher: FolderWatcher::new(&cc.egui_ctx),
+            show_watch_panel: false,
+            audio_muted: false,
+            exclusive_audio: true,
+            audible_block: None,
+            show_memory_overlay: false,
+        };
+
+        let restore_previous = !args.new_board && args.session.is_none();
+        let recovered = match app
+            .journal
+            .as_ref()
+            .filter(|_| restore_previous)
+            .and_then(Journal::recover)
+        {
+            Some(Ok(recovered)) => Some(recovered),
+            Some(Err(err)) => {
+                log::error!("Failed to recover autosave journal: {err}");
+                None
+            }
+            None => None,
         };
 
-        if let Some(storage) = cc.storage {
-            if let Some(session) = eframe::get_value::<Session>(storage, eframe::APP_KEY) {
-                app.apply_session_data(&cc.egui_ctx, session);
+        let was_recovered = recovered.is_some();
+        if let Some((session, replayed)) = recovered {
+            app.apply_session_data(&cc.egui_ctx, session);
+            app.recovery_notice = Some(format!(
+                "MaBlocks did not shut down cleanly last time. The session was recovered \
+                 from the autosave journal ({replayed} unsaved changes replayed)."
+            ));
+        } else if let Some(storage) = cc.storage.filter(|_| restore_previous) {
+            match session::session_from_storage(storage) {
+                Some(Ok(session)) => app.apply_session_data(&cc.egui_ctx, session),
+                Some(Err(err)) => log::error!("Failed to restore previous session: {err}"),
+                None => {}
+            }
+        }
+
+        if let Some(path) = args.session {
+            app.open_session_file(&cc.egui_ctx, path);
+        }
+        for path in args.images {
+            app.trigger_image_load(path, true);
+        }
+        if let Some(zoom) = args.zoom {
+            app.zoom = zoom;
+        }
+
+        // Starting the journal moves the files of a crashed run aside rather than
+        // overwriting them, e.g. when `--new` or a session file skipped the recovery.
+        let session = app.to_session();
+        if let Some(journal) = app.journal.as_mut() {
+            match journal.start(session) {
+                Ok(Some(crashed)) if !was_recovered => {
+                    app.recovery_notice = Some(format!(
+                        "MaBlocks did not shut down cleanly last time. Its unsaved session \
+                         was not restored; it has been kept in {}.",
+                        crashed.display()
+                    ));
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    log::error!("Failed to start autosave journal: {err}");
+                    app.journal = None;
+                }
             }
         }
 
@@ -214,7 +473,14 @@ impl MaBlocksApp {
     }
 
     fn apply_session_data(&mut self, ctx: &egui::Context, session: Session) {
+        // Loads for the previous board would otherwise come back as new blocks.
+        self.load_queue.cancel_all();
+        self.poster_queue.cancel_all();
+        self.detail_queue.cancel_all();
+        self.detail_pending.clear();
+        self.detail_failed.clear();
         self.block_manager.clear();
+        self.history.clear();
         for block_data in session.blocks {
             if let Some(block) = self.data_to_block_skeleton(ctx, block_data) {
                 self.block_manager.push(block);
@@ -226,13 +492,20 @@ impl MaBlocksApp {
         self.last_boxed_id = session.last_boxed_id;
         self.zoom = session.zoom;
         self.show_file_names = session.show_file_names;
+        self.watcher.set_folders(session.watched_folders);
+        self.audio_muted = session.audio_muted;
+        self.exclusive_audio = session.exclusive_audio;
+        self.audible_block = None;
+        audio::set_muted(self.audio_muted);
+        self.block_manager
+            .set_animation_budget_mb(session.animation_budget_mb);
         self.reorder_and_reflow(None);
     }
 
     /// Opens a file dialog to pick images and triggers background loading for each.
     fn load_images(&mut self) {
-        let mut dialog = rfd::FileDialog::new()
-            .add_filter("Images", &["png", "jpg", "jpeg", "gif", "webp", "avif", "webm", "mp4"]);
+        let mut dialog =
+            rfd::FileDialog::new().add_filter("Images", image_loader::SUPPORTED_EXTENSIONS);
 
         if let Some(ref p) = self.paths {
             dialog = dialog.set_directory(&p.images);
@@ -245,18 +518,214 @@ impl MaBlocksApp {
         }
     }
 
-    /// Spawns a background thread to load and decode an image from the specified path.
+    /// Queues a file to be decoded in the background: its first frame, or the full
+    /// animation, which loads before anything else.
     fn trigger_image_load(&self, path: PathBuf, first_frame_only: bool) {
-        let tx = self.image_tx.clone();
-        std::thread::spawn(move || {
-            let result = image_loader::load_image_frames_scaled(
-                &path,
-                Some(MAX_BLOCK_DIMENSION as u32),
-                first_frame_only,
-            )
-            .map(|loaded| (path, loaded, !first_frame_only));
-            let _ = tx.send(result);
-        });
+        let priority = if first_frame_only {
+            LoadPriority::Visible
+        } else {
+            LoadPriority::Full
+        };
+        self.load_queue.push(path, first_frame_only, priority);
+    }
+
+    /// Moves queued first-frame loads of blocks inside `viewport` (canvas coordinates)
+    /// ahead of the offscreen ones.
+    fn prioritize_visible_loads(&self, viewport: Rect) {
+        if !self.load_queue.has_pending() {
+            return;
+        }
+        let mut priorities: HashMap<String, LoadPriority> = HashMap::new();
+        for block in self.blocks() {
+            let rect = Rect::from_min_size(block.pos.position, block.outer_size());
+            let priority = if rect.intersects(viewport) {
+                LoadPriority::Visible
+            } else {
+                LoadPriority::Offscreen
+            };
+            let mut paths = Vec::new();
+            block.collect_skeleton_paths(&mut paths);
+            for path in paths {
+                let entry = priorities.entry(path).or_insert(priority);
+                *entry = (*entry).max(priority);
+            }
+        }
+        self.load_queue
+            .reprioritize(|path| priorities.get(&*path.to_string_lossy()).copied());
+    }
+
+    /// Decodes sharper textures for still blocks inside `viewport` (canvas coordinates)
+    /// shown larger than their regular texture, and drops those no longer needed, within
+    /// `DETAIL_TEXTURE_BUDGET_BYTES`. Decodes for blocks that left the viewport are
+    /// cancelled.
+    fn update_detail_textures(&mut self, ctx: &egui::Context, viewport: Rect) {
+        let scale = self.zoom * ctx.pixels_per_point();
+        let mut used = 0;
+        let mut visible_ids = HashSet::new();
+        let mut wanted = Vec::new();
+        for block in self.blocks_mut() {
+            let rect = Rect::from_min_size(block.pos.position, block.outer_size());
+            let visible = rect.intersects(viewport);
+            let screen_px = block.image_size.max_elem() * scale;
+            block.release_detail(visible, screen_px);
+            used += block.detail_bytes();
+            if !visible {
+                continue;
+            }
+            visible_ids.insert(block.id);
+            if let Some(tier) = block.wanted_detail_tier(screen_px) {
+                let [w, h] = block.texture.size();
+                let factor = tier as f32 / w.max(h) as f32;
+                let estimate = (w * h) as f32 * factor * factor * 4.0;
+                wanted.push((block.id, tier, estimate as usize));
+            }
+        }
+        let stale: Vec<PathBuf> = self
+            .detail_pending
+            .iter()
+            .filter(|(id, _)| !visible_ids.contains(*id))
+            .map(|(_, (path, _))| path.clone())
+            .collect();
+        for path in stale {
+            self.cancel_detail_loads(&path);
+        }
+        used += self
+            .detail_pending
+            .values()
+            .map(|(_, estimate)| estimate)
+            .sum::<usize>();
+
+        for (id, tier, estimate) in wanted {
+            let Some(block) = self.block_by_id(id) else {
+                continue;
+            };
+            if self.detail_pending.contains_key(&id)
+                || self.detail_failed.get(&id) == Some(&block.path)
+                || used + estimate - block.detail_bytes() > DETAIL_TEXTURE_BUDGET_BYTES
+            {
+                continue;
+            }
+            let path = PathBuf::from(&block.path);
+            let poster = block.poster_frame();
+            used += estimate;
+            self.detail_queue
+                .push(path.clone(), (id, tier, poster), LoadPriority::Visible);
+            self.detail_pending.insert(id, (path, estimate));
+        }
+    }
+
+    /// Cancels the detail decodes of `path`, for every block waiting for one.
+    fn cancel_detail_loads(&mut self, path: &Path) {
+        self.detail_queue.cancel(path);
+        self.detail_pending
+            .retain(|_, (pending, _)| pending.as_path() != path);
+    }
+
+    fn poll_detail_rx(&mut self, ctx: &egui::Context) {
+        while let Ok((path, id, tier, poster, image)) = self.detail_rx.try_recv() {
+            if !self.detail_queue.finish(&path) {
+                continue;
+            }
+            self.detail_pending.remove(&id);
+            let Some(image) = image else {
+                self.detail_failed
+                    .insert(id, path.to_string_lossy().into_owned());
+                continue;
+            };
+            // Ignore images of a file or poster that has been changed since.
+            if let Some(block) = self
+                .block_by_id_mut(id)
+                .filter(|b| Path::new(&b.path) == path && b.poster_frame() == poster)
+            {
+                block.set_detail(ctx, tier, image);
+            }
+        }
+    }
+
+    /// Cancels the loads of files shown only by the blocks just removed from the canvas.
+    fn cancel_detached_loads(&self) {
+        let mut paths = Vec::new();
+        for block in self.block_manager.detached() {
+            block.collect_paths(&mut paths);
+        }
+        for path in paths {
+            if !self.blocks().iter().any(|b| b.contains_path(&path)) {
+                self.load_queue.cancel(Path::new(&path));
+                self.poster_queue.cancel(Path::new(&path));
+            }
+        }
+    }
+
+    /// Queues first-frame loads for skeleton blocks without one, e.g. deleted blocks
+    /// brought back by undo after their load was cancelled.
+    fn queue_skeleton_loads(&self) {
+        let mut paths = Vec::new();
+        for block in self.blocks() {
+            block.collect_skeleton_paths(&mut paths);
+        }
+        paths.sort();
+        paths.dedup();
+        for path in paths.into_iter().map(PathBuf::from) {
+            if !self.load_queue.is_queued(&path) {
+                self.load_queue.push(path, true, LoadPriority::Offscreen);
+            }
+        }
+    }
+
+    /// Shows the poster frame of a block (see `ImageBlock::poster_frame`): taken from its
+    /// loaded animation frames if possible, otherwise decoded in the background.
+    fn trigger_poster_load(&mut self, id: Uuid) {
+        let Some(block) = self.block_by_id_mut(id) else {
+            return;
+        };
+        let Some(poster) = block.poster_frame() else {
+            block.set_poster(None);
+            return;
+        };
+        if let Some(image) = block.poster_from_frames() {
+            block.set_poster(Some(image));
+            return;
+        }
+        let path = PathBuf::from(&block.path);
+        self.poster_queue
+            .push(path, (id, poster), LoadPriority::Visible);
+    }
+
+    fn poll_poster_rx(&mut self) {
+        while let Ok((path, id, poster, image)) = self.poster_rx.try_recv() {
+            if !self.poster_queue.finish(&path) {
+                continue;
+            }
+            // Ignore frames for a poster that has been changed since.
+            if let Some(block) = self
+                .block_by_id_mut(id)
+                .filter(|b| b.poster_frame() == Some(poster))
+            {
+                block.set_poster(image);
+            }
+        }
+    }
+
+    /// Loads finished clips as new blocks placed right after their source video.
+    fn poll_clip_rx(&mut self) {
+        while let Ok((id, result)) = self.clip_rx.try_recv() {
+            let source = self.block_by_id_mut(id).map(|block| {
+                block.exporting_clip = false;
+                block.pos.position
+            });
+            match result {
+                Ok(path) => {
+                    log::info!("Saved clip to {}", path.display());
+                    if let Some(pos) = source {
+                        // Just right of the source, so the reflow puts it next.
+                        self.pending_drops
+                            .insert(path.clone(), DropTarget::Canvas(pos + vec2(0.01, 0.0)));
+                    }
+                    self.trigger_image_load(path, true);
+                }
+                Err(err) => log::error!("Failed to export clip: {err}"),
+            }
+        }
     }
 
     /// Polls the image loading channel for completed tasks and integrates them into the application state.
@@ -269,10 +738,15 @@ impl MaBlocksApp {
             let current_max_h = self.block_manager.max_block_height();
 
             while let Ok(result) = rx.try_recv() {
+                let (Ok((path, ..)) | Err((path, _))) = &result;
+                if !self.load_queue.finish(path) {
+                    continue;
+                }
                 match result {
                     Ok((path, loaded, is_full)) => {
                         let mut loaded = loaded;
                         let path_str = path.to_string_lossy().into_owned();
+                        let drop_target = self.pending_drops.remove(&path);
 
                         // Check if any block (including group children) needs this image
                         let needs_update = self
@@ -281,30 +755,73 @@ impl MaBlocksApp {
                             .any(|b| b.needs_skeleton_for_path(&path_str, is_full));
 
                         if needs_update {
+                            // The file may have been replaced; its detail texture can be
+                            // tried again.
+                            self.detail_failed.retain(|_, failed| *failed != path_str);
                             // Update all matching blocks recursively (including group children)
+                            let mut animated = Vec::new();
                             for block in self.blocks_mut() {
                                 let (updated, _) = block.populate_skeletons_by_path(
                                     &path_str,
                                     &mut loaded.frames,
                                     loaded.has_animation,
                                     is_full,
+                                    loaded.fingerprint,
+                                    loaded.video_metadata.as_ref(),
                                 );
-                                if updated && is_full {
-                                    // Note: We can't call mark_animation_used here for children
-                                    // as they're not in the main block list. This is acceptable
-                                    // since group children don't play animations independently.
+                                // Group children don't play animations independently, so
+                                // only top-level blocks count against the animation budget.
+                                if updated && is_full && block.path == path_str {
+                                    animated.push(block.id);
                                 }
                             }
+                            for id in animated {
+                                self.block_manager.mark_animation_used(id);
+                            }
+                            // Blocks with a poster frame show it instead of the first frame.
+                            let pending: Vec<Uuid> = self
+                                .blocks()
+                                .iter()
+                                .filter(|b| b.path == path_str && b.is_poster_pending())
+                                .map(|b| b.id)
+                                .collect();
+                            for id in pending {
+                                self.trigger_poster_load(id);
+                            }
+                            // The same file was also dropped while it was loading for an
+                            // existing block; load it again for the new one.
+                            if let Some(target) = drop_target {
+                                self.pending_drops.insert(path.clone(), target);
+                                self.trigger_image_load(path, true);
+                            }
                         } else {
                             // New block being added (not a skeleton restore)
                             match self.insert_loaded_image(ctx, path, loaded, is_full) {
-                                Ok(id) => added_ids.push(id),
+                                Ok(id) => {
+                                    if let Some(target) = drop_target {
+                                        self.place_dropped_block(id, target);
+                                    }
+                                    added_ids.push(id);
+                                }
                                 Err(err) => log::error!("{err}"),
                             }
                         }
                     }
-                    Err(err) => {
+                    Err((path, err)) => {
                         log::error!("Failed to load image: {err}");
+                        self.pending_drops.remove(&path);
+                        // A file that exists but does not decode cannot be fixed by
+                        // relinking, so only absent files are flagged as missing.
+                        if !path.exists() {
+                            let path_str = path.to_string_lossy();
+                            let mut marked = false;
+                            for block in self.blocks_mut() {
+                                marked |= block.mark_missing_by_path(&path_str);
+                            }
+                            if marked {
+                                self.show_missing_panel = true;
+                            }
+                        }
                     }
                 }
                 got_any = true;
@@ -393,13 +910,25 @@ impl MaBlocksApp {
             block.counter = data.counter;
             block.counter_start_day = data.counter_start_day;
             block.file_size = std::fs::metadata(&data.path).map(|m| m.len()).unwrap_or(0);
+            block.fingerprint = data.fingerprint;
+            block.video_position = data.video_position;
+            block.video_metadata = data.video_metadata.clone();
+            block.video_trim = data.video_trim;
+            block.poster = data.poster;
+            block.audio_muted = data.audio_muted;
+            block.volume = block::restored_volume(data.volume);
+            // A zero or negative speed from an edited file would panic the frame timing.
+            block.playback_speed = block::nearest_playback_speed(data.playback_speed);
+            block.playback_mode = data.playback_mode;
             // Note: we don't restore animation_enabled here - it will be set to false
             // and the user will need to click to load the full animation sequence on demand
 
             // Always load first frame only on session restore
-            // Full sequence will be loaded on-demand when user clicks
+            // Full sequence will be loaded on-demand when user clicks.
+            // Blocks in the viewport are moved ahead once the canvas is drawn.
             let path_buf = PathBuf::from(&data.path);
-            self.trigger_image_load(path_buf, true);
+            self.load_queue
+                .push(path_buf, true, LoadPriority::Offscreen);
 
             Some(block)
         }
@@ -437,6 +966,8 @@ impl MaBlocksApp {
         );
         block.pos.position = pos2(CANVAS_PADDING, CANVAS_PADDING);
         block.file_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        block.fingerprint = loaded.fingerprint;
+        block.video_metadata = loaded.video_metadata;
         Ok(block)
     }
 
@@ -449,6 +980,7 @@ impl MaBlocksApp {
     ) -> Result<Uuid, String> {
         let block = self.create_block_from_loaded(ctx, path, loaded, is_full)?;
         let id = block.id;
+        self.history.begin(&self.block_manager);
         self.block_manager.push(block);
 
         if is_full {
@@ -458,6 +990,138 @@ impl MaBlocksApp {
         Ok(id)
     }
 
+    /// Queues files dropped from the desktop for loading, expanding folders recursively.
+    /// They are placed where they were dropped, or added to the Box under the pointer.
+    fn handle_dropped_files(
+        &mut self,
+        ctx: &egui::Context,
+        input: &InputSnapshot,
+        canvas_origin: Pos2,
+        zoom: f32,
+    ) {
+        if input.dropped_files.is_empty() {
+            return;
+        }
+
+        let drop_pos = input
+            .latest_pos
+            .map(|p| ((p - canvas_origin) / zoom).to_pos2());
+        let target_box = drop_pos
+            .and_then(|p| self.block_manager.find_group_at_pos(p, Uuid::nil()))
+            .and_then(|idx| self.block_manager.get_by_index(idx))
+            .map(|b| b.id);
+        self.scan_media_paths(ctx, input.dropped_files.clone(), target_box, drop_pos);
+    }
+
+    /// Expands `paths` with `supported_media_paths` on a background thread, so large
+    /// folders do not stall the UI; see `poll_scan_rx`.
+    fn scan_media_paths(
+        &self,
+        ctx: &egui::Context,
+        paths: Vec<PathBuf>,
+        target_box: Option<Uuid>,
+        drop_pos: Option<Pos2>,
+    ) {
+        let tx = self.scan_tx.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = tx.send((supported_media_paths(&paths), target_box, drop_pos));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Queues the files of finished scans for loading, placed where they were dropped.
+    fn poll_scan_rx(&mut self) {
+        while let Ok((media, target_box, drop_pos)) = self.scan_rx.try_recv() {
+            for (i, path) in media.into_iter().enumerate() {
+                // Nudge each file right so they keep their drop order when sorted.
+                let nudge = vec2(i as f32 * 0.01, 0.0);
+                let target = match (target_box, drop_pos) {
+                    (Some(box_id), _) => Some(DropTarget::Box(box_id)),
+                    (None, Some(pos)) => Some(DropTarget::Canvas(pos + nudge)),
+                    (None, None) => None,
+                };
+                if let Some(target) = target {
+                    self.pending_drops.insert(path.clone(), target);
+                }
+                self.trigger_image_load(path, true);
+            }
+        }
+    }
+
+    /// Ctrl+V adds files or a bitmap from the clipboard. egui-winit swallows the key press
+    /// (turning it into a text-only `Paste` event), so the key release triggers it.
+    fn handle_paste_shortcut(&mut self, ctx: &egui::Context) {
+        let released = ctx.input(|i| {
+            i.events.iter().any(|e| {
+                matches!(e, egui::Event::Key { key: egui::Key::V, pressed: false, modifiers, .. }
+                    if modifiers.command)
+            })
+        });
+        if released && !ctx.wants_keyboard_input() {
+            self.paste_from_clipboard(ctx);
+        }
+    }
+
+    /// Loads copied files, or saves a copied bitmap into the images directory and inserts
+    /// it as a new block. The bitmap is encoded on a worker thread.
+    fn paste_from_clipboard(&mut self, ctx: &egui::Context) {
+        let Some(images_dir) = self.paths.as_ref().map(|p| p.images.clone()) else {
+            log::error!("Cannot paste: no application data directory");
+            return;
+        };
+
+        match clipboard::read_clipboard() {
+            Ok(Some(clipboard::Pasted::Files(files))) => {
+                self.scan_media_paths(ctx, files, None, None);
+            }
+            Ok(Some(clipboard::Pasted::Image { size, rgba })) => {
+                let tx = self.image_tx.clone();
+                let ctx = ctx.clone();
+                std::thread::spawn(move || {
+                    let path = match clipboard::save_image(&images_dir, size, &rgba) {
+                        Ok(path) => path,
+                        Err(err) => {
+                            log::error!("Failed to paste: {err}");
+                            return;
+                        }
+                    };
+                    log::info!("Saved pasted image to {}", path.display());
+                    let mut loaded = image_loader::loaded_image_from_rgba(
+                        size,
+                        &rgba,
+                        Some(MAX_BLOCK_DIMENSION as u32),
+                    );
+                    loaded.fingerprint = relink::file_fingerprint(&path).ok();
+                    // Goes through `poll_image_rx` like any other load, so it is inserted
+                    // and sized to match the board.
+                    let _ = tx.send(Ok((path, loaded, false)));
+                    ctx.request_repaint();
+                });
+            }
+            Ok(None) => log::info!("Nothing to paste: the clipboard holds no image or files"),
+            Err(err) => log::error!("Failed to paste: {err}"),
+        }
+    }
+
+    /// Moves a newly loaded block to the place its file was dropped on.
+    fn place_dropped_block(&mut self, id: Uuid, target: DropTarget) {
+        match target {
+            DropTarget::Canvas(pos) => {
+                if let Some(block) = self.block_by_id_mut(id) {
+                    block.pos.position = pos;
+                }
+            }
+            DropTarget::Box(box_id) => {
+                if let (Some(block_idx), Some(box_idx)) =
+                    (self.block_index(id), self.block_index(box_id))
+                {
+                    self.drop_block_into_box(block_idx, box_idx);
+                }
+            }
+        }
+    }
+
     fn advance_animations(&mut self, dt: f32, ctx: &egui::Context) {
         let mut changed = false;
         let mut next_frame_in: Option<Duration> = None;
@@ -480,6 +1144,17 @@ impl MaBlocksApp {
         }
     }
 
+    /// Turns the sound of each playing video on or off. With `exclusive_audio`, only the
+    /// video started or unmuted last is heard.
+    fn update_audio(&mut self) {
+        let exclusive = self.exclusive_audio;
+        let audible_block = self.audible_block;
+        for block in self.blocks_mut() {
+            let audible = block.wants_sound() && (!exclusive || audible_block == Some(block.id));
+            block.set_audible(audible);
+        }
+    }
+
     /// Recalculates the positions of all blocks to fit within the current canvas width.
     fn reflow_blocks(&mut self) {
         self.block_manager.reflow(self.working_inner_width);
@@ -490,28 +1165,33 @@ impl MaBlocksApp {
     }
 
     fn clear_chain_group(&mut self) {
+        self.history.begin(&self.block_manager);
         self.block_manager.clear_chain_group();
         self.skip_chain_cancel = false;
     }
 
     fn toggle_chain_for_block(&mut self, index: usize) {
+        self.history.begin(&self.block_manager);
         self.block_manager.toggle_chain(index);
         self.skip_chain_cancel = true;
     }
 
     /// Combines all currently chained blocks into a single group block.
     fn box_group(&mut self, ctx: &egui::Context) -> Uuid {
+        self.history.begin(&self.block_manager);
         let new_id = self.block_manager.box_chained(ctx);
         self.reflow_blocks();
         new_id
     }
 
     fn unbox_group(&mut self, index: usize) {
+        self.history.begin(&self.block_manager);
         self.block_manager.unbox_group(index);
         self.reflow_blocks();
     }
 
     fn drop_block_into_box(&mut self, block_idx: usize, box_idx: usize) {
+        self.history.begin(&self.block_manager);
         self.block_manager.drop_into_group(block_idx, box_idx);
     }
 
@@ -582,6 +1262,8 @@ impl MaBlocksApp {
     }
 
     fn toggle_compact_group(&mut self, ctx: &egui::Context) {
+        // Re-boxing marks blocks as chained before boxing them; capture the layout first.
+        self.history.begin(&self.block_manager);
         let chained_count = self.block_manager.chained_count();
 
         // No blocks chained - try to restore previous state
@@ -615,25 +1297,32 @@ impl MaBlocksApp {
 
 impl eframe::App for MaBlocksApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let current_time = ctx.input(|i| i.time);
-        if current_time - self.last_auto_save_time > 300.0 {
-            if let Some(storage) = _frame.storage_mut() {
-                self.save(storage);
-                self.last_auto_save_time = current_time;
-                log::info!("Auto-saved session");
-            }
-        }
-
         self.poll_image_rx(ctx);
+        self.poll_poster_rx();
+        self.poll_detail_rx(ctx);
+        self.poll_clip_rx();
+        self.poll_relink_rx();
+        self.poll_scan_rx();
+        self.poll_exports();
+        self.handle_watch_events(ctx);
         if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::N)) {
             self.show_file_names = !self.show_file_names;
         }
+        self.handle_undo_shortcuts(ctx);
+        self.handle_paste_shortcut(ctx);
 
         let dt = ctx.input(|i| i.unstable_dt).max(0.0);
         self.advance_animations(dt, ctx);
+        // Video streams report their decoder size once open, which can exceed the budget.
+        self.block_manager.enforce_animation_budget();
+        self.update_audio();
         self.block_manager.enforce_chain_constraints();
 
         self.render_toolbar(ctx);
+        self.render_missing_panel(ctx);
+        self.render_watch_panel(ctx);
+        self.render_recovery_notice(ctx);
+        self.render_memory_overlay(ctx);
 
         let (dropped_leader_id, should_reflow) = self.render_canvas(ctx);
 
@@ -642,23 +1331,73 @@ impl eframe::App for MaBlocksApp {
         } else if should_reflow {
             self.reflow_blocks();
         }
+
+        // Drags and resizes become a single undo step once the gesture ends.
+        let gesture_active = self.block_manager.any_dragging() || self.resizing_state.is_some();
+        let committed = !gesture_active && self.history.commit(&mut self.block_manager);
+
+        // Journal committed steps right away; other changes (zoom, relinks) periodically.
+        let current_time = ctx.input(|i| i.time);
+        if committed
+            || (!gesture_active
+                && current_time - self.last_journal_sync_time > JOURNAL_SYNC_INTERVAL_SECS)
+        {
+            self.sync_journal();
+            self.last_journal_sync_time = current_time;
+        }
     }
 
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
-        let session = Session {
-            blocks: self
-                .blocks()
-                .iter()
-                .map(|b| Self::block_to_data(b))
-                .collect(),
-            remembered_chains: self.serialize_remembered_chains(),
-            last_unboxed_ids: self.last_unboxed_ids.clone(),
-            last_boxed_id: self.last_boxed_id,
-            zoom: self.zoom,
-            show_file_names: self.show_file_names,
-        };
-        eframe::set_value(storage, eframe::APP_KEY, &session);
+        session::session_to_storage(storage, &self.to_session());
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        let session = self.to_session();
+        if let Some(journal) = self.journal.as_mut() {
+            if let Err(err) = journal.finish(session) {
+                log::error!("Failed to close autosave journal: {err}");
+            }
+        }
+    }
+}
+
+/// Applies the updates of a background export and returns its result once it is done,
+/// clearing `job`.
+fn poll_export<T>(job: &mut Option<ExportJob<T>>) -> Option<(PathBuf, Result<T, String>)> {
+    let (rx, progress) = job.as_mut()?;
+    loop {
+        match rx.try_recv() {
+            Ok(ExportUpdate::Progress(done)) => *progress = done,
+            Ok(ExportUpdate::Done(path, result)) => {
+                *job = None;
+                return Some((path, result));
+            }
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => {
+                *job = None;
+                return None;
+            }
+        }
+    }
+}
+
+/// Expands directories recursively and keeps only files with a supported extension.
+fn supported_media_paths(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut media = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            media.extend(
+                relink::scan_folder(path)
+                    .into_iter()
+                    .filter(|p| image_loader::has_supported_extension(p)),
+            );
+        } else if image_loader::has_supported_extension(path) {
+            media.push(path.clone());
+        } else {
+            log::warn!("Ignoring file of unsupported type: {}", path.display());
+        }
     }
+    media
 }
 
 /// Creates a toolbar button with consistent styling.
@@ -691,6 +1430,29 @@ impl MaBlocksApp {
                     if toolbar_button(ui, "📂", "Load Session") {
                         self.load_session(ctx);
                     }
+                    if let Some((_, progress)) = self.bundle_export {
+                        ui.add(
+                            egui::ProgressBar::new(progress)
+                                .desired_width(EXPORT_PROGRESS_WIDTH)
+                                .show_percentage(),
+                        )
+                        .on_hover_text("Exporting bundle…");
+                    } else if toolbar_button(ui, "📤", "Export Bundle") {
+                        self.export_bundle(ctx);
+                    }
+                    if toolbar_button(ui, "📥", "Import Bundle") {
+                        self.import_bundle(ctx);
+                    }
+                    if let Some((_, progress)) = self.png_export {
+                        ui.add(
+                            egui::ProgressBar::new(progress)
+                                .desired_width(EXPORT_PROGRESS_WIDTH)
+                                .show_percentage(),
+                        )
+                        .on_hover_text("Exporting PNG…");
+                    } else if toolbar_button(ui, "📷", "Export PNG") {
+                        self.export_png(ctx);
+                    }
                     if toolbar_button(ui, "🖼", "Add Image") {
                         self.load_images();
                     }
@@ -700,30 +1462,376 @@ impl MaBlocksApp {
                     if toolbar_button(ui, "📦", "Compact/Unbox Group") {
                         self.toggle_compact_group(ctx);
                     }
+                    ui.menu_button(RichText::new("🔃").size(TOOLBAR_ICON_SIZE), |ui| {
+                        for key in SortKey::ALL {
+                            if ui.button(key.label()).clicked() {
+                                self.sort_blocks(key);
+                                ui.close_menu();
+                            }
+                        }
+                    })
+                    .response
+                    .on_hover_text("Sort Blocks");
+                    let sound_icon = if self.audio_muted { "🔇" } else { "🔊" };
+                    ui.menu_button(RichText::new(sound_icon).size(TOOLBAR_ICON_SIZE), |ui| {
+                        if ui.checkbox(&mut self.audio_muted, "Mute all").changed() {
+                            audio::set_muted(self.audio_muted);
+                        }
+                        ui.checkbox(
+                            &mut self.exclusive_audio,
+                            "Only one video plays sound at a time",
+                        );
+                    })
+                    .response
+                    .on_hover_text("Sound");
+                    ui.menu_button(RichText::new("📊").size(TOOLBAR_ICON_SIZE), |ui| {
+                        ui.label("Animation memory");
+                        let current = self.block_manager.animation_budget_mb();
+                        for mb in ANIMATION_BUDGET_CHOICES_MB {
+                            if ui.radio(current == mb, format_megabytes(mb)).clicked() {
+                                self.block_manager.set_animation_budget_mb(mb);
+                            }
+                        }
+                        ui.separator();
+                        ui.checkbox(&mut self.show_memory_overlay, "Show memory usage");
+                    })
+                    .response
+                    .on_hover_text("Memory");
+                    if toolbar_button(ui, "🔗", "Missing Files") {
+                        self.show_missing_panel = !self.show_missing_panel;
+                    }
+                    if toolbar_button(ui, "👁", "Watched Folders") {
+                        self.show_watch_panel = !self.show_watch_panel;
+                    }
                 });
             });
     }
 
-    fn render_canvas(&mut self, ctx: &egui::Context) -> (Option<Uuid>, bool) {
-        let mut dropped_leader_id = None;
-        let mut should_reflow = false;
+    /// Returns the path and fingerprint of every missing block (including group children),
+    /// deduplicated by path.
+    fn missing_files(&self) -> Vec<relink::MissingFile> {
+        let mut missing = Vec::new();
+        for block in self.blocks() {
+            block.collect_missing(&mut missing);
+        }
+        missing.sort_by(|a, b| a.0.cmp(&b.0));
+        missing.dedup_by(|a, b| a.0 == b.0);
+        missing
+            .into_iter()
+            .map(|(path, fingerprint)| relink::MissingFile { path, fingerprint })
+            .collect()
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let input = InputSnapshot::from_ui(ui);
+    /// Shows the list of files that failed to load, with a relink action.
+    fn render_missing_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_missing_panel {
+            return;
+        }
 
-            self.handle_zoom_input(ui, &input);
+        let missing = self.missing_files();
+        let mut open = true;
+        let mut relink_clicked = false;
+        egui::Window::new(format!("Missing Files ({})", missing.len()))
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                if missing.is_empty() {
+                    ui.label("All files were found.");
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(240.0)
+                    .show(ui, |ui| {
+                        for file in &missing {
+                            ui.label(&file.path);
+                        }
+                    });
+                ui.separator();
+                ui.checkbox(
+                    &mut self.relink_by_fingerprint,
+                    "Also match by file content",
+                )
+                .on_hover_text("Finds renamed files and resolves duplicate names (slower)");
+                if self.relink_rx.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Scanning folder…");
+                    });
+                } else if ui.button("Relink from folder…").clicked() {
+                    relink_clicked = true;
+                }
+            });
 
-            if input.secondary_released {
-                self.resizing_state = None;
-                should_reflow = true;
-            }
+        self.show_missing_panel = open;
+        if relink_clicked {
+            self.relink_missing_files(ctx, missing);
+        }
+    }
 
-            if let Some(curr_mouse_pos) = input.hover_pos {
-                if let Some(state) = self.resizing_state.clone() {
-                    let zoom = self.zoom;
-                    handle_blocks_resizing(self.blocks_mut(), &state, curr_mouse_pos, zoom);
-                }
-            }
+    /// Lists watched folders with their target and lets the user add or remove them.
+    fn render_watch_panel(&mut self, ctx: &egui::Context) {
+        if !self.show_watch_panel {
+            return;
+        }
+
+        let folders = self.watcher.folders().to_vec();
+        let mut open = true;
+        let mut add_clicked = false;
+        let mut remove_index = None;
+        egui::Window::new(format!("Watched Folders ({})", folders.len()))
+            .open(&mut open)
+            .default_width(420.0)
+            .show(ctx, |ui| {
+                if folders.is_empty() {
+                    ui.label("No folders are watched.");
+                }
+                for (i, folder) in folders.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .small_button("✖")
+                            .on_hover_text("Stop watching")
+                            .clicked()
+                        {
+                            remove_index = Some(i);
+                        }
+                        ui.label(&folder.path);
+                        ui.weak(format!("→ {}", self.watch_target_name(folder.target)));
+                    });
+                }
+                ui.separator();
+                if ui
+                    .button("Watch folder…")
+                    .on_hover_text("New files go to the selected Box, or to the canvas")
+                    .clicked()
+                {
+                    add_clicked = true;
+                }
+            });
+
+        self.show_watch_panel = open;
+        if let Some(index) = remove_index {
+            self.watcher.remove(index);
+        }
+        if add_clicked {
+            self.add_watched_folder();
+        }
+    }
+
+    fn watch_target_name(&self, target: Option<Uuid>) -> String {
+        match target {
+            None => "Canvas".to_string(),
+            Some(id) => match self.block_by_id(id) {
+                Some(block) => format!("Box \"{}\"", block.group.group_name),
+                None => "Canvas (Box removed)".to_string(),
+            },
+        }
+    }
+
+    /// Lets the user pick a folder to watch and imports the files already in it. New files
+    /// go to the selected Box if exactly one is chained, otherwise to the canvas.
+    fn add_watched_folder(&mut self) {
+        let Some(folder) = rfd::FileDialog::new().pick_folder() else {
+            return;
+        };
+
+        let selected_boxes: Vec<Uuid> = self
+            .blocks()
+            .iter()
+            .filter(|b| b.chained && b.group.is_group)
+            .map(|b| b.id)
+            .collect();
+        let target = match selected_boxes[..] {
+            [id] => Some(id),
+            _ => None,
+        };
+
+        match self.watcher.add(&folder, target) {
+            Ok(watched) => {
+                log::info!("Watching {}", watched.path);
+                for path in relink::scan_folder(Path::new(&watched.path)) {
+                    if image_loader::has_supported_extension(&path) {
+                        self.import_watched_file(path, target);
+                    }
+                }
+            }
+            Err(err) => log::error!("{err}"),
+        }
+    }
+
+    /// Imports files created in watched folders and flags blocks whose files were deleted.
+    fn handle_watch_events(&mut self, ctx: &egui::Context) {
+        for event in self.watcher.poll(ctx) {
+            match event {
+                WatchEvent::Created { path, target } => self.import_watched_file(path, target),
+                WatchEvent::Removed(path) => {
+                    let path_str = path.to_string_lossy();
+                    let mut marked = false;
+                    for block in self.blocks_mut() {
+                        marked |= block.mark_deleted_by_path(&path_str);
+                    }
+                    if marked {
+                        log::info!("Watched file was removed: {}", path.display());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Loads a file from a watched folder unless it is already on the board. A file that
+    /// reappears after being deleted repopulates its missing blocks instead.
+    fn import_watched_file(&mut self, path: PathBuf, target: Option<Uuid>) {
+        let path_str = path.to_string_lossy();
+        if self.blocks().iter().any(|b| b.contains_path(&path_str)) {
+            if self
+                .blocks()
+                .iter()
+                .any(|b| b.needs_skeleton_for_path(&path_str, false))
+            {
+                self.trigger_image_load(path, true);
+            }
+            return;
+        }
+
+        if let Some(box_id) = target.filter(|id| self.block_index(*id).is_some()) {
+            self.pending_drops
+                .insert(path.clone(), DropTarget::Box(box_id));
+        }
+        self.trigger_image_load(path, true);
+    }
+
+    /// Shows the memory taken by cached animations against the budget, and by all blocks.
+    fn render_memory_overlay(&self, ctx: &egui::Context) {
+        if !self.show_memory_overlay {
+            return;
+        }
+        let to_mb = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+        let animations = self.block_manager.animation_memory_bytes();
+        let blocks: usize = self.blocks().iter().map(ImageBlock::memory_bytes).sum();
+        egui::Area::new(egui::Id::new("memory_overlay"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, vec2(-24.0, -24.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(format!(
+                        "Animations: {:.0} / {} MB ({} cached)",
+                        to_mb(animations),
+                        self.block_manager.animation_budget_mb(),
+                        self.block_manager.animation_access_order().len()
+                    ));
+                    ui.label(format!("Blocks: {:.0} MB", to_mb(blocks)));
+                });
+            });
+        ctx.request_repaint_after(Duration::from_secs(1));
+    }
+
+    /// Tells the user that the previous session was recovered from the autosave journal.
+    fn render_recovery_notice(&mut self, ctx: &egui::Context) {
+        let Some(message) = self.recovery_notice.as_deref() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut dismissed = false;
+        egui::Window::new("Session Recovered")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(360.0)
+            .show(ctx, |ui| {
+                ui.label(message);
+                if ui.button("OK").clicked() {
+                    dismissed = true;
+                }
+            });
+
+        if !open || dismissed {
+            self.recovery_notice = None;
+        }
+    }
+
+    /// Appends any changes since the last sync to the autosave journal.
+    fn sync_journal(&mut self) {
+        if self.journal.is_none() {
+            return;
+        }
+        let session = self.to_session();
+        if let Some(journal) = self.journal.as_mut() {
+            if let Err(err) = journal.record(session) {
+                log::error!("Failed to write autosave journal: {err}");
+            }
+        }
+    }
+
+    /// Lets the user pick a folder and remaps all missing paths to files found inside it.
+    /// The folder is scanned (and fingerprinted) in the background; see `poll_relink_rx`.
+    fn relink_missing_files(&mut self, ctx: &egui::Context, missing: Vec<relink::MissingFile>) {
+        let Some(folder) = rfd::FileDialog::new().pick_folder() else {
+            return;
+        };
+
+        let (tx, rx) = channel();
+        self.relink_rx = Some(rx);
+        let by_fingerprint = self.relink_by_fingerprint;
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let candidates = relink::scan_folder(&folder);
+            let renames = relink::plan_relink(&missing, &candidates, by_fingerprint);
+            let _ = tx.send((folder, missing.len(), renames));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Applies a finished relink scan, loading the relinked files.
+    fn poll_relink_rx(&mut self) {
+        let Some(rx) = &self.relink_rx else {
+            return;
+        };
+        let (folder, missing, renames) = match rx.try_recv() {
+            Ok(plan) => plan,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.relink_rx = None;
+                return;
+            }
+        };
+        self.relink_rx = None;
+        log::info!(
+            "Relinked {} of {missing} missing files from {}",
+            renames.len(),
+            folder.display()
+        );
+
+        let mut to_load = Vec::new();
+        for block in self.blocks_mut() {
+            to_load.extend(block.relink_missing(&renames));
+        }
+        to_load.sort();
+        to_load.dedup();
+        for path in to_load {
+            self.trigger_image_load(path, true);
+        }
+    }
+
+    fn render_canvas(&mut self, ctx: &egui::Context) -> (Option<Uuid>, bool) {
+        let mut dropped_leader_id = None;
+        let mut should_reflow = false;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let input = InputSnapshot::from_ui(ui);
+
+            self.handle_zoom_input(ui, &input);
+
+            if input.secondary_released {
+                self.resizing_state = None;
+                should_reflow = true;
+            }
+
+            if let Some(curr_mouse_pos) = input.hover_pos {
+                if let Some(state) = self.resizing_state.clone() {
+                    let zoom = self.zoom;
+                    handle_blocks_resizing(self.blocks_mut(), &state, curr_mouse_pos, zoom);
+                }
+            }
 
             egui::ScrollArea::both()
                 .id_salt("main_canvas")
@@ -744,8 +1852,16 @@ impl MaBlocksApp {
                             .layout(egui::Layout::default()),
                     );
                     let canvas_origin = canvas_rect.min;
+                    let viewport = ui.clip_rect().translate(-canvas_origin.to_vec2());
+                    let canvas_viewport = Rect::from_min_max(
+                        (viewport.min.to_vec2() / zoom).to_pos2(),
+                        (viewport.max.to_vec2() / zoom).to_pos2(),
+                    );
+                    self.prioritize_visible_loads(canvas_viewport);
+                    self.update_detail_textures(ctx, canvas_viewport);
 
                     self.update_drop_target(&input, canvas_origin, zoom);
+                    self.handle_dropped_files(ctx, &input, canvas_origin, zoom);
 
                     let mut hovered_box_to_render = None;
                     let mut dragging_blocks_to_render = Vec::new();
@@ -769,14 +1885,40 @@ impl MaBlocksApp {
                         let block_id = canvas_ui.id().with(id);
                         let is_hovering_block =
                             input.hover_pos.is_some_and(|p| block_rect.contains(p));
+                        let poster_rect = block
+                            .has_poster_button()
+                            .then(|| poster_button_rect(block_rect, zoom));
+                        let speed_rect = block
+                            .has_poster_button()
+                            .then(|| speed_button_rect(block_rect, zoom));
+                        let video_rects = (block.is_video() && !block.is_missing)
+                            .then(|| VideoControlRects::new(block_rect, zoom, block.has_sound()));
                         let hover_state = BlockControlHover::from_mouse_pos(
                             input.hover_pos,
                             &rects,
+                            poster_rect,
+                            speed_rect,
+                            video_rects.as_ref(),
                             block.group.is_group,
                         );
-                        let any_button_hovered = hover_state.close_hovered
-                            || hover_state.chain_hovered
-                            || hover_state.counter_hovered;
+                        let any_button_hovered = hover_state.any_hovered();
+
+                        if let Some(video_rects) = video_rects {
+                            self.handle_video_scrub(index, &input, video_rects.scrub, &hover_state);
+                            self.handle_video_trim(index, &input, &hover_state);
+                        }
+                        if hover_state.poster_hovered {
+                            self.handle_poster_button(index, &input);
+                        }
+                        if hover_state.sound_hovered {
+                            self.handle_sound_button(index, &input);
+                        }
+                        if hover_state.clip_hovered {
+                            self.handle_clip_button(ctx, index, &input);
+                        }
+                        if hover_state.speed_hovered {
+                            self.handle_speed_button(index, &input);
+                        }
 
                         let mut remove_single = false;
                         let mut remove_cascade = false;
@@ -848,6 +1990,22 @@ impl MaBlocksApp {
                             block.render(&mut canvas_ui, block_rect, config);
                         }
 
+                        let response = match &block.video_metadata {
+                            _ if hover_state.speed_hovered => {
+                                response.on_hover_text(block.playback_details())
+                            }
+                            Some(_) if hover_state.sound_hovered => {
+                                response.on_hover_text(block.sound_details())
+                            }
+                            Some(_) if hover_state.clip_hovered => {
+                                response.on_hover_text(block.clip_details())
+                            }
+                            Some(meta) if !block.pos.is_dragging && !any_button_hovered => {
+                                response.on_hover_text(meta.details())
+                            }
+                            _ => response,
+                        };
+
                         if is_hovered_box {
                             hovered_box_to_render = Some((id, block_rect, config));
                         } else if block.pos.is_dragging || (is_any_dragging && block.chained) {
@@ -864,7 +2022,9 @@ impl MaBlocksApp {
                         }
 
                         if remove_cascade || remove_single {
+                            self.history.begin(&self.block_manager);
                             self.block_manager.remove_cascade(index);
+                            self.cancel_detached_loads();
                             should_reflow = true;
                         }
                     }
@@ -919,10 +2079,22 @@ impl MaBlocksApp {
         )
     }
 
-    /// Updates the hovered drop target when dragging a non-group block over groups.
+    /// Updates the hovered drop target when dragging a non-group block or files from the
+    /// desktop over groups.
     fn update_drop_target(&mut self, input: &InputSnapshot, canvas_origin: Pos2, zoom: f32) {
         self.hovered_box_id = None;
 
+        if input.files_hovered {
+            let world_mouse = input
+                .latest_pos
+                .map(|p| ((p - canvas_origin) / zoom).to_pos2());
+            self.hovered_box_id = world_mouse
+                .and_then(|p| self.block_manager.find_group_at_pos(p, Uuid::nil()))
+                .and_then(|idx| self.block_manager.get_by_index(idx))
+                .map(|b| b.id);
+            return;
+        }
+
         let Some(dragging_idx) = self.blocks().iter().position(|b| b.pos.is_dragging) else {
             return;
         };
@@ -990,22 +2162,14 @@ impl MaBlocksApp {
 
         if !block.is_full_sequence {
             let path = PathBuf::from(&block.path);
-            if video_stream::is_video_format(&path) {
-                // Grab first frame before taking mutable borrow.
-                let first_frame = self.block_manager
-                    .get_by_index(index)
-                    .and_then(|b| b.anim.frames.first())
-                    .map(|f| f.image.clone());
-
-                if let Some(first_frame) = first_frame {
-                    let handle = video_stream::spawn_video_decoder(path, first_frame);
-                    handle.cmd_tx.send(video_stream::StreamCmd::Play).ok();
-                    let block = self.block_manager.get_by_index_mut(index).unwrap();
-                    block.anim.video = Some(handle);
-                    block.anim.has_animation = true;
-                    block.is_full_sequence = true;
-                    block.anim.animation_enabled = true;
+            if block.streams_playback() {
+                let block = self.block_manager.get_by_index_mut(index).unwrap();
+                if block.start_video_decoder() {
+                    block.toggle_animation();
                     let id = block.id;
+                    if block.has_sound() {
+                        self.audible_block = Some(id);
+                    }
                     self.block_manager.mark_animation_used(id);
                 }
             } else {
@@ -1016,6 +2180,9 @@ impl MaBlocksApp {
             block.toggle_animation();
             if block.anim.animation_enabled {
                 let id = block.id;
+                if block.has_sound() {
+                    self.audible_block = Some(id);
+                }
                 self.block_manager.mark_animation_used(id);
             }
         }
@@ -1031,13 +2198,13 @@ impl MaBlocksApp {
         is_hovering_block: bool,
     ) -> bool {
         let mut skip_chain_cancel = false;
-        let any_button_hovered =
-            hover_state.close_hovered || hover_state.chain_hovered || hover_state.counter_hovered;
+        let any_button_hovered = hover_state.any_hovered();
 
         if input.primary_clicked {
             if hover_state.chain_hovered {
                 self.toggle_chain_for_block(index);
             } else if hover_state.counter_hovered {
+                self.history.begin(&self.block_manager);
                 let block = self.block_manager.get_by_index_mut(index).unwrap();
                 block.counter += 1;
                 block.counter_start_day = crate::block::current_day();
@@ -1046,6 +2213,7 @@ impl MaBlocksApp {
         }
 
         if input.secondary_clicked && hover_state.counter_hovered {
+            self.history.begin(&self.block_manager);
             let block = self.block_manager.get_by_index_mut(index).unwrap();
             block.counter = (block.counter - 1).max(0);
             if block.counter > 0 {
@@ -1065,6 +2233,7 @@ impl MaBlocksApp {
                     (true, false) => ResizeHandle::BottomLeft,
                     (false, false) => ResizeHandle::BottomRight,
                 };
+                self.history.begin(&self.block_manager);
                 self.resizing_state = Some(InteractionState {
                     id: block.id,
                     handle,
@@ -1076,6 +2245,160 @@ impl MaBlocksApp {
         skip_chain_cancel
     }
 
+    /// Seeks a video block while its scrub bar is pressed or dragged.
+    fn handle_video_scrub(
+        &mut self,
+        index: usize,
+        input: &InputSnapshot,
+        scrub_rect: Rect,
+        hover_state: &BlockControlHover,
+    ) {
+        let id = self.block_manager.get_by_index(index).unwrap().id;
+        if input.primary_pressed && hover_state.scrub_hovered {
+            self.scrubbing = Some(id);
+        }
+        if self.scrubbing != Some(id) {
+            return;
+        }
+        if !input.primary_down {
+            self.scrubbing = None;
+            let block = self.block_manager.get_by_index_mut(index).unwrap();
+            block.finish_scrub();
+            return;
+        }
+        if let Some(pointer) = input.interact_pos {
+            let fraction = (pointer.x - scrub_rect.min.x) / scrub_rect.width();
+            let block = self.block_manager.get_by_index_mut(index).unwrap();
+            if block.scrub_video(fraction) {
+                self.block_manager.mark_animation_used(id);
+            }
+        }
+    }
+
+    /// Sets (LMB) or clears (RMB) a trim point of a video block from its trim buttons.
+    fn handle_video_trim(
+        &mut self,
+        index: usize,
+        input: &InputSnapshot,
+        hover_state: &BlockControlHover,
+    ) {
+        let point = if hover_state.trim_in_hovered {
+            TrimPoint::In
+        } else if hover_state.trim_out_hovered {
+            TrimPoint::Out
+        } else {
+            return;
+        };
+        if !input.primary_clicked && !input.secondary_clicked {
+            return;
+        }
+        let block = self.block_manager.get_by_index_mut(index).unwrap();
+        let position = input
+            .primary_clicked
+            .then(|| block.video_current_position());
+        let id = block.id;
+        if block.set_trim_point(point, position) {
+            self.trigger_poster_load(id);
+        }
+        self.skip_chain_cancel = true;
+    }
+
+    /// Chooses the shown frame as the poster (LMB) or clears it (RMB).
+    fn handle_poster_button(&mut self, index: usize, input: &InputSnapshot) {
+        if !input.primary_clicked && !input.secondary_clicked {
+            return;
+        }
+        let block = self.block_manager.get_by_index_mut(index).unwrap();
+        let changed = if input.primary_clicked {
+            block.choose_poster()
+        } else {
+            block.clear_poster()
+        };
+        let id = block.id;
+        if changed {
+            self.trigger_poster_load(id);
+        }
+        self.skip_chain_cancel = true;
+    }
+
+    /// Toggles the sound of a video block (LMB) or steps its volume (RMB). Unmuting a
+    /// video makes it the one heard.
+    fn handle_sound_button(&mut self, index: usize, input: &InputSnapshot) {
+        if !input.primary_clicked && !input.secondary_clicked {
+            return;
+        }
+        let block = self.block_manager.get_by_index_mut(index).unwrap();
+        if input.primary_clicked {
+            block.audio_muted = !block.audio_muted;
+            if !block.audio_muted {
+                self.audible_block = Some(block.id);
+            }
+        } else {
+            block.cycle_volume();
+        }
+        self.skip_chain_cancel = true;
+    }
+
+    /// Speeds up playback (LMB), or switches an animation to its next playback mode and
+    /// a video back to normal speed (RMB).
+    fn handle_speed_button(&mut self, index: usize, input: &InputSnapshot) {
+        if !input.primary_clicked && !input.secondary_clicked {
+            return;
+        }
+        let block = self.block_manager.get_by_index_mut(index).unwrap();
+        if input.primary_clicked {
+            block.cycle_speed();
+        } else {
+            let playing = block.anim.animation_enabled;
+            block.cycle_playback_mode();
+            // Switching between streamed and pre-decoded playback drops the frames; a
+            // playing animation goes on in the new mode once they are back.
+            if playing && !block.anim.animation_enabled {
+                self.handle_block_click(index, false);
+            }
+        }
+        self.skip_chain_cancel = true;
+    }
+
+    /// Exports the clip range of a video block (see `ImageBlock::clip_range`) as a GIF
+    /// (LMB) or animated WebP (RMB) in the background.
+    fn handle_clip_button(&mut self, ctx: &egui::Context, index: usize, input: &InputSnapshot) {
+        if !input.primary_clicked && !input.secondary_clicked {
+            return;
+        }
+        self.skip_chain_cancel = true;
+        let Some(images_dir) = self.paths.as_ref().map(|p| p.images.clone()) else {
+            log::error!("Cannot export a clip: no application data directory");
+            return;
+        };
+        let format = if input.primary_clicked {
+            clip::ClipFormat::Gif
+        } else {
+            clip::ClipFormat::WebP
+        };
+        let block = self.block_manager.get_by_index_mut(index).unwrap();
+        if block.exporting_clip {
+            return;
+        }
+        block.exporting_clip = true;
+        let (start, end) = block.clip_range();
+        let source = PathBuf::from(&block.path);
+        let output = clip::clip_output_path(&images_dir, &source, start, format);
+        let (id, tx, ctx) = (block.id, self.clip_tx.clone(), ctx.clone());
+        log::info!(
+            "Exporting {:.2}s-{:.2}s of {} as {}",
+            start,
+            end,
+            source.display(),
+            format.label()
+        );
+        std::thread::spawn(move || {
+            let result = clip::export_clip(&source, start, end, format, &output).map(|()| output);
+            let _ = tx.send((id, result));
+            ctx.request_repaint();
+        });
+    }
+
     fn process_block_drag(
         &mut self,
         index: usize,
@@ -1088,8 +2411,10 @@ impl MaBlocksApp {
         let mut dropped_leader_id = None;
         let mut should_reflow = false;
 
-        if response.drag_started_by(egui::PointerButton::Primary) {
+        let id = self.block_manager.get_by_index(index).unwrap().id;
+        if response.drag_started_by(egui::PointerButton::Primary) && self.scrubbing != Some(id) {
             if let Some(pointer) = response.interact_pointer_pos() {
+                self.history.begin(&self.block_manager);
                 let block = self.block_manager.get_by_index_mut(index).unwrap();
                 block.pos.drag_offset = (pointer - canvas_origin) / zoom
                     - vec2(block.pos.position.x, block.pos.position.y);
@@ -1207,18 +2532,7 @@ impl MaBlocksApp {
         }
 
         if let Some(path) = dialog.save_file() {
-            let session = Session {
-                blocks: self
-                    .blocks()
-                    .iter()
-                    .map(|b| Self::block_to_data(b))
-                    .collect(),
-                remembered_chains: self.serialize_remembered_chains(),
-                last_unboxed_ids: self.last_unboxed_ids.clone(),
-                last_boxed_id: self.last_boxed_id,
-                zoom: self.zoom,
-                show_file_names: self.show_file_names,
-            };
+            let session = self.to_session();
 
             if let Ok(file) = std::fs::File::create(&path) {
                 let _ = serde_json::to_writer_pretty(file, &session);
@@ -1227,6 +2541,27 @@ impl MaBlocksApp {
         }
     }
 
+    /// Captures the current canvas state as a session at the current schema version.
+    fn to_session(&self) -> Session {
+        Session {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            blocks: self
+                .blocks()
+                .iter()
+                .map(|b| Self::block_to_data(b))
+                .collect(),
+            remembered_chains: self.serialize_remembered_chains(),
+            last_unboxed_ids: self.last_unboxed_ids.clone(),
+            last_boxed_id: self.last_boxed_id,
+            zoom: self.zoom,
+            show_file_names: self.show_file_names,
+            watched_folders: self.watcher.folders().to_vec(),
+            audio_muted: self.audio_muted,
+            exclusive_audio: self.exclusive_audio,
+            animation_budget_mb: self.block_manager.animation_budget_mb(),
+        }
+    }
+
     fn block_to_data(b: &ImageBlock) -> BlockData {
         BlockData {
             id: b.id,
@@ -1246,6 +2581,15 @@ impl MaBlocksApp {
                 .iter()
                 .map(|c| Self::block_to_data(c))
                 .collect(),
+            fingerprint: b.fingerprint,
+            video_position: b.video_position,
+            video_metadata: b.video_metadata.clone(),
+            video_trim: b.video_trim,
+            poster: b.poster,
+            audio_muted: b.audio_muted,
+            volume: b.volume,
+            playback_speed: b.playback_speed,
+            playback_mode: b.playback_mode,
         }
     }
 
@@ -1279,20 +2623,208 @@ impl MaBlocksApp {
         }
 
         if let Some(path) = dialog.pick_file() {
-            if let Ok(file) = std::fs::File::open(&path) {
-                if let Ok(session) =
-                    serde_json::from_reader::<_, Session>(std::io::BufReader::new(file))
-                {
+            self.open_session_file(ctx, path);
+        }
+    }
+
+    /// Replaces the canvas with the session stored in the JSON file at `path`.
+    fn open_session_file(&mut self, ctx: &egui::Context, path: PathBuf) {
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                log::error!("Failed to open session {}: {err}", path.display());
+                return;
+            }
+        };
+        match session::session_from_reader(std::io::BufReader::new(file)) {
+            Ok(session) => {
+                self.apply_session_data(ctx, session);
+                self.session_file = Some(path);
+            }
+            Err(err) => log::error!("Failed to load session {}: {err}", path.display()),
+        }
+    }
+
+    /// Exports the current session plus all referenced media into a single archive, on a
+    /// background thread; see `poll_exports`.
+    fn export_bundle(&mut self, ctx: &egui::Context) {
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("Bundle", &["zip"])
+            .set_file_name("ma_blocks_bundle.zip");
+
+        if let Some(ref p) = self.paths {
+            dialog = dialog.set_directory(&p.sessions);
+        }
+
+        if let Some(path) = dialog.save_file() {
+            let session = self.to_session();
+            let (tx, rx) = channel();
+            self.bundle_export = Some((rx, 0.0));
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let result = bundle::export_bundle(session, &path, &|done| {
+                    let _ = tx.send(ExportUpdate::Progress(done));
+                    ctx.request_repaint();
+                });
+                let _ = tx.send(ExportUpdate::Done(path, result));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Renders the whole board to a PNG at the current zoom (at least 100%) and layout width,
+    /// on a background thread; see `poll_exports`.
+    fn export_png(&mut self, ctx: &egui::Context) {
+        let mut dialog = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .set_file_name("ma_blocks_board.png");
+
+        if let Some(ref p) = self.paths {
+            dialog = dialog.set_directory(&p.sessions);
+        }
+
+        if let Some(path) = dialog.save_file() {
+            let options = export::ExportOptions {
+                scale: self.zoom.max(1.0),
+                inner_width: self.working_inner_width,
+                show_file_names: self.show_file_names,
+            };
+            let session = self.to_session();
+            let (tx, rx) = channel();
+            self.png_export = Some((rx, 0.0));
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let result = export::export_png(session, &path, options, &|done| {
+                    let _ = tx.send(ExportUpdate::Progress(done));
+                    ctx.request_repaint();
+                });
+                let _ = tx.send(ExportUpdate::Done(path, result));
+                ctx.request_repaint();
+            });
+        }
+    }
+
+    /// Reports finished background exports.
+    fn poll_exports(&mut self) {
+        match poll_export(&mut self.png_export) {
+            Some((path, Ok([width, height]))) => {
+                log::info!("Exported {width}x{height} px board to {}", path.display())
+            }
+            Some((_, Err(err))) => log::error!("Failed to export PNG: {err}"),
+            None => {}
+        }
+        match poll_export(&mut self.bundle_export) {
+            Some((path, Ok(count))) => {
+                log::info!("Exported bundle with {count} files to {}", path.display())
+            }
+            Some((_, Err(err))) => log::error!("Failed to export bundle: {err}"),
+            None => {}
+        }
+    }
+
+    /// Imports a bundle archive, extracting its media into the images directory.
+    fn import_bundle(&mut self, ctx: &egui::Context) {
+        let Some(images_dir) = self.paths.as_ref().map(|p| p.images.clone()) else {
+            log::error!("Cannot import bundle: no application data directory");
+            return;
+        };
+
+        let dialog = rfd::FileDialog::new()
+            .add_filter("Bundle", &["zip"])
+            .set_directory(&images_dir);
+
+        if let Some(path) = dialog.pick_file() {
+            match bundle::import_bundle(&path, &images_dir) {
+                Ok(session) => {
                     self.apply_session_data(ctx, session);
-                    self.session_file = Some(path);
+                    self.session_file = None;
                 }
+                Err(err) => log::error!("Failed to import bundle {}: {err}", path.display()),
             }
         }
     }
 
     fn reset_all_counters(&mut self) {
+        self.history.begin(&self.block_manager);
         self.block_manager.reset_all_counters();
     }
+
+    fn sort_blocks(&mut self, key: SortKey) {
+        self.history.begin(&self.block_manager);
+        self.block_manager
+            .sort_blocks(key, self.working_inner_width);
+    }
+
+    /// Ctrl+Z undoes and Ctrl+Shift+Z redoes the last canvas operation. Ignored while a
+    /// drag or resize is in progress.
+    fn handle_undo_shortcuts(&mut self, ctx: &egui::Context) {
+        let (undo, redo) = ctx.input_mut(|i| {
+            // Match the more specific shortcut first: Ctrl+Z also matches with Shift held.
+            let redo = i.consume_key(
+                egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                egui::Key::Z,
+            );
+            let undo = i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z);
+            (undo, redo)
+        });
+        if self.block_manager.any_dragging() || self.resizing_state.is_some() {
+            return;
+        }
+
+        let changed = if redo {
+            self.history.redo(&mut self.block_manager)
+        } else if undo {
+            self.history.undo(&mut self.block_manager)
+        } else {
+            false
+        };
+        if changed {
+            self.hovered_box_id = None;
+            self.queue_skeleton_loads();
+            self.reflow_blocks();
+        }
+    }
+}
+
+/// Decodes a file for the load queue, downsampled to the block size. First frames come
+/// from the thumbnail cache if possible, and are added to it otherwise.
+fn load_image_file(
+    thumbnails: Option<&ThumbnailCache>,
+    path: &Path,
+    first_frame_only: bool,
+) -> image_loader::ImageLoadResponse {
+    let path = path.to_path_buf();
+    let thumbnails = thumbnails.filter(|_| first_frame_only);
+    if let Some(loaded) = thumbnails.and_then(|cache| cache.load(&path)) {
+        return Ok((path, loaded, false));
+    }
+    match image_loader::load_image_frames_scaled(
+        &path,
+        Some(MAX_BLOCK_DIMENSION as u32),
+        first_frame_only,
+    ) {
+        Ok(mut loaded) => {
+            if first_frame_only {
+                loaded.fingerprint = relink::file_fingerprint(&path).ok();
+            }
+            if let Some(cache) = thumbnails {
+                if let Err(err) = cache.store(&path, &loaded) {
+                    log::warn!("Failed to cache thumbnail: {err}");
+                }
+            }
+            Ok((path, loaded, !first_frame_only))
+        }
+        Err(err) => Err((path, err)),
+    }
+}
+
+/// Formats a size for menus, e.g. "512 MB" or "2 GB".
+fn format_megabytes(mb: u32) -> String {
+    if mb >= 1024 {
+        format!("{} GB", f64::from(mb) / 1024.0)
+    } else {
+        format!("{mb} MB")
+    }
 }
 
 fn scaled_size(original: Vec2) -> Vec2 {
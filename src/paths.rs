@@ -6,6 +6,8 @@ use std::path::PathBuf;
 pub struct AppPaths {
     pub sessions: PathBuf,
     pub images: PathBuf,
+    /// Downsampled first frames of media files (see `thumbnail_cache`).
+    pub thumbnails: PathBuf,
 }
 
 impl AppPaths {
@@ -15,8 +17,13 @@ impl AppPaths {
             let base = dirs.data_dir().to_path_buf();
             let sessions = base.join("sessions");
             let images = base.join("images");
+            let thumbnails = base.join("thumbnails");
 
-            Self { sessions, images }
+            Self {
+                sessions,
+                images,
+                thumbnails,
+            }
         })
     }
 
@@ -24,6 +31,7 @@ impl AppPaths {
     pub fn ensure_dirs_exist(&self) -> std::io::Result<()> {
         fs::create_dir_all(&self.sessions)?;
         fs::create_dir_all(&self.images)?;
+        fs::create_dir_all(&self.thumbnails)?;
         Ok(())
     }
 }
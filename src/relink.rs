@@ -0,0 +1,187 @@
+//! Relinking of blocks whose media files have gone missing (moved, renamed, or restored
+//! from another machine).
+//!
+//! Missing paths are remapped by file name against the files found in a user-chosen
+//! folder. Optionally, a content fingerprint recorded while the file was still reachable
+//! resolves ambiguous names and finds renamed files.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Number of bytes sampled from each end of a file for its fingerprint.
+const FINGERPRINT_SAMPLE_BYTES: u64 = 64 * 1024;
+
+/// A block path that failed to load, with the fingerprint recorded for it (if any).
+pub struct MissingFile {
+    pub path: String,
+    pub fingerprint: Option<u64>,
+}
+
+/// Computes a cheap content fingerprint: FNV-1a over the file length plus the first and
+/// last 64 KiB. Stable across runs and platforms, so it can be persisted in sessions.
+pub fn file_fingerprint(path: &Path) -> std::io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, &len.to_le_bytes());
+    let mut buffer = Vec::with_capacity(FINGERPRINT_SAMPLE_BYTES as usize);
+    (&mut file)
+        .take(FINGERPRINT_SAMPLE_BYTES)
+        .read_to_end(&mut buffer)?;
+    hash = fnv1a(hash, &buffer);
+
+    if len > FINGERPRINT_SAMPLE_BYTES * 2 {
+        buffer.clear();
+        file.seek(SeekFrom::End(-(FINGERPRINT_SAMPLE_BYTES as i64)))?;
+        file.read_to_end(&mut buffer)?;
+        hash = fnv1a(hash, &buffer);
+    }
+    Ok(hash)
+}
+
+/// Initial value of an FNV-1a hash.
+pub const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continues the FNV-1a hash `hash` over `bytes`.
+pub fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for &byte in bytes {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Recursively lists all regular files below `root`. Unreadable entries are skipped.
+pub fn scan_folder(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => pending.push(path),
+                Ok(t) if t.is_file() => files.push(path),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Maps each missing path to a replacement among `candidates`.
+///
+/// A missing file is matched by file name when exactly one candidate shares it. With
+/// `match_by_fingerprint`, candidates are also fingerprinted: an equal fingerprint wins
+/// over name matching, which both disambiguates duplicates and finds renamed files.
+/// Paths without a unique match are left out of the result.
+pub fn plan_relink(
+    missing: &[MissingFile],
+    candidates: &[PathBuf],
+    match_by_fingerprint: bool,
+) -> HashMap<String, PathBuf> {
+    let mut by_name: HashMap<&str, Vec<&PathBuf>> = HashMap::new();
+    for candidate in candidates {
+        if let Some(name) = candidate.file_name().and_then(|n| n.to_str()) {
+            by_name.entry(name).or_default().push(candidate);
+        }
+    }
+
+    let wants_fingerprints =
+        match_by_fingerprint && missing.iter().any(|m| m.fingerprint.is_some());
+    let mut by_fingerprint: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    if wants_fingerprints {
+        for candidate in candidates {
+            if let Ok(fingerprint) = file_fingerprint(candidate) {
+                by_fingerprint
+                    .entry(fingerprint)
+                    .or_default()
+                    .push(candidate);
+            }
+        }
+    }
+
+    let mut plan = HashMap::new();
+    for file in missing {
+        let name = Path::new(&file.path).file_name().and_then(|n| n.to_str());
+        let same_name = name.and_then(|n| by_name.get(n));
+
+        let by_content = file
+            .fingerprint
+            .filter(|_| wants_fingerprints)
+            .and_then(|fp| by_fingerprint.get(&fp));
+
+        let chosen = match (by_content, same_name) {
+            // Prefer a content match that also keeps the original name.
+            (Some(content), _) => content
+                .iter()
+                .find(|c| c.file_name().and_then(|n| n.to_str()) == name)
+                .or_else(|| (content.len() == 1).then(|| &content[0])),
+            (None, Some(named)) if named.len() == 1 => Some(&named[0]),
+            _ => None,
+        };
+
+        if let Some(path) = chosen {
+            plan.insert(file.path.clone(), (*path).clone());
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_root() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("ma_blocks_relink_{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn test_plan_relink_by_name_and_fingerprint() {
+        let root = temp_root();
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("unique.png"), b"unique").unwrap();
+        fs::write(root.join("a/dup.png"), b"dup a").unwrap();
+        fs::write(root.join("b/dup.png"), b"dup b").unwrap();
+        fs::write(root.join("renamed.gif"), b"renamed").unwrap();
+
+        let fingerprint = |p: &str| Some(file_fingerprint(&root.join(p)).unwrap());
+        let missing = vec![
+            MissingFile {
+                path: "/old/unique.png".into(),
+                fingerprint: None,
+            },
+            MissingFile {
+                path: "/old/dup.png".into(),
+                fingerprint: fingerprint("b/dup.png"),
+            },
+            MissingFile {
+                path: "/old/original.gif".into(),
+                fingerprint: fingerprint("renamed.gif"),
+            },
+        ];
+        let candidates = scan_folder(&root);
+
+        let by_name = plan_relink(&missing, &candidates, false);
+        assert_eq!(by_name["/old/unique.png"], root.join("unique.png"));
+        assert!(!by_name.contains_key("/old/dup.png"));
+        assert!(!by_name.contains_key("/old/original.gif"));
+
+        let by_hash = plan_relink(&missing, &candidates, true);
+        assert_eq!(by_hash["/old/unique.png"], root.join("unique.png"));
+        assert_eq!(by_hash["/old/dup.png"], root.join("b/dup.png"));
+        assert_eq!(by_hash["/old/original.gif"], root.join("renamed.gif"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
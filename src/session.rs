@@ -0,0 +1,495 @@
+//! Persisted session format and its schema migrations.
+//!
+//! Sessions are stored as JSON, in files (explicit save/load) and as text in eframe's
+//! storage under `APP_KEY` (automatic persistence). Both carry a `schema_version`; older
+//! documents are upgraded step by step on a generic `serde_json::Value` before being
+//! deserialized into the current `Session` shape, so no field is silently dropped when the
+//! format evolves.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+/// A single migration step, upgrading a session document by exactly one version in place.
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Ordered migration pipeline: `MIGRATIONS[n]` upgrades a version `n` document to `n + 1`.
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+/// Schema version written by this build. Always equal to the number of migration steps.
+pub const CURRENT_SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Represents a saved application session, containing blocks and their relationships.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub blocks: Vec<BlockData>,
+    #[serde(default)]
+    pub remembered_chains: Vec<Vec<String>>,
+    #[serde(default)]
+    pub last_unboxed_ids: Vec<Uuid>,
+    #[serde(default)]
+    pub last_boxed_id: Option<Uuid>,
+    #[serde(default = "default_zoom")]
+    pub zoom: f32,
+    #[serde(default)]
+    pub show_file_names: bool,
+    #[serde(default)]
+    pub watched_folders: Vec<WatchedFolder>,
+    /// Global mute of all video sound.
+    #[serde(default)]
+    pub audio_muted: bool,
+    /// Only the video started last plays its sound.
+    #[serde(default = "default_exclusive_audio")]
+    pub exclusive_audio: bool,
+    /// Memory in MB for the frames of played animations and videos.
+    #[serde(default = "default_animation_budget_mb")]
+    pub animation_budget_mb: u32,
+}
+
+fn default_zoom() -> f32 {
+    1.0
+}
+
+fn default_volume() -> f32 {
+    1.0
+}
+
+fn default_playback_speed() -> f32 {
+    1.0
+}
+
+pub(crate) fn default_exclusive_audio() -> bool {
+    true
+}
+
+pub(crate) fn default_animation_budget_mb() -> u32 {
+    crate::constants::DEFAULT_ANIMATION_BUDGET_MB
+}
+
+/// Serialized form of an ImageBlock for persistence.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockData {
+    pub id: Uuid,
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub path: String,
+    pub chained: bool,
+    pub animation_enabled: bool,
+    pub counter: i32,
+    #[serde(default)]
+    pub counter_start_day: u32,
+    #[serde(default)]
+    pub is_group: bool,
+    #[serde(default)]
+    pub group_name: String,
+    #[serde(default)]
+    pub color: [u8; 4],
+    #[serde(default)]
+    pub children: Vec<BlockData>,
+    #[serde(default)]
+    pub fingerprint: Option<u64>,
+    /// Scrub position of a video block in seconds.
+    #[serde(default)]
+    pub video_position: Option<f64>,
+    /// Probed video properties, so they are shown before the file is reloaded.
+    #[serde(default)]
+    pub video_metadata: Option<crate::video_stream::VideoMetadata>,
+    /// In and out points of a video block.
+    #[serde(default)]
+    pub video_trim: Option<crate::video_stream::TrimRange>,
+    /// Frame shown while a video or animation is not playing.
+    #[serde(default)]
+    pub poster: Option<crate::image_loader::PosterFrame>,
+    /// Set when the sound of a video block is turned off.
+    #[serde(default)]
+    pub audio_muted: bool,
+    /// Volume (0..=1) of a video block's sound.
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    /// Playback rate of a video or animation.
+    #[serde(default = "default_playback_speed")]
+    pub playback_speed: f32,
+    /// Frame order of an animation.
+    #[serde(default)]
+    pub playback_mode: crate::block::PlaybackMode,
+}
+
+/// An empty block at the origin, with the same defaults as a deserialized one.
+impl Default for BlockData {
+    fn default() -> Self {
+        Self {
+            id: Uuid::nil(),
+            position: [0.0, 0.0],
+            size: [0.0, 0.0],
+            path: String::new(),
+            chained: false,
+            animation_enabled: false,
+            counter: 0,
+            counter_start_day: 0,
+            is_group: false,
+            group_name: String::new(),
+            color: [0; 4],
+            children: Vec::new(),
+            fingerprint: None,
+            video_position: None,
+            video_metadata: None,
+            video_trim: None,
+            poster: None,
+            audio_muted: false,
+            volume: default_volume(),
+            playback_speed: default_playback_speed(),
+            playback_mode: Default::default(),
+        }
+    }
+}
+
+/// A directory whose new files are imported automatically, bound to the canvas or a Box.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatchedFolder {
+    pub path: String,
+    /// Box block receiving new files; `None` adds them to the canvas.
+    #[serde(default)]
+    pub target: Option<Uuid>,
+}
+
+/// Reads the schema version of a raw session document. Documents written before
+/// versioning was introduced have no `schema_version` field and are treated as version 0.
+fn schema_version_of(value: &Value) -> Result<u32, String> {
+    match value.get("schema_version") {
+        None | Some(Value::Null) => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .map(|v| v as u32)
+            .ok_or_else(|| format!("Invalid schema_version: {v}")),
+    }
+}
+
+/// Upgrades a raw session document to `CURRENT_SCHEMA_VERSION` by applying every pending
+/// migration step in order.
+pub fn migrate(mut value: Value) -> Result<Value, String> {
+    if !value.is_object() {
+        return Err("Session document is not a JSON object".to_string());
+    }
+
+    let version = schema_version_of(&value)?;
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "Session schema v{version} is newer than supported v{CURRENT_SCHEMA_VERSION}"
+        ));
+    }
+
+    for (step, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(&mut value)
+            .map_err(|err| format!("Migration v{step} -> v{}: {err}", step + 1))?;
+        value["schema_version"] = Value::from(step as u32 + 1);
+    }
+
+    Ok(value)
+}
+
+/// Migrates a raw session document and deserializes it into the current `Session` shape.
+pub fn session_from_value(value: Value) -> Result<Session, String> {
+    let migrated = migrate(value)?;
+    serde_json::from_value(migrated).map_err(|err| format!("Invalid session: {err}"))
+}
+
+/// Parses a session from any JSON reader, migrating older schema versions.
+pub fn session_from_reader<R: std::io::Read>(reader: R) -> Result<Session, String> {
+    let value: Value =
+        serde_json::from_reader(reader).map_err(|err| format!("Invalid session JSON: {err}"))?;
+    session_from_value(value)
+}
+
+/// Persists the session in eframe storage under `APP_KEY`, as JSON text like session files.
+pub fn session_to_storage(storage: &mut dyn eframe::Storage, session: &Session) {
+    match serde_json::to_string(session) {
+        Ok(json) => storage.set_string(eframe::APP_KEY, json),
+        Err(err) => log::error!("Failed to persist session: {err}"),
+    }
+}
+
+/// Loads the automatically persisted session from eframe storage, migrating it if needed.
+/// Returns None when nothing was persisted yet.
+pub fn session_from_storage(storage: &dyn eframe::Storage) -> Option<Result<Session, String>> {
+    let text = storage.get_string(eframe::APP_KEY)?;
+    if let Ok(value) = serde_json::from_str::<Value>(&text) {
+        return Some(session_from_value(value));
+    }
+    // Earlier builds stored RON (`eframe::set_value`). Read untyped, RON drops the names of
+    // enum variants, so it is read as a `Session` and then migrated like JSON.
+    let session = eframe::get_value::<Session>(storage, eframe::APP_KEY)
+        .ok_or_else(|| "Stored session is neither JSON nor a known RON shape".to_string());
+    Some(
+        session
+            .and_then(|s| serde_json::to_value(s).map_err(|err| format!("Invalid session: {err}")))
+            .and_then(session_from_value),
+    )
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Migration steps
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// v0 → v1: unversioned documents. Early builds wrote blocks without the group fields,
+/// `counter_start_day`, or the session-level chain/zoom settings. All of these are
+/// materialized with their historical defaults so the document matches the v1 shape.
+fn migrate_v0_to_v1(value: &mut Value) -> Result<(), String> {
+    let session = value
+        .as_object_mut()
+        .ok_or_else(|| "Session document is not a JSON object".to_string())?;
+
+    session
+        .entry("remembered_chains")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    session
+        .entry("last_unboxed_ids")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    session.entry("last_boxed_id").or_insert(Value::Null);
+    session
+        .entry("zoom")
+        .or_insert_with(|| Value::from(default_zoom()));
+    session
+        .entry("show_file_names")
+        .or_insert(Value::Bool(false));
+
+    let blocks = session
+        .get_mut("blocks")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| "Missing blocks array".to_string())?;
+    for block in blocks {
+        fill_v1_block_defaults(block)?;
+    }
+    Ok(())
+}
+
+fn fill_v1_block_defaults(block: &mut Value) -> Result<(), String> {
+    let block = block
+        .as_object_mut()
+        .ok_or_else(|| "Block entry is not a JSON object".to_string())?;
+
+    block
+        .entry("counter_start_day")
+        .or_insert_with(|| Value::from(0u32));
+    block.entry("is_group").or_insert(Value::Bool(false));
+    block
+        .entry("group_name")
+        .or_insert_with(|| Value::String(String::new()));
+    block
+        .entry("color")
+        .or_insert_with(|| Value::from(vec![0u8; 4]));
+    let children = block
+        .entry("children")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    if let Some(children) = children.as_array_mut() {
+        for child in children {
+            fill_v1_block_defaults(child)?;
+        }
+    }
+    Ok(())
+}
+
+/// v1 → v2: adds the watched folders, sound, playback and memory settings and the
+/// per-block video, poster, fingerprint and playback fields. All of them have serde
+/// defaults, so nothing needs rewriting; the bump lets older builds reject documents
+/// they would re-save without these fields.
+fn migrate_v1_to_v2(_value: &mut Value) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Oldest known shape: flat list of image blocks, no groups, no session settings.
+    const V0_ORIGINAL: &str = r#"{
+        "blocks": [
+            {
+                "id": "6f1c9a52-7c1f-4b43-9d6e-0a3f7a0a1b01",
+                "position": [32.0, 32.0],
+                "size": [420.0, 236.0],
+                "path": "/home/user/images/cat.png",
+                "chained": false,
+                "animation_enabled": false,
+                "counter": 3
+            }
+        ]
+    }"#;
+
+    /// Unversioned shape after boxes and remembered chains were introduced.
+    const V0_GROUPS: &str = r#"{
+        "blocks": [
+            {
+                "id": "6f1c9a52-7c1f-4b43-9d6e-0a3f7a0a1b02",
+                "position": [32.0, 32.0],
+                "size": [160.0, 160.0],
+                "path": "",
+                "chained": true,
+                "animation_enabled": false,
+                "counter": 0,
+                "is_group": true,
+                "group_name": "Group of 1",
+                "color": [200, 100, 50, 255],
+                "children": [
+                    {
+                        "id": "6f1c9a52-7c1f-4b43-9d6e-0a3f7a0a1b03",
+                        "position": [0.0, 0.0],
+                        "size": [300.0, 200.0],
+                        "path": "/home/user/images/dog.gif",
+                        "chained": false,
+                        "animation_enabled": true,
+                        "counter": 1
+                    }
+                ]
+            }
+        ],
+        "remembered_chains": [[
+            "6f1c9a52-7c1f-4b43-9d6e-0a3f7a0a1b02",
+            "6f1c9a52-7c1f-4b43-9d6e-0a3f7a0a1b04"
+        ]],
+        "zoom": 1.5
+    }"#;
+
+    /// Last unversioned shape: everything but `schema_version`.
+    const V0_COUNTER_DAYS: &str = r#"{
+        "blocks": [
+            {
+                "id": "6f1c9a52-7c1f-4b43-9d6e-0a3f7a0a1b05",
+                "position": [32.0, 32.0],
+                "size": [420.0, 420.0],
+                "path": "/home/user/images/clip.webm",
+                "chained": false,
+                "animation_enabled": false,
+                "counter": 2,
+                "counter_start_day": 20000,
+                "is_group": false,
+                "group_name": "",
+                "color": [1, 2, 3, 255],
+                "children": []
+            }
+        ],
+        "remembered_chains": [],
+        "last_unboxed_ids": ["6f1c9a52-7c1f-4b43-9d6e-0a3f7a0a1b05"],
+        "last_boxed_id": null,
+        "zoom": 0.75,
+        "show_file_names": true
+    }"#;
+
+    /// Parses a fixture, serializes the migrated session and parses it again, asserting
+    /// that the second pass is a no-op.
+    fn round_trip(json: &str) -> Session {
+        let session = session_from_value(serde_json::from_str(json).unwrap()).unwrap();
+        assert_eq!(session.schema_version, CURRENT_SCHEMA_VERSION);
+
+        let first = serde_json::to_value(&session).unwrap();
+        let reparsed = session_from_value(first.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&reparsed).unwrap(), first);
+        session
+    }
+
+    #[test]
+    fn test_migrate_v0_original() {
+        let session = round_trip(V0_ORIGINAL);
+        assert_eq!(session.blocks.len(), 1);
+        assert_eq!(session.blocks[0].counter, 3);
+        assert_eq!(session.blocks[0].counter_start_day, 0);
+        assert!(!session.blocks[0].is_group);
+        assert_eq!(session.zoom, 1.0);
+        assert!(session.remembered_chains.is_empty());
+    }
+
+    #[test]
+    fn test_migrate_v0_groups() {
+        let session = round_trip(V0_GROUPS);
+        let group = &session.blocks[0];
+        assert!(group.is_group);
+        assert_eq!(group.color, [200, 100, 50, 255]);
+        assert_eq!(group.children.len(), 1);
+        assert_eq!(group.children[0].path, "/home/user/images/dog.gif");
+        assert_eq!(group.children[0].counter, 1);
+        assert_eq!(session.remembered_chains.len(), 1);
+        assert_eq!(session.zoom, 1.5);
+    }
+
+    #[test]
+    fn test_migrate_v0_counter_days() {
+        let session = round_trip(V0_COUNTER_DAYS);
+        assert_eq!(session.blocks[0].counter_start_day, 20000);
+        assert_eq!(session.last_unboxed_ids.len(), 1);
+        assert!(session.show_file_names);
+        assert_eq!(session.zoom, 0.75);
+    }
+
+    #[test]
+    fn test_migrate_v1_keeps_fields() {
+        let value = serde_json::json!({
+            "schema_version": 1,
+            "blocks": [],
+            "zoom": 2.0,
+            "show_file_names": true
+        });
+        let migrated = migrate(value).unwrap();
+        assert_eq!(migrated["schema_version"], CURRENT_SCHEMA_VERSION);
+        let session = round_trip(&migrated.to_string());
+        assert_eq!(session.zoom, 2.0);
+        assert!(session.show_file_names);
+    }
+
+    /// In-memory eframe storage.
+    #[derive(Default)]
+    struct MemoryStorage(std::collections::HashMap<String, String>);
+
+    impl eframe::Storage for MemoryStorage {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_string(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[test]
+    fn test_storage_round_trip_keeps_enums() {
+        use crate::block::PlaybackMode;
+        use crate::image_loader::PosterFrame;
+
+        let mut session = round_trip(V0_GROUPS);
+        session.blocks[0].children[0].playback_mode = PlaybackMode::PingPong;
+        session.blocks[0].children[0].poster = Some(PosterFrame::Index(3));
+        session.blocks.push(BlockData {
+            id: Uuid::new_v4(),
+            path: "/home/user/images/clip.webm".to_string(),
+            poster: Some(PosterFrame::Time(1.5)),
+            playback_mode: PlaybackMode::Once,
+            ..Default::default()
+        });
+
+        let mut storage = MemoryStorage::default();
+        session_to_storage(&mut storage, &session);
+        assert!(session_from_storage(&storage).unwrap() == Ok(session.clone()));
+
+        // Sessions persisted as RON by earlier builds.
+        let mut storage = MemoryStorage::default();
+        eframe::set_value(&mut storage, eframe::APP_KEY, &session);
+        assert!(session_from_storage(&storage).unwrap() == Ok(session.clone()));
+
+        assert!(session_from_storage(&MemoryStorage::default()).is_none());
+        let mut storage = MemoryStorage::default();
+        eframe::Storage::set_string(&mut storage, eframe::APP_KEY, "(blocks: 3)".to_string());
+        assert!(session_from_storage(&storage).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_schema() {
+        let value = serde_json::json!({
+            "schema_version": CURRENT_SCHEMA_VERSION + 1,
+            "blocks": []
+        });
+        assert!(migrate(value).is_err());
+    }
+}
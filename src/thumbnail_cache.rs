@@ -0,0 +1,294 @@
+//! On-disk cache of the downsampled first frames shown for blocks, so restoring a board
+//! does not decode every original file again.
+//!
+//! Entries are named after a hash of the file's path, modification time and size, so an
+//! edited or replaced file simply misses and gets a new entry. Each entry is one file: a
+//! JSON header line with the image's properties, followed by the frame as PNG. Reading an
+//! entry refreshes its modification time, and `trim` removes the least recently used
+//! entries once the cache outgrows its budget: at startup, and again after every
+//! `THUMBNAIL_CACHE_TRIM_INTERVAL` stores.
+
+use crate::constants::{THUMBNAIL_CACHE_MIN_FILE_BYTES, THUMBNAIL_CACHE_TRIM_INTERVAL};
+use crate::image_loader::{AnimationFrame, LoadedImage};
+use crate::relink::{fnv1a, FNV_OFFSET_BASIS};
+use crate::video_stream::VideoMetadata;
+use image::ImageEncoder;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Thumbnail cache in a directory of the app data folder (`AppPaths.thumbnails`).
+#[derive(Clone)]
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Entries written since startup, shared by all clones.
+    stores: Arc<AtomicUsize>,
+}
+
+/// Properties of the cached image, stored before its pixels.
+#[derive(Serialize, Deserialize)]
+struct EntryHeader {
+    original_size: [f32; 2],
+    has_animation: bool,
+    frame_duration: Duration,
+    fingerprint: Option<u64>,
+    video_metadata: Option<VideoMetadata>,
+}
+
+impl ThumbnailCache {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            max_bytes,
+            stores: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the cached first frame of `path`, or None if there is none for the file as
+    /// it is now. Unreadable entries are removed.
+    pub fn load(&self, path: &Path) -> Option<LoadedImage> {
+        let (entry, _) = self.entry_path(path)?;
+        let bytes = fs::read(&entry).ok()?;
+        match decode_entry(&bytes) {
+            Ok(loaded) => {
+                // Mark the entry as recently used for `trim`.
+                let _ = File::options()
+                    .write(true)
+                    .open(&entry)
+                    .and_then(|file| file.set_modified(SystemTime::now()));
+                Some(loaded)
+            }
+            Err(err) => {
+                log::warn!("Discarding thumbnail {}: {err}", entry.display());
+                let _ = fs::remove_file(&entry);
+                None
+            }
+        }
+    }
+
+    /// Caches the first frame of `loaded`, decoded from `path`. Small images are skipped
+    /// (see `THUMBNAIL_CACHE_MIN_FILE_BYTES`). Every `THUMBNAIL_CACHE_TRIM_INTERVAL`
+    /// entries, the cache is trimmed to its budget.
+    pub fn store(&self, path: &Path, loaded: &LoadedImage) -> Result<(), String> {
+        let Some((entry, file_len)) = self.entry_path(path) else {
+            return Ok(());
+        };
+        if file_len < THUMBNAIL_CACHE_MIN_FILE_BYTES && loaded.video_metadata.is_none() {
+            return Ok(());
+        }
+        let bytes = encode_entry(loaded)?;
+
+        // Written under a unique name first, so concurrent loads never see half an entry.
+        let temp = self.dir.join(format!("{}.tmp", Uuid::new_v4()));
+        fs::write(&temp, &bytes)
+            .and_then(|_| fs::rename(&temp, &entry))
+            .map_err(|err| {
+                let _ = fs::remove_file(&temp);
+                format!("Failed to write {}: {err}", entry.display())
+            })?;
+
+        let stores = self.stores.fetch_add(1, Ordering::Relaxed) + 1;
+        if stores.is_multiple_of(THUMBNAIL_CACHE_TRIM_INTERVAL) {
+            match self.trim() {
+                Ok(0) => {}
+                Ok(removed) => log::info!("Removed {removed} old thumbnails from the cache"),
+                Err(err) => log::error!("Failed to trim the thumbnail cache: {err}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the least recently used entries until the cache fits in its budget.
+    /// Other files, such as entries still being written, are left alone. Returns the
+    /// number of entries removed.
+    pub fn trim(&self) -> Result<usize, String> {
+        let dir = fs::read_dir(&self.dir)
+            .map_err(|err| format!("Failed to read {}: {err}", self.dir.display()))?;
+        let mut files: Vec<(SystemTime, u64, PathBuf)> = dir
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension().is_none_or(|ext| ext != "thumb") {
+                    return None;
+                }
+                let metadata = entry.metadata().ok()?;
+                metadata.is_file().then(|| {
+                    let used = metadata.modified().unwrap_or(UNIX_EPOCH);
+                    (used, metadata.len(), path)
+                })
+            })
+            .collect();
+        files.sort();
+
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        let mut removed = 0;
+        for (_, len, path) in files {
+            if total <= self.max_bytes {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Returns the entry for `path` in its current version, and the file's length.
+    fn entry_path(&self, path: &Path) -> Option<(PathBuf, u64)> {
+        let metadata = fs::metadata(path).ok()?;
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        let mut key = fnv1a(FNV_OFFSET_BASIS, path.to_string_lossy().as_bytes());
+        key = fnv1a(key, &modified.to_le_bytes());
+        key = fnv1a(key, &metadata.len().to_le_bytes());
+        Some((self.dir.join(format!("{key:016x}.thumb")), metadata.len()))
+    }
+}
+
+fn encode_entry(loaded: &LoadedImage) -> Result<Vec<u8>, String> {
+    let frame = loaded.frames.first().ok_or("No frame to cache")?;
+    let header = EntryHeader {
+        original_size: [loaded.original_size.x, loaded.original_size.y],
+        has_animation: loaded.has_animation,
+        frame_duration: frame.duration,
+        fingerprint: loaded.fingerprint,
+        video_metadata: loaded.video_metadata.clone(),
+    };
+    let mut bytes = serde_json::to_vec(&header)
+        .map_err(|err| format!("Failed to serialize thumbnail header: {err}"))?;
+    bytes.push(b'\n');
+
+    let [w, h] = frame.image.size;
+    let rgba: Vec<u8> = frame
+        .image
+        .pixels
+        .iter()
+        .flat_map(|p| p.to_srgba_unmultiplied())
+        .collect();
+    image::codecs::png::PngEncoder::new(&mut bytes)
+        .write_image(&rgba, w as u32, h as u32, image::ExtendedColorType::Rgba8)
+        .map_err(|err| format!("Failed to encode thumbnail: {err}"))?;
+    Ok(bytes)
+}
+
+fn decode_entry(bytes: &[u8]) -> Result<LoadedImage, String> {
+    let split = bytes
+        .iter()
+        .position(|b| *b == b'\n')
+        .ok_or("Missing header")?;
+    let header: EntryHeader =
+        serde_json::from_slice(&bytes[..split]).map_err(|err| format!("Invalid header: {err}"))?;
+    let image = image::load_from_memory_with_format(&bytes[split + 1..], image::ImageFormat::Png)
+        .map_err(|err| format!("Invalid image: {err}"))?
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+
+    let mut loaded = LoadedImage::from_frames(
+        vec![AnimationFrame {
+            image: egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+            duration: header.frame_duration,
+        }],
+        header.has_animation,
+    );
+    loaded.original_size = egui::vec2(header.original_size[0], header.original_size[1]);
+    loaded.fingerprint = header.fingerprint;
+    loaded.video_metadata = header.video_metadata;
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thumbnail(color: egui::Color32) -> LoadedImage {
+        let mut loaded = LoadedImage::from_frames(
+            vec![AnimationFrame {
+                image: egui::ColorImage::new([8, 6], color),
+                duration: Duration::from_millis(40),
+            }],
+            true,
+        );
+        loaded.original_size = egui::vec2(1600.0, 1200.0);
+        loaded.fingerprint = Some(42);
+        loaded
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let root = std::env::temp_dir().join(format!("ma_blocks_thumbs_{}", Uuid::new_v4()));
+        let cache = ThumbnailCache::new(root.join("cache"), u64::MAX);
+        fs::create_dir_all(root.join("cache")).unwrap();
+        let large = root.join("large.avif");
+        fs::write(&large, vec![0u8; THUMBNAIL_CACHE_MIN_FILE_BYTES as usize]).unwrap();
+        let small = root.join("small.png");
+        fs::write(&small, b"small").unwrap();
+
+        assert!(cache.load(&large).is_none());
+        cache.store(&large, &thumbnail(egui::Color32::RED)).unwrap();
+        let loaded = cache.load(&large).unwrap();
+        assert_eq!(loaded.frames[0].image.size, [8, 6]);
+        assert_eq!(loaded.frames[0].image.pixels[0], egui::Color32::RED);
+        assert_eq!(loaded.frames[0].duration, Duration::from_millis(40));
+        assert_eq!(loaded.original_size, egui::vec2(1600.0, 1200.0));
+        assert!(loaded.has_animation);
+        assert_eq!(loaded.fingerprint, Some(42));
+
+        // A changed file misses the cache.
+        fs::write(
+            &large,
+            vec![1u8; THUMBNAIL_CACHE_MIN_FILE_BYTES as usize + 1],
+        )
+        .unwrap();
+        assert!(cache.load(&large).is_none());
+
+        cache.store(&small, &thumbnail(egui::Color32::RED)).unwrap();
+        assert!(cache.load(&small).is_none());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_trim_removes_least_recently_used() {
+        let root = std::env::temp_dir().join(format!("ma_blocks_thumbs_{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let now = SystemTime::now();
+        for (i, name) in ["a.thumb", "b.thumb", "c.thumb"].iter().enumerate() {
+            let path = root.join(name);
+            fs::write(&path, [0u8; 100]).unwrap();
+            // "b" was used last, then "a", then "c".
+            let age = [20, 0, 40][i];
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_secs(age))
+                .unwrap();
+        }
+
+        // An entry being written is neither counted nor removed.
+        fs::write(root.join("d.tmp"), [0u8; 100]).unwrap();
+
+        let cache = ThumbnailCache::new(root.clone(), 250);
+        assert_eq!(cache.trim().unwrap(), 1);
+        assert!(!root.join("c.thumb").exists());
+        assert!(root.join("a.thumb").exists());
+
+        let cache = ThumbnailCache::new(root.clone(), 100);
+        assert_eq!(cache.trim().unwrap(), 1);
+        assert!(!root.join("a.thumb").exists());
+        assert!(root.join("b.thumb").exists());
+        assert!(root.join("d.tmp").exists());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,28 +1,87 @@
-//! Per-block streaming video decoder for WebM/VP9 (and other ffmpeg-supported formats).
+//! Streaming video decoder for WebM/VP9 (and other ffmpeg-supported formats), also used
+//! to play animated GIF/WebP/AVIF images without decoding all of their frames first.
 //!
-//! Each playing block gets a background thread that owns the ffmpeg context
-//! (which is !Send) and writes decoded RGBA frames into an Arc<Mutex<...>>.
+//! Playing blocks are multiplexed onto a fixed pool of decode workers. A stream is
+//! opened (and probed) on the worker it is assigned to and stays there, since the
+//! ffmpeg context is !Send; decoded RGBA frames are written into an Arc<Mutex<...>>.
+//! Each worker services whichever of its streams has the nearest deadline.
 //! The render loop reads the latest frame each tick without blocking.
+//! Frames are shown at their presentation timestamps, so variable-frame-rate
+//! videos play at the right pace; each published frame also carries the deadline
+//! of the next one, which the render loop uses to schedule its repaint.
+//! Seeking jumps to the nearest preceding keyframe and decodes forward to the
+//! requested frame, which is published even while paused so scrubbing is live.
+//! At its end a video loops by seeking back to the start, keeping the input, decoder
+//! and scaler open, so a loop neither reopens the file nor rebuilds the pipeline.
+//! A trim range limits playback to a segment, which then loops instead of the video.
+//! Streams with sound also decode their audio track, reading ahead of the video to keep
+//! the audio output buffered, and then time their frames from the audio clock.
+//! Animated images are decoded with `image_loader::AnimationStream` and loop like videos;
+//! they have no sound and are not trimmed.
 //!
 //! Memory model:
 //! - Static block (not playing): only the first frame ColorImage in RAM (~700KB)
-//! - Playing block: ~2MB (decoder + scaler buffers + one ColorImage)
+//! - Playing block: ~3.5MB (decoder + scaler buffers + published and two queued ColorImages)
+//! - Playing with sound: plus ~0.5s of audio and the video packets read ahead for it
+//! - Playing animation: the same output frames, plus the file and the decoder's canvas
 
+use crate::audio::{AudioSource, Mixer, CHANNELS};
+use crate::constants::{
+    AUDIO_BUFFER_SECS, AUDIO_LOW_WATER_SECS, AUDIO_READ_AHEAD_PACKETS, VIDEO_DECODE_WORKERS,
+    VIDEO_MAX_LATENESS_MILLIS,
+};
+use crate::image_loader::{AnimationFrame, AnimationStream};
 use egui::ColorImage;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Frame duration assumed when the stream does not report a frame rate (~24 fps).
+const DEFAULT_FRAME_DURATION: Duration = Duration::from_millis(42);
+
+/// Output frames held for a stream: the first frame, the latest one, and up to two queued.
+const OUTPUT_FRAMES_HELD: usize = 4;
+
+/// Frames the decoder holds at the source resolution (the current one plus references),
+/// assumed for memory estimates. 8-bit 4:2:0 frames take 1.5 bytes per pixel.
+const DECODER_FRAMES_HELD: usize = 8;
 
 // ─────────────────────────────────────────────────────────────────────────────
 // Public types (all Send — safe to store on ImageBlock)
 // ─────────────────────────────────────────────────────────────────────────────
 
-/// Commands sent from the main thread to the background decoder thread.
-// Drop/shutdown is implicit: when VideoBlockHandle is dropped, the Sender
-// disconnects and the decoder thread exits on the next TryRecvError::Disconnected.
+/// Commands sent from the main thread to the stream's decode worker.
 pub enum StreamCmd {
     Play,
     Pause,
+    /// Shows the frame at the given position in seconds; playback continues from there.
+    Seek(f64),
+    /// Plays and loops only the given segment, or the whole video for None.
+    Trim(Option<TrimRange>),
+    /// Plays the audio track at the given volume (0..=1), or stops decoding it for None.
+    Audio(Option<f32>),
+    /// Plays at the given rate (1.0 is normal speed).
+    Rate(f64),
+}
+
+/// In and out points of a video block in seconds.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrimRange {
+    pub start: f64,
+    /// None plays to the end of the video.
+    pub end: Option<f64>,
+}
+
+impl TrimRange {
+    /// Moves `position` into the range.
+    pub fn clamp(&self, position: f64) -> f64 {
+        let position = position.max(self.start);
+        self.end.map_or(position, |end| position.min(end))
+    }
 }
 
 /// A single decoded RGBA frame plus a monotonic sequence number.
@@ -30,16 +89,182 @@ pub enum StreamCmd {
 pub struct DecodedVideoFrame {
     pub image: ColorImage,
     pub seq: u64,
+    /// Position of the frame in seconds from the start of the video.
+    pub position: f64,
+    /// Index of the frame within an animated image; 0 for videos.
+    pub index: usize,
+    /// Number of seeks handled before this frame was decoded.
+    pub seeks: u64,
+    /// When the decoder will publish the following frame, if known.
+    pub next_due: Option<Instant>,
+}
+
+/// Properties of a video file, probed when its first frame is loaded and cached in the
+/// session so they are known before the file is opened again.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VideoMetadata {
+    /// Length in seconds, if the container reports it.
+    pub duration: Option<f64>,
+    /// Short ffmpeg codec name, e.g. `vp9`.
+    pub codec: String,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: Option<f64>,
+    /// Overall bitrate in bits per second.
+    pub bit_rate: Option<u64>,
+    pub has_audio: bool,
+}
+
+impl VideoMetadata {
+    /// One-line summary for the file-name label, e.g. `0:12 · 1920×1080 · vp9`.
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(duration) = self.duration {
+            parts.push(format_timestamp(duration));
+        }
+        parts.push(format!("{}×{}", self.width, self.height));
+        parts.push(self.codec.clone());
+        parts.join(" · ")
+    }
+
+    /// Multi-line description for the hover tooltip.
+    pub fn details(&self) -> String {
+        let mut lines = vec![
+            format!(
+                "Duration: {}",
+                self.duration
+                    .map_or("unknown".to_string(), format_timestamp)
+            ),
+            format!("Resolution: {}×{}", self.width, self.height),
+            format!("Codec: {}", self.codec),
+        ];
+        if let Some(fps) = self.frame_rate {
+            lines.push(format!("Frame rate: {fps:.2} fps"));
+        }
+        if let Some(bit_rate) = self.bit_rate {
+            lines.push(format!("Bitrate: {:.0} kb/s", bit_rate as f64 / 1000.0));
+        }
+        lines.push(format!(
+            "Audio: {}",
+            if self.has_audio { "yes" } else { "no" }
+        ));
+        lines.join("\n")
+    }
+}
+
+/// Timing and size of a stream, probed by its worker once the file is open.
+struct StreamInfo {
+    frame_duration: Duration,
+    duration: Option<f64>,
+    /// Width × height of the decoded (unscaled) frames.
+    source_pixels: usize,
 }
 
 /// Stored on `AnimationState`. All fields are Send.
+// Dropping the handle closes the stream on its worker.
 pub struct VideoBlockHandle {
-    pub cmd_tx: std::sync::mpsc::Sender<StreamCmd>,
+    id: u64,
+    worker_tx: Sender<WorkerMsg>,
     pub latest_frame: Arc<Mutex<Option<DecodedVideoFrame>>>,
-    /// Nominal frame duration, used for repaint scheduling.
-    pub frame_duration: Duration,
+    info: Arc<OnceLock<StreamInfo>>,
     /// First frame image, restored to texture when playback stops.
     pub first_frame: ColorImage,
+    /// Number of seeks sent, compared against `DecodedVideoFrame::seeks`.
+    seeks_sent: u64,
+    /// Target of the latest seek sent.
+    last_seek: Option<f64>,
+    /// Volume of the sound last requested, None while silent.
+    audio: Option<f32>,
+    /// Playback rate last requested.
+    rate: f64,
+}
+
+impl VideoBlockHandle {
+    pub fn send(&self, cmd: StreamCmd) {
+        self.worker_tx.send(WorkerMsg::Cmd(self.id, cmd)).ok();
+    }
+
+    /// Asks the decoder to show the frame at `seconds`.
+    pub fn seek(&mut self, seconds: f64) {
+        self.seeks_sent += 1;
+        self.last_seek = Some(seconds);
+        self.send(StreamCmd::Seek(seconds));
+    }
+
+    /// Plays the sound at `volume`, or silences it for None. Only changes are sent.
+    pub fn set_audio(&mut self, volume: Option<f32>) {
+        if self.audio != volume {
+            self.audio = volume;
+            self.send(StreamCmd::Audio(volume));
+        }
+    }
+
+    /// Plays at `rate` times normal speed. Only changes are sent.
+    pub fn set_rate(&mut self, rate: f64) {
+        if self.rate != rate {
+            self.rate = rate;
+            self.send(StreamCmd::Rate(rate));
+        }
+    }
+
+    /// Returns true while the stream was asked to play its sound.
+    pub fn is_audible(&self) -> bool {
+        self.audio.is_some()
+    }
+
+    /// Nominal frame duration, used for repaint scheduling when no deadline is known.
+    pub fn frame_duration(&self) -> Duration {
+        self.info
+            .get()
+            .map_or(DEFAULT_FRAME_DURATION, |i| i.frame_duration)
+    }
+
+    /// Length of the video in seconds, once probed and if the container reports it.
+    pub fn duration(&self) -> Option<f64> {
+        self.info.get().and_then(|i| i.duration)
+    }
+
+    /// Approximate memory held for the stream: its output frames, and once the file is
+    /// open, the decoder's frames at the source resolution.
+    pub fn memory_bytes(&self) -> usize {
+        let output = self.first_frame.pixels.len() * 4 * OUTPUT_FRAMES_HELD;
+        let source = self
+            .info
+            .get()
+            .map_or(0, |i| i.source_pixels * 3 / 2 * DECODER_FRAMES_HELD);
+        output + source
+    }
+
+    /// Target of the latest seek sent, if any.
+    pub fn last_seek(&self) -> Option<f64> {
+        self.last_seek
+    }
+
+    /// Returns true while a requested seek has not produced its frame yet.
+    pub fn is_seeking(&self) -> bool {
+        self.latest_frame
+            .lock()
+            .map(|guard| guard.as_ref().map_or(0, |f| f.seeks) < self.seeks_sent)
+            .unwrap_or(false)
+    }
+
+    /// Returns when the frame after the latest one is due, based on its timestamp.
+    pub fn next_frame_due(&self) -> Option<Instant> {
+        let guard = self.latest_frame.lock().ok()?;
+        guard.as_ref().and_then(|f| f.next_due)
+    }
+
+    /// Position in seconds of the latest decoded frame, if any.
+    pub fn position(&self) -> Option<f64> {
+        let guard = self.latest_frame.lock().ok()?;
+        guard.as_ref().map(|f| f.position)
+    }
+}
+
+impl Drop for VideoBlockHandle {
+    fn drop(&mut self) {
+        self.worker_tx.send(WorkerMsg::Close(self.id)).ok();
+    }
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -54,43 +279,144 @@ pub fn is_video_format(path: &Path) -> bool {
     )
 }
 
-/// Spawns a background decoder thread and returns a handle to it.
-/// The decoder starts in the Paused state; call `cmd_tx.send(StreamCmd::Play)` to begin.
+/// Opens a stream of a video or animated image on the least busy decode worker and
+/// returns a handle to it. The stream starts in the Paused state; call
+/// `send(StreamCmd::Play)` to begin.
+/// Returns immediately: the file is opened and probed on the worker.
 pub fn spawn_video_decoder(path: PathBuf, first_frame: ColorImage) -> VideoBlockHandle {
-    let (cmd_tx, cmd_rx) = std::sync::mpsc::channel::<StreamCmd>();
-    let latest_frame: Arc<Mutex<Option<DecodedVideoFrame>>> = Arc::new(Mutex::new(None));
-    let latest_frame_bg = Arc::clone(&latest_frame);
+    let pool = DecodePool::global();
+    let id = pool.next_id.fetch_add(1, Ordering::Relaxed);
+    let worker = pool
+        .workers
+        .iter()
+        .min_by_key(|w| w.streams.load(Ordering::Relaxed))
+        .expect("decode pool has workers");
+    worker.streams.fetch_add(1, Ordering::Relaxed);
 
-    let frame_duration = probe_frame_duration(&path).unwrap_or(Duration::from_millis(42));
-    let first_frame_bg = first_frame.clone();
-
-    std::thread::spawn(move || {
-        decoder_thread(path, frame_duration, cmd_rx, latest_frame_bg, first_frame_bg);
-    });
+    let latest_frame = Arc::new(Mutex::new(None));
+    let info = Arc::new(OnceLock::new());
+    worker
+        .tx
+        .send(WorkerMsg::Open {
+            id,
+            path,
+            latest_frame: Arc::clone(&latest_frame),
+            info: Arc::clone(&info),
+        })
+        .ok();
 
     VideoBlockHandle {
-        cmd_tx,
+        id,
+        worker_tx: worker.tx.clone(),
         latest_frame,
-        frame_duration,
+        info,
         first_frame,
+        seeks_sent: 0,
+        last_seek: None,
+        audio: None,
+        rate: 1.0,
     }
 }
 
+/// Formats a position in seconds as `m:ss`, or `h:mm:ss` from one hour on.
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds.max(0.0) as u64;
+    let (h, m, s) = (total / 3600, total / 60 % 60, total % 60);
+    if h > 0 {
+        format!("{h}:{m:02}:{s:02}")
+    } else {
+        format!("{m}:{s:02}")
+    }
+}
+
+/// Decodes the frame at `seconds` (e.g. a trim in-point) at display resolution, for use
+/// as a block's static image.
+pub fn load_video_frame_at(path: &Path, seconds: f64) -> Result<ColorImage, String> {
+    let mut decoder = StreamDecoder::open(path)?;
+    decoder
+        .seek(seconds)
+        .map(|(image, _)| image)
+        .ok_or_else(|| format!("No frame at {seconds:.3}s in {:?}", path))
+}
+
+/// Decodes the frames of `start..end` (seconds) at display resolution, keeping at most
+/// `max_fps` frames per second. Each frame lasts until the next one kept; the last one
+/// for a nominal frame duration, cut at `end`.
+pub fn decode_video_segment(
+    path: &Path,
+    start: f64,
+    end: f64,
+    max_fps: f64,
+) -> Result<Vec<crate::image_loader::AnimationFrame>, String> {
+    let mut decoder = StreamDecoder::open(path)?;
+    let (first, first_position) = decoder
+        .seek(start)
+        .ok_or_else(|| format!("No frame at {start:.3}s in {:?}", path))?;
+    let min_gap = 1.0 / max_fps;
+    let mut kept = vec![(first, first_position)];
+    while let Some(position) = decoder.decode_next().filter(|&p| p < end) {
+        let previous = kept.last().map_or(f64::NEG_INFINITY, |(_, p)| *p);
+        if position - previous < min_gap {
+            continue;
+        }
+        // Only kept frames need scaling.
+        if let Some(image) = decoder.current_image() {
+            kept.push((image, position));
+        }
+    }
+
+    let last_duration = decoder.frame_duration.as_secs_f64().max(min_gap);
+    let ends: Vec<f64> = kept
+        .iter()
+        .skip(1)
+        .map(|(_, p)| *p)
+        .chain(std::iter::once(f64::INFINITY))
+        .collect();
+    Ok(kept
+        .into_iter()
+        .zip(ends)
+        .map(|((image, position), next)| {
+            let until = if next.is_finite() {
+                next
+            } else {
+                (position + last_duration).min(end)
+            };
+            let seconds = until - position;
+            crate::image_loader::AnimationFrame {
+                image,
+                duration: Duration::from_secs_f64(if seconds > 0.0 {
+                    seconds
+                } else {
+                    last_duration
+                }),
+            }
+        })
+        .collect())
+}
+
 /// Decodes only the first frame of a video file for static display.
-/// Returns a single-frame `LoadedImage` with `has_animation = true`.
+/// Returns a single-frame `LoadedImage` with `has_animation = true` and the probed
+/// `VideoMetadata`.
 pub fn load_video_first_frame(path: &Path) -> Result<crate::image_loader::LoadedImage, String> {
     use ffmpeg_next as ff;
 
     let mut input = ff::format::input(path)
         .map_err(|e| format!("Failed to open {:?}: {e}", path))?;
 
-    let (stream_index, frame_duration, codec_params) = {
+    let (stream_index, frame_duration, codec_params, frame_rate, duration) = {
         let stream = input
             .streams()
             .best(ff::media::Type::Video)
             .ok_or_else(|| format!("No video stream in {:?}", path))?;
-        (stream.index(), frame_duration_from_stream(&stream), stream.parameters())
+        (
+            stream.index(),
+            frame_duration_from_stream(&stream),
+            stream.parameters(),
+            frame_rate_from_stream(&stream),
+            duration_secs(&input, stream.duration(), f64::from(stream.time_base())),
+        )
     };
+    let has_audio = input.streams().best(ff::media::Type::Audio).is_some();
 
     let mut decoder = ff::codec::context::Context::from_parameters(codec_params)
         .map_err(|e| format!("Codec context: {e}"))?
@@ -99,6 +425,15 @@ pub fn load_video_first_frame(path: &Path) -> Result<crate::image_loader::Loaded
         .map_err(|e| format!("Video decoder: {e}"))?;
 
     let (src_w, src_h) = (decoder.width(), decoder.height());
+    let metadata = VideoMetadata {
+        duration,
+        codec: decoder.id().name().to_string(),
+        width: src_w,
+        height: src_h,
+        frame_rate,
+        bit_rate: u64::try_from(input.bit_rate()).ok().filter(|&b| b > 0),
+        has_audio,
+    };
     let src_fmt = decoder.format();
     let (out_w, out_h) = scaled_output_dims(src_w, src_h);
 
@@ -129,13 +464,15 @@ pub fn load_video_first_frame(path: &Path) -> Result<crate::image_loader::Loaded
                 .map_err(|e| format!("Scale first frame: {e}"))?;
 
             let ci = rgba_frame_to_color_image(&rgba);
-            return Ok(crate::image_loader::LoadedImage::from_frames(
+            let mut loaded = crate::image_loader::LoadedImage::from_frames(
                 vec![crate::image_loader::AnimationFrame {
                     image: ci,
                     duration: frame_duration,
                 }],
                 true,
-            ));
+            );
+            loaded.video_metadata = Some(metadata);
+            return Ok(loaded);
         }
     }
 
@@ -143,130 +480,1043 @@ pub fn load_video_first_frame(path: &Path) -> Result<crate::image_loader::Loaded
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
-// Background decoder thread (!Send types live here exclusively)
+// Decode workers (!Send types live here exclusively)
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn decoder_thread(
-    path: PathBuf,
+/// An open video stream that decodes frames in order and can seek to any timestamp.
+struct StreamDecoder {
+    input: ffmpeg_next::format::context::Input,
+    decoder: ffmpeg_next::decoder::Video,
+    scaler: ffmpeg_next::software::scaling::Context,
+    stream_index: usize,
+    /// Seconds per stream timestamp unit.
+    time_base: f64,
+    /// Stream timestamp of the first frame; positions are reported relative to it.
+    start_ts: i64,
+    /// Set once EOF has been sent to the decoder; cleared by a seek.
+    draining: bool,
+    /// Nominal frame duration; the last frame before a loop is shown this long.
     frame_duration: Duration,
-    cmd_rx: std::sync::mpsc::Receiver<StreamCmd>,
-    latest_frame: Arc<Mutex<Option<DecodedVideoFrame>>>,
-    _first_frame: ColorImage,
-) {
-    use std::sync::mpsc::TryRecvError;
-    use std::time::Instant;
-
-    let mut playing = false;
-    let mut seq: u64 = 0;
-
-    'outer: loop {
-        // When paused, block until a command arrives rather than spinning.
-        if !playing {
-            match cmd_rx.recv() {
-                Ok(StreamCmd::Play) => playing = true,
-                Ok(StreamCmd::Pause) => {}
-                Err(_) => break 'outer, // Sender dropped → block deleted/evicted
-            }
-        }
+    /// Length of the video in seconds, if the container reports it.
+    duration: Option<f64>,
+    // Reused across frames to avoid per-frame allocations.
+    raw: ffmpeg_next::frame::Video,
+    rgba: ffmpeg_next::frame::Video,
+    /// The audio track, while the stream plays sound.
+    audio: Option<AudioTrack>,
+    /// Video packets read ahead of the video decoder while decoding audio.
+    video_packets: VecDeque<ffmpeg_next::Packet>,
+    /// Set once reading reached the end of the input; cleared by a seek.
+    input_eof: bool,
+    /// Number of seeks, so that audio decoded before and after a loop can be told apart.
+    generation: u64,
+}
 
-        // (Re-)open the file on every loop iteration (also handles loop restart after EOF).
-        let mut input = match ffmpeg_next::format::input(&path) {
-            Ok(i) => i,
-            Err(e) => {
-                log::error!("video_stream: failed to open {:?}: {e}", path);
-                break 'outer;
-            }
-        };
+impl StreamDecoder {
+    fn open(path: &Path) -> Result<Self, String> {
+        let input = ffmpeg_next::format::input(path)
+            .map_err(|e| format!("failed to open {:?}: {e}", path))?;
 
-        let (stream_index, codec_params) = match input.streams().best(ffmpeg_next::media::Type::Video) {
-            Some(s) => (s.index(), s.parameters()),
-            None => {
-                log::error!("video_stream: no video stream in {:?}", path);
-                break 'outer;
-            }
+        let (stream_index, codec_params, time_base, start_ts, frame_duration, stream_duration) = {
+            let stream = input
+                .streams()
+                .best(ffmpeg_next::media::Type::Video)
+                .ok_or_else(|| format!("no video stream in {:?}", path))?;
+            let start = stream.start_time();
+            (
+                stream.index(),
+                stream.parameters(),
+                f64::from(stream.time_base()),
+                if start == ffmpeg_next::ffi::AV_NOPTS_VALUE {
+                    0
+                } else {
+                    start
+                },
+                frame_duration_from_stream(&stream),
+                stream.duration(),
+            )
         };
+        let duration = duration_secs(&input, stream_duration, time_base);
 
-        let mut decoder =
-            match ffmpeg_next::codec::context::Context::from_parameters(codec_params)
-                .and_then(|ctx| ctx.decoder().video())
-            {
-                Ok(d) => d,
-                Err(e) => {
-                    log::error!("video_stream: decoder init failed: {e}");
-                    break 'outer;
-                }
-            };
+        let decoder = ffmpeg_next::codec::context::Context::from_parameters(codec_params)
+            .and_then(|ctx| ctx.decoder().video())
+            .map_err(|e| format!("decoder init failed: {e}"))?;
 
         let (src_w, src_h) = (decoder.width(), decoder.height());
-        let src_fmt = decoder.format();
         let (out_w, out_h) = scaled_output_dims(src_w, src_h);
-
-        let mut scaler = match ffmpeg_next::software::scaling::context::Context::get(
-            src_fmt,
+        let scaler = ffmpeg_next::software::scaling::context::Context::get(
+            decoder.format(),
             src_w,
             src_h,
             ffmpeg_next::format::pixel::Pixel::RGBA,
             out_w,
             out_h,
             ffmpeg_next::software::scaling::flag::Flags::BILINEAR,
-        ) {
-            Ok(s) => s,
-            Err(e) => {
-                log::error!("video_stream: scaler init failed: {e}");
-                break 'outer;
+        )
+        .map_err(|e| format!("scaler init failed: {e}"))?;
+
+        Ok(Self {
+            input,
+            decoder,
+            scaler,
+            stream_index,
+            time_base,
+            start_ts,
+            draining: false,
+            frame_duration,
+            duration,
+            raw: ffmpeg_next::frame::Video::empty(),
+            rgba: ffmpeg_next::frame::Video::empty(),
+            audio: None,
+            video_packets: VecDeque::new(),
+            input_eof: false,
+            generation: 0,
+        })
+    }
+
+    /// Starts decoding the audio track, resampled to `rate`, from the current read
+    /// position on. Returns false if the video has no (decodable) audio.
+    fn enable_audio(&mut self, rate: u32) -> bool {
+        if self.audio.is_none() {
+            let origin = self.start_ts as f64 * self.time_base;
+            match AudioTrack::open(&self.input, origin, rate) {
+                Ok(track) => self.audio = track,
+                Err(e) => log::error!("video_stream: {e}"),
             }
-        };
+        }
+        self.audio.is_some()
+    }
 
-        // Reuse allocations across frames.
-        let mut raw = ffmpeg_next::frame::Video::empty();
-        let mut rgba = ffmpeg_next::frame::Video::empty();
-        // Track when the last frame was emitted so we can sleep the remainder
-        // of the frame interval and avoid running at CPU decode speed.
-        let mut next_frame_at = Instant::now();
-
-        for (stream, packet) in input.packets() {
-            // Check for commands between packets (non-blocking).
-            match cmd_rx.try_recv() {
-                Ok(StreamCmd::Pause) => {
-                    playing = false;
-                    continue 'outer; // return to the blocking recv at top
+    /// Returns true if more audio can be decoded by reading ahead of the video.
+    fn can_read_audio(&self) -> bool {
+        self.audio.as_ref().is_some_and(|a| a.decoded_to < a.until)
+            && !self.input_eof
+            && self.video_packets.len() < AUDIO_READ_AHEAD_PACKETS
+    }
+
+    /// Reads ahead until `seconds` of audio wait to be taken (or no more can be read),
+    /// queueing the video packets read on the way.
+    fn read_ahead_audio(&mut self, seconds: f64) {
+        while self.can_read_audio() && self.audio.as_ref().is_some_and(|a| a.buffered() < seconds) {
+            match self.read_packet() {
+                Ok(Some(packet)) => {
+                    if packet.stream() == self.stream_index {
+                        self.video_packets.push_back(packet);
+                    }
+                }
+                Ok(None) => self.input_eof = true,
+                Err(e) => {
+                    log::error!("video_stream: read failed: {e}");
+                    self.input_eof = true;
                 }
-                Err(TryRecvError::Disconnected) => break 'outer,
-                Ok(StreamCmd::Play) | Err(TryRecvError::Empty) => {}
             }
+        }
+    }
+
+    /// Returns the next packet of the video stream, read ahead earlier or from the input.
+    /// Audio packets read on the way are decoded. None at the end of the input.
+    fn next_video_packet(&mut self) -> Result<Option<ffmpeg_next::Packet>, ffmpeg_next::Error> {
+        if let Some(packet) = self.video_packets.pop_front() {
+            return Ok(Some(packet));
+        }
+        while !self.input_eof {
+            match self.read_packet()? {
+                Some(packet) if packet.stream() == self.stream_index => return Ok(Some(packet)),
+                Some(_) => {}
+                None => self.input_eof = true,
+            }
+        }
+        Ok(None)
+    }
 
-            if stream.index() != stream_index {
-                continue;
+    /// Reads the next packet of any stream, decoding it right away if it belongs to the
+    /// audio track. None at the end of the input.
+    fn read_packet(&mut self) -> Result<Option<ffmpeg_next::Packet>, ffmpeg_next::Error> {
+        let mut packet = ffmpeg_next::Packet::empty();
+        match packet.read(&mut self.input) {
+            Ok(()) => {
+                let generation = self.generation;
+                if let Some(audio) = self
+                    .audio
+                    .as_mut()
+                    .filter(|a| a.stream_index == packet.stream())
+                {
+                    audio.decode(&packet, generation);
+                }
+                Ok(Some(packet))
+            }
+            Err(ffmpeg_next::Error::Eof) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Decodes the next frame into `raw` and returns its position in seconds.
+    /// Returns None at the end of the stream.
+    fn decode_next(&mut self) -> Option<f64> {
+        loop {
+            if self.decoder.receive_frame(&mut self.raw).is_ok() {
+                let ts = self.raw.timestamp().unwrap_or(self.start_ts);
+                return Some(((ts - self.start_ts) as f64 * self.time_base).max(0.0));
+            }
+            if self.draining {
+                return None;
+            }
+
+            match self.next_video_packet() {
+                Ok(Some(packet)) => {
+                    // Corrupt packets are skipped, as during normal playback.
+                    let _ = self.decoder.send_packet(&packet);
+                }
+                Ok(None) => {
+                    // Flush the frames still buffered in the decoder.
+                    let _ = self.decoder.send_eof();
+                    self.draining = true;
+                }
+                Err(e) => {
+                    log::error!("video_stream: read failed: {e}");
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Converts the last decoded frame to a ColorImage at display resolution.
+    fn current_image(&mut self) -> Option<ColorImage> {
+        self.scaler.run(&self.raw, &mut self.rgba).ok()?;
+        Some(rgba_frame_to_color_image(&self.rgba))
+    }
+
+    /// Decodes and converts the next frame, returning it with its position in seconds.
+    /// At the end of the stream (or of `trim`) it seeks back to the start of the video
+    /// (or of `trim`) and continues from there (seamless loop); the input, decoder and
+    /// scaler stay open. The flag is true for the first frame after such a rewind.
+    fn next_frame_looping(&mut self, trim: Option<TrimRange>) -> Option<(ColorImage, f64, bool)> {
+        let end = trim.and_then(|t| t.end).unwrap_or(f64::INFINITY);
+        if let Some(audio) = self.audio.as_mut() {
+            audio.until = end;
+        }
+        if let Some(position) = self.decode_next().filter(|&p| p < end) {
+            return Some((self.current_image()?, position, false));
+        }
+        let (image, position) = self.seek(trim.map_or(0.0, |t| t.start))?;
+        Some((image, position, true))
+    }
+
+    /// Jumps to the keyframe at or before `seconds` without decoding anything.
+    fn seek_keyframe(&mut self, seconds: f64) -> Result<(), String> {
+        // Input::seek takes AV_TIME_BASE (microsecond) timestamps, which include the
+        // stream's start offset.
+        let start = self.start_ts as f64 * self.time_base;
+        let ts = ((seconds.max(0.0) + start) * 1_000_000.0) as i64;
+        self.input
+            .seek(ts, ..ts)
+            .map_err(|e| format!("seek to {seconds:.3}s failed: {e}"))?;
+        // Drop frames buffered from before the seek, or they would be shown next.
+        self.decoder.flush();
+        self.draining = false;
+        self.video_packets.clear();
+        self.input_eof = false;
+        self.generation += 1;
+        if let Some(audio) = self.audio.as_mut() {
+            audio.restart(seconds.max(0.0));
+        }
+        Ok(())
+    }
+
+    /// Seeks to the first frame at or after `seconds`: jumps to the preceding keyframe
+    /// and decodes forward. Past the end of the stream the last frame is returned.
+    fn seek(&mut self, seconds: f64) -> Option<(ColorImage, f64)> {
+        if let Err(e) = self.seek_keyframe(seconds) {
+            log::error!("video_stream: {e}");
+            return None;
+        }
+        let mut last = None;
+        while let Some(position) = self.decode_next() {
+            last = Some(position);
+            if position >= seconds {
+                break;
+            }
+        }
+        // Only the frame that is shown needs scaling.
+        let position = last?;
+        Some((self.current_image()?, position))
+    }
+}
+
+/// An animated GIF, WebP or AVIF image that decodes frames in order and starts over
+/// after the last one. Positions add up the durations of the frames before.
+struct AnimationDecoder {
+    stream: AnimationStream,
+    /// The first frame, decoded on open for its timing and not returned yet.
+    pending: Option<AnimationFrame>,
+    /// Display time of the first frame, reported as the stream's frame duration.
+    frame_duration: Duration,
+    /// Frames returned since the start of the current pass.
+    frames_decoded: usize,
+    /// Position in seconds of the next frame of the current pass.
+    position: f64,
+    /// Display time of the last frame returned.
+    last_duration: Duration,
+    /// Display time of the last frame of the previous pass, shown before the loop.
+    tail: Duration,
+    /// Copy of the first frame of the current pass while it is the only one returned.
+    first: Option<ColorImage>,
+    /// The frame of an animation that turned out to have only one, shown again on each
+    /// loop instead of being decoded again.
+    still: Option<ColorImage>,
+}
+
+impl AnimationDecoder {
+    fn open(path: &Path) -> Result<Self, String> {
+        let max_dimension = crate::constants::MAX_BLOCK_DIMENSION as u32;
+        let mut stream = AnimationStream::open(path, Some(max_dimension))?;
+        let first = stream
+            .next_frame()?
+            .ok_or_else(|| format!("{} has no frames", path.display()))?;
+        Ok(Self {
+            stream,
+            frame_duration: first.duration,
+            pending: Some(first),
+            frames_decoded: 0,
+            position: 0.0,
+            last_duration: Duration::ZERO,
+            tail: Duration::ZERO,
+            first: None,
+            still: None,
+        })
+    }
+
+    /// Decodes the next frame of the current pass and returns it with its position in
+    /// seconds. Returns None after the last frame, or if the frame cannot be decoded.
+    fn decode_next(&mut self) -> Option<(ColorImage, f64)> {
+        let frame = match self.pending.take() {
+            Some(frame) => frame,
+            None => match self.stream.next_frame() {
+                Ok(frame) => frame?,
+                Err(e) => {
+                    log::error!("video_stream: {e}");
+                    return None;
+                }
+            },
+        };
+        let position = self.position;
+        self.position += frame.duration.as_secs_f64();
+        self.last_duration = frame.duration;
+        self.frames_decoded += 1;
+        self.first = (self.frames_decoded == 1).then(|| frame.image.clone());
+        Some((frame.image, position))
+    }
+
+    /// Starts a new pass at the first frame.
+    fn rewind(&mut self) -> Option<()> {
+        if let Err(e) = self.stream.rewind() {
+            log::error!("video_stream: {e}");
+            return None;
+        }
+        self.pending = None;
+        self.frames_decoded = 0;
+        self.position = 0.0;
+        Some(())
+    }
+
+    /// Like `StreamDecoder::next_frame_looping`, over the whole animation.
+    fn next_frame_looping(&mut self) -> Option<(ColorImage, f64, bool)> {
+        if self.still.is_none() {
+            if let Some((image, position)) = self.decode_next() {
+                return Some((image, position, false));
+            }
+            self.tail = self.last_duration;
+            if self.frames_decoded == 1 {
+                self.still = self.first.take();
+            }
+        }
+        if let Some(image) = &self.still {
+            return Some((image.clone(), 0.0, true));
+        }
+        self.rewind()?;
+        let (image, position) = self.decode_next()?;
+        Some((image, position, true))
+    }
+
+    /// Decodes from the start up to the frame shown at `seconds`. Past the end of the
+    /// animation the last frame is returned.
+    fn seek(&mut self, seconds: f64) -> Option<(ColorImage, f64)> {
+        if let Some(image) = &self.still {
+            return Some((image.clone(), 0.0));
+        }
+        self.rewind()?;
+        let mut shown = self.decode_next()?;
+        // Only whole frames are decoded, so skipping ahead costs what playing does.
+        while self.position <= seconds {
+            match self.decode_next() {
+                Some(frame) => shown = frame,
+                None => break,
             }
-            if decoder.send_packet(&packet).is_err() {
+        }
+        Some(shown)
+    }
+}
+
+/// What a playback stream decodes: a video, or an animated image.
+enum FrameSource {
+    Video(StreamDecoder),
+    Animation(AnimationDecoder),
+}
+
+impl FrameSource {
+    fn open(path: &Path) -> Result<Self, String> {
+        if is_video_format(path) {
+            StreamDecoder::open(path).map(Self::Video)
+        } else {
+            AnimationDecoder::open(path).map(Self::Animation)
+        }
+    }
+
+    fn info(&self) -> StreamInfo {
+        match self {
+            Self::Video(decoder) => StreamInfo {
+                frame_duration: decoder.frame_duration,
+                duration: decoder.duration,
+                source_pixels: decoder.decoder.width() as usize * decoder.decoder.height() as usize,
+            },
+            Self::Animation(decoder) => StreamInfo {
+                frame_duration: decoder.frame_duration,
+                // Only known once the whole animation was decoded.
+                duration: None,
+                source_pixels: decoder.stream.source_pixels(),
+            },
+        }
+    }
+
+    /// The video decoder; only videos have sound.
+    fn video(&mut self) -> Option<&mut StreamDecoder> {
+        match self {
+            Self::Video(decoder) => Some(decoder),
+            Self::Animation(_) => None,
+        }
+    }
+
+    fn can_read_audio(&self) -> bool {
+        matches!(self, Self::Video(decoder) if decoder.can_read_audio())
+    }
+
+    /// See `StreamDecoder::next_frame_looping`. Animations are not trimmed.
+    fn next_frame_looping(&mut self, trim: Option<TrimRange>) -> Option<(ColorImage, f64, bool)> {
+        match self {
+            Self::Video(decoder) => decoder.next_frame_looping(trim),
+            Self::Animation(decoder) => decoder.next_frame_looping(),
+        }
+    }
+
+    fn seek(&mut self, seconds: f64) -> Option<(ColorImage, f64)> {
+        match self {
+            Self::Video(decoder) => decoder.seek(seconds),
+            Self::Animation(decoder) => decoder.seek(seconds),
+        }
+    }
+
+    /// How long the last frame before a loop is shown: the nominal frame duration of a
+    /// video, or the last frame's own duration in an animation.
+    fn loop_gap(&self) -> Duration {
+        match self {
+            Self::Video(decoder) => decoder.frame_duration,
+            Self::Animation(decoder) => decoder.tail,
+        }
+    }
+
+    /// Index of the last decoded frame within an animation; 0 for videos.
+    fn frame_index(&self) -> usize {
+        match self {
+            Self::Video(_) => 0,
+            Self::Animation(decoder) => decoder.frames_decoded.saturating_sub(1),
+        }
+    }
+}
+
+/// Decoded audio starting at `position` seconds.
+struct AudioChunk {
+    position: f64,
+    /// `StreamDecoder::generation` when the chunk was decoded.
+    generation: u64,
+    /// Interleaved stereo samples at the output rate.
+    samples: Vec<f32>,
+}
+
+/// The audio track of a video, decoded and resampled to interleaved stereo f32 at the
+/// output rate.
+struct AudioTrack {
+    decoder: ffmpeg_next::decoder::Audio,
+    /// Created from the first decoded frame, and again after each seek.
+    resampler: Option<ffmpeg_next::software::resampling::Context>,
+    stream_index: usize,
+    /// Seconds per audio stream timestamp unit.
+    time_base: f64,
+    /// Start of the video stream in seconds; positions are relative to it, like frames'.
+    origin: f64,
+    rate: u32,
+    raw: ffmpeg_next::frame::Audio,
+    /// Decoded audio not yet handed to the output.
+    chunks: VecDeque<AudioChunk>,
+    /// Audio before this position (a seek target) is dropped.
+    from: f64,
+    /// Audio from this position on (the trim out point) is dropped.
+    until: f64,
+    /// Position just past the latest decoded sample.
+    decoded_to: f64,
+}
+
+impl AudioTrack {
+    /// Opens the best audio stream of `input`, or returns None if there is none.
+    fn open(
+        input: &ffmpeg_next::format::context::Input,
+        origin: f64,
+        rate: u32,
+    ) -> Result<Option<Self>, String> {
+        let Some(stream) = input.streams().best(ffmpeg_next::media::Type::Audio) else {
+            return Ok(None);
+        };
+        let decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+            .and_then(|ctx| ctx.decoder().audio())
+            .map_err(|e| format!("audio decoder init failed: {e}"))?;
+        Ok(Some(Self {
+            decoder,
+            resampler: None,
+            stream_index: stream.index(),
+            time_base: f64::from(stream.time_base()),
+            origin,
+            rate,
+            raw: ffmpeg_next::frame::Audio::empty(),
+            chunks: VecDeque::new(),
+            from: 0.0,
+            until: f64::INFINITY,
+            decoded_to: 0.0,
+        }))
+    }
+
+    /// Seconds of decoded audio waiting to be taken.
+    fn buffered(&self) -> f64 {
+        let samples: usize = self.chunks.iter().map(|c| c.samples.len()).sum();
+        (samples / CHANNELS) as f64 / f64::from(self.rate)
+    }
+
+    /// Discards the decoder state after the input was seeked; audio before `from` is
+    /// dropped once decoding resumes.
+    fn restart(&mut self, from: f64) {
+        self.decoder.flush();
+        self.resampler = None;
+        self.from = from;
+        self.decoded_to = from;
+    }
+
+    /// Decodes an audio packet, keeping the samples between `from` and `until`.
+    fn decode(&mut self, packet: &ffmpeg_next::Packet, generation: u64) {
+        // Corrupt packets are skipped, like video ones.
+        if self.decoder.send_packet(packet).is_err() {
+            return;
+        }
+        while self.decoder.receive_frame(&mut self.raw).is_ok() {
+            let position = self.raw.timestamp().map_or(self.decoded_to, |ts| {
+                ts as f64 * self.time_base - self.origin
+            });
+            let Some(samples) = self.resample() else {
                 continue;
+            };
+            let rate = f64::from(self.rate);
+            let frames = samples.len() / CHANNELS;
+            self.decoded_to = position + frames as f64 / rate;
+
+            let first = ((self.from - position) * rate)
+                .ceil()
+                .clamp(0.0, frames as f64) as usize;
+            let last = ((self.until - position) * rate)
+                .floor()
+                .clamp(0.0, frames as f64) as usize;
+            if first < last {
+                self.chunks.push_back(AudioChunk {
+                    position: position + first as f64 / rate,
+                    generation,
+                    samples: samples[first * CHANNELS..last * CHANNELS].to_vec(),
+                });
+            }
+        }
+    }
+
+    /// Converts the last decoded frame to interleaved stereo f32 at the output rate.
+    fn resample(&mut self) -> Option<Vec<f32>> {
+        use ffmpeg_next::format::{sample::Type, Sample};
+        use ffmpeg_next::ChannelLayout;
+
+        if self.resampler.is_none() {
+            let layout = match self.raw.channel_layout() {
+                layout if layout.is_empty() => {
+                    ChannelLayout::default(i32::from(self.raw.channels()))
+                }
+                layout => layout,
+            };
+            let resampler = ffmpeg_next::software::resampling::Context::get(
+                self.raw.format(),
+                layout,
+                self.raw.rate(),
+                Sample::F32(Type::Packed),
+                ChannelLayout::STEREO,
+                self.rate,
+            );
+            match resampler {
+                Ok(resampler) => self.resampler = Some(resampler),
+                Err(e) => {
+                    log::error!("video_stream: audio resampler init failed: {e}");
+                    return None;
+                }
+            }
+        }
+        let resampler = self.resampler.as_mut()?;
+        // Room for the converted frame plus what the resampler held back before.
+        let capacity =
+            self.raw.samples() * self.rate as usize / self.raw.rate().max(1) as usize + 256;
+        let mut out = ffmpeg_next::frame::Audio::new(
+            Sample::F32(Type::Packed),
+            capacity,
+            ChannelLayout::STEREO,
+        );
+        resampler.run(&self.raw, &mut out).ok()?;
+        let bytes = &out.data(0)[..out.samples() * CHANNELS * 4];
+        Some(
+            bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        )
+    }
+}
+
+/// Maps the playback timeline (seconds, growing across loops) to wall-clock deadlines.
+struct PlaybackClock {
+    /// A timeline position and the instant it is (or was) due.
+    anchor: Option<(f64, Instant)>,
+    /// Timeline seconds per wall-clock second.
+    rate: f64,
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self {
+            anchor: None,
+            rate: 1.0,
+        }
+    }
+}
+
+impl PlaybackClock {
+    /// Returns when the frame at `timeline` is due without re-anchoring.
+    fn deadline(&self, timeline: f64) -> Option<Instant> {
+        let (anchor_timeline, anchor_at) = self.anchor?;
+        let offset = (timeline - anchor_timeline) / self.rate;
+        (offset >= 0.0).then(|| anchor_at + Duration::from_secs_f64(offset))
+    }
+
+    /// Changes the playback rate; the next frame is rescheduled from the current time.
+    fn set_rate(&mut self, rate: f64) {
+        self.rate = rate.max(f64::EPSILON);
+        self.anchor = None;
+    }
+
+    /// Returns when the frame at `timeline` is due. Playback is rescheduled from `now`
+    /// when there is no anchor yet, time went backwards, or the frame is already too late
+    /// (e.g. after a stall), instead of rushing through the late frames.
+    fn due(&mut self, timeline: f64, now: Instant) -> Instant {
+        let max_lateness = Duration::from_millis(VIDEO_MAX_LATENESS_MILLIS);
+        match self.deadline(timeline) {
+            Some(due) if due + max_lateness >= now => due,
+            _ => {
+                self.anchor = Some((timeline, now));
+                now
             }
-            while decoder.receive_frame(&mut raw).is_ok() {
-                if scaler.run(&raw, &mut rgba).is_err() {
-                    continue;
+        }
+    }
+
+    /// Anchors the timeline to an external clock: `timeline` was due at `at`.
+    fn sync(&mut self, timeline: f64, at: Instant) {
+        self.anchor = Some((timeline, at));
+    }
+
+    fn reset(&mut self) {
+        self.anchor = None;
+    }
+}
+
+/// A decoded frame waiting for its deadline.
+struct QueuedFrame {
+    image: ColorImage,
+    /// Position within the video, as published.
+    position: f64,
+    /// Index within an animated image, as published.
+    index: usize,
+    /// Position on the playback timeline, which keeps growing when the video loops.
+    timeline: f64,
+}
+
+/// A stream being played by a decode worker.
+struct PlaybackStream {
+    decoder: FrameSource,
+    latest_frame: Arc<Mutex<Option<DecodedVideoFrame>>>,
+    playing: bool,
+    seq: u64,
+    /// Number of seeks received, published with each frame.
+    seeks: u64,
+    /// Latest seek target not performed yet; a burst of seeks (dragging the scrub
+    /// bar) collapses into the last one.
+    pending_seek: Option<f64>,
+    clock: PlaybackClock,
+    /// Frames are decoded one ahead of the next one to show, so each published frame
+    /// carries the deadline of the frame after it.
+    queue: VecDeque<QueuedFrame>,
+    /// Added to positions after the video looped, keeping the timeline increasing.
+    loop_offset: f64,
+    /// Loop offset of the previous pass, for audio decoded before the latest loop.
+    prev_loop_offset: f64,
+    last_timeline: f64,
+    trim: Option<TrimRange>,
+    /// Where the sound goes, while the stream plays it.
+    audio: Option<Arc<AudioSource>>,
+}
+
+impl PlaybackStream {
+    fn new(decoder: FrameSource, latest_frame: Arc<Mutex<Option<DecodedVideoFrame>>>) -> Self {
+        Self {
+            decoder,
+            latest_frame,
+            playing: false,
+            seq: 0,
+            seeks: 0,
+            pending_seek: None,
+            clock: PlaybackClock::default(),
+            queue: VecDeque::with_capacity(2),
+            loop_offset: 0.0,
+            prev_loop_offset: 0.0,
+            last_timeline: 0.0,
+            trim: None,
+            audio: None,
+        }
+    }
+
+    fn apply(&mut self, cmd: StreamCmd) {
+        match cmd {
+            StreamCmd::Play => {
+                self.playing = true;
+                // Resume with the queued frame shown right away.
+                self.clock.reset();
+                if let Some(source) = &self.audio {
+                    source.set_paused(false);
                 }
-                // Sleep until the next frame's scheduled display time,
-                // keeping playback at the correct rate without drift.
-                let now = Instant::now();
-                if next_frame_at > now {
-                    std::thread::sleep(next_frame_at - now);
+            }
+            StreamCmd::Pause => {
+                self.playing = false;
+                if let Some(source) = &self.audio {
+                    source.set_paused(true);
                 }
-                next_frame_at += frame_duration;
-                // If we've fallen more than one frame behind (e.g. after a pause),
-                // reset the schedule to avoid a catch-up burst.
-                if next_frame_at < Instant::now() {
-                    next_frame_at = Instant::now() + frame_duration;
+            }
+            StreamCmd::Seek(seconds) => {
+                self.pending_seek = Some(seconds);
+                self.seeks += 1;
+            }
+            StreamCmd::Trim(trim) => {
+                self.trim = trim;
+                // Playback outside the new range jumps into it rather than running on to
+                // the end of the video first. Animations are not trimmed.
+                if let (Some(trim), FrameSource::Video(_)) = (trim, &self.decoder) {
+                    let position = self
+                        .pending_seek
+                        .unwrap_or(self.last_timeline - self.loop_offset);
+                    let clamped = trim.clamp(position);
+                    if clamped != position {
+                        self.pending_seek = Some(clamped);
+                    }
                 }
+            }
+            StreamCmd::Audio(volume) => self.set_audio(volume),
+            StreamCmd::Rate(rate) => self.clock.set_rate(rate),
+        }
+    }
+
+    /// Starts playing the audio track at `volume`, or stops decoding it for None.
+    fn set_audio(&mut self, volume: Option<f32>) {
+        let Some(volume) = volume else {
+            // Dropping the source removes it from the mix.
+            self.audio = None;
+            if let Some(decoder) = self.decoder.video() {
+                decoder.audio = None;
+            }
+            return;
+        };
+        if self.audio.is_none() {
+            let mixer = Mixer::global();
+            let Some(decoder) = self.decoder.video() else {
+                return;
+            };
+            if !decoder.enable_audio(mixer.rate()) {
+                return;
+            }
+            let source = mixer.add_source();
+            source.set_paused(!self.playing);
+            self.audio = Some(source);
+        }
+        if let Some(source) = &self.audio {
+            source.set_volume(volume);
+        }
+    }
 
-                seq += 1;
-                let ci = rgba_frame_to_color_image(&rgba);
-                if let Ok(mut guard) = latest_frame.lock() {
-                    *guard = Some(DecodedVideoFrame { image: ci, seq });
+    /// Returns when the stream next needs its worker: now for a pending seek or to
+    /// fill the queue, the front frame's deadline while playing (or earlier, when the
+    /// audio output runs low), or None while idle.
+    fn next_wake(&mut self, now: Instant) -> Option<Instant> {
+        if self.pending_seek.is_some() || (self.playing && self.queue.len() < 2) {
+            return Some(now);
+        }
+        if !self.playing {
+            return None;
+        }
+        let timeline = self.queue.front()?.timeline;
+        if let Some((heard, at)) = self.audio.as_ref().and_then(|a| a.clock()) {
+            // With sound, frames follow the audio clock.
+            self.clock.sync(heard, at);
+        }
+        let due = self.clock.due(timeline, now);
+        Some(self.audio_wake(now).map_or(due, |wake| wake.min(due)))
+    }
+
+    /// Returns when the buffered audio falls below the low-water mark, if more can be
+    /// decoded.
+    fn audio_wake(&self, now: Instant) -> Option<Instant> {
+        let source = self.audio.as_ref()?;
+        if !self.decoder.can_read_audio() {
+            return None;
+        }
+        let spare = (source.buffered() - AUDIO_LOW_WATER_SECS).max(0.0);
+        Some(now + Duration::from_secs_f64(spare))
+    }
+
+    /// Does the work that is due (see `step_video`) and keeps the audio output
+    /// buffered. Returns false if the stream can no longer be decoded.
+    fn step(&mut self, now: Instant) -> bool {
+        let decodable = self.step_video(now);
+        self.fill_audio();
+        decodable
+    }
+
+    /// Performs a pending seek, decodes ahead, or publishes the front frame once it is
+    /// due. Returns false if the stream can no longer be decoded.
+    fn step_video(&mut self, now: Instant) -> bool {
+        if let Some(target) = self.pending_seek.take() {
+            self.queue.clear();
+            self.clock.reset();
+            self.loop_offset = 0.0;
+            // Audio queued for the old position must not be heard.
+            if let Some(source) = &self.audio {
+                source.clear();
+            }
+            if let Some(track) = self.decoder.video().and_then(|d| d.audio.as_mut()) {
+                track.chunks.clear();
+            }
+            // Published even while paused, so scrubbing shows the frame.
+            if let Some((image, position)) = self.decoder.seek(target) {
+                self.last_timeline = position;
+                let frame = QueuedFrame {
+                    image,
+                    position,
+                    index: self.decoder.frame_index(),
+                    timeline: position,
+                };
+                self.publish(frame, None);
+            }
+            return true;
+        }
+
+        if self.queue.len() < 2 {
+            let Some((image, position, looped)) = self.decoder.next_frame_looping(self.trim) else {
+                return false;
+            };
+            if looped {
+                self.prev_loop_offset = self.loop_offset;
+                // The last frame before the loop is shown for its duration (see `loop_gap`).
+                self.loop_offset =
+                    self.last_timeline + self.decoder.loop_gap().as_secs_f64() - position;
+            }
+            self.last_timeline = self.loop_offset + position;
+            self.queue.push_back(QueuedFrame {
+                image,
+                position,
+                index: self.decoder.frame_index(),
+                timeline: self.last_timeline,
+            });
+            return true;
+        }
+
+        // The stream may have been woken for audio before the front frame is due.
+        let front_due = self.clock.deadline(self.queue[0].timeline);
+        if front_due.is_some_and(|due| due > now) {
+            return true;
+        }
+        let next_due = self.clock.deadline(self.queue[1].timeline);
+        if let Some(frame) = self.queue.pop_front() {
+            self.publish(frame, next_due);
+        }
+        true
+    }
+
+    /// Reads ahead when the buffered audio runs low and hands decoded audio to the
+    /// output, on the playback timeline.
+    fn fill_audio(&mut self) {
+        let Some(source) = self.audio.clone() else {
+            return;
+        };
+        let Some(decoder) = self.decoder.video() else {
+            return;
+        };
+        let buffered = source.buffered();
+        if self.playing && buffered < AUDIO_LOW_WATER_SECS {
+            decoder.read_ahead_audio(AUDIO_BUFFER_SECS - buffered);
+        }
+        let generation = decoder.generation;
+        let Some(track) = decoder.audio.as_mut() else {
+            return;
+        };
+        for chunk in track.chunks.drain(..) {
+            // Audio decoded before the latest loop belongs to the previous pass.
+            let offset = if chunk.generation == generation {
+                self.loop_offset
+            } else {
+                self.prev_loop_offset
+            };
+            source.push(offset + chunk.position, &chunk.samples);
+        }
+    }
+
+    fn publish(&mut self, frame: QueuedFrame, next_due: Option<Instant>) {
+        self.seq += 1;
+        if let Ok(mut guard) = self.latest_frame.lock() {
+            *guard = Some(DecodedVideoFrame {
+                image: frame.image,
+                seq: self.seq,
+                position: frame.position,
+                index: frame.index,
+                seeks: self.seeks,
+                next_due,
+            });
+        }
+    }
+}
+
+/// Messages from handles to the worker owning their stream.
+enum WorkerMsg {
+    Open {
+        id: u64,
+        path: PathBuf,
+        latest_frame: Arc<Mutex<Option<DecodedVideoFrame>>>,
+        info: Arc<OnceLock<StreamInfo>>,
+    },
+    Cmd(u64, StreamCmd),
+    Close(u64),
+}
+
+struct PoolWorker {
+    tx: Sender<WorkerMsg>,
+    /// Number of streams assigned to the worker, for load balancing.
+    streams: Arc<AtomicUsize>,
+}
+
+/// The process-wide pool of decode workers, started on first use.
+struct DecodePool {
+    workers: Vec<PoolWorker>,
+    next_id: AtomicU64,
+}
+
+impl DecodePool {
+    fn global() -> &'static DecodePool {
+        static POOL: OnceLock<DecodePool> = OnceLock::new();
+        POOL.get_or_init(|| {
+            let workers = (0..VIDEO_DECODE_WORKERS.max(1))
+                .map(|index| {
+                    let (tx, rx) = std::sync::mpsc::channel();
+                    let streams = Arc::new(AtomicUsize::new(0));
+                    let worker_streams = Arc::clone(&streams);
+                    std::thread::Builder::new()
+                        .name(format!("video-decode-{index}"))
+                        .spawn(move || decode_worker(rx, worker_streams))
+                        .expect("failed to spawn video decode worker");
+                    PoolWorker { tx, streams }
+                })
+                .collect();
+            DecodePool {
+                workers,
+                next_id: AtomicU64::new(0),
+            }
+        })
+    }
+}
+
+/// Runs one decode worker: applies messages as they arrive and otherwise services
+/// the stream whose next deadline is nearest.
+fn decode_worker(rx: Receiver<WorkerMsg>, load: Arc<AtomicUsize>) {
+    let mut streams: HashMap<u64, PlaybackStream> = HashMap::new();
+
+    loop {
+        let now = Instant::now();
+        let next = streams
+            .iter_mut()
+            .filter_map(|(id, stream)| stream.next_wake(now).map(|at| (at, *id)))
+            .min();
+
+        // Idle workers block until a message arrives rather than spinning.
+        let msg = match next {
+            None => match rx.recv() {
+                Ok(msg) => Some(msg),
+                Err(_) => break,
+            },
+            Some((at, _)) => match rx.recv_timeout(at.saturating_duration_since(now)) {
+                Ok(msg) => Some(msg),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+        };
+
+        match msg {
+            Some(WorkerMsg::Open {
+                id,
+                path,
+                latest_frame,
+                info,
+            }) => match FrameSource::open(&path) {
+                Ok(decoder) => {
+                    let _ = info.set(decoder.info());
+                    streams.insert(id, PlaybackStream::new(decoder, latest_frame));
+                }
+                Err(e) => {
+                    log::error!("video_stream: {e}");
+                    load.fetch_sub(1, Ordering::Relaxed);
+                }
+            },
+            Some(WorkerMsg::Cmd(id, cmd)) => {
+                if let Some(stream) = streams.get_mut(&id) {
+                    stream.apply(cmd);
+                }
+            }
+            Some(WorkerMsg::Close(id)) => {
+                if streams.remove(&id).is_some() {
+                    load.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+            None => {
+                let Some((_, id)) = next else { continue };
+                let failed = streams
+                    .get_mut(&id)
+                    .is_some_and(|stream| !stream.step(Instant::now()));
+                if failed {
+                    log::error!("video_stream: no frames decoded, closing stream {id}");
+                    streams.remove(&id);
+                    load.fetch_sub(1, Ordering::Relaxed);
                 }
             }
         }
-        // EOF reached — loop back to reopen the file (seamless loop).
-        // If paused, the blocking recv at the top of 'outer will wait.
     }
 }
 
@@ -290,20 +1540,32 @@ fn scaled_output_dims(src_w: u32, src_h: u32) -> (u32, u32) {
     }
 }
 
-/// Probes a video file to determine the nominal frame duration.
-/// Returns None on any error; callers should substitute a default.
-fn probe_frame_duration(path: &Path) -> Option<Duration> {
-    let input = ffmpeg_next::format::input(path).ok()?;
-    let stream = input.streams().best(ffmpeg_next::media::Type::Video)?;
-    Some(frame_duration_from_stream(&stream))
+fn frame_duration_from_stream(stream: &ffmpeg_next::format::stream::Stream) -> Duration {
+    frame_rate_from_stream(stream).map_or(DEFAULT_FRAME_DURATION, |fps| {
+        Duration::from_secs_f64(1.0 / fps)
+    })
 }
 
-fn frame_duration_from_stream(stream: &ffmpeg_next::format::stream::Stream) -> Duration {
+fn frame_rate_from_stream(stream: &ffmpeg_next::format::stream::Stream) -> Option<f64> {
     let r = stream.avg_frame_rate();
-    if r.numerator() > 0 && r.denominator() > 0 {
-        Duration::from_secs_f64(r.denominator() as f64 / r.numerator() as f64)
+    (r.numerator() > 0 && r.denominator() > 0)
+        .then(|| r.numerator() as f64 / r.denominator() as f64)
+}
+
+/// Length in seconds from the stream duration (in `time_base` units), falling back to
+/// the container duration.
+fn duration_secs(
+    input: &ffmpeg_next::format::context::Input,
+    stream_duration: i64,
+    time_base: f64,
+) -> Option<f64> {
+    if stream_duration > 0 {
+        Some(stream_duration as f64 * time_base)
+    } else if input.duration() > 0 {
+        // WebM usually only records the container duration.
+        Some(input.duration() as f64 / f64::from(ffmpeg_next::ffi::AV_TIME_BASE))
     } else {
-        Duration::from_millis(42) // ~24 fps fallback
+        None
     }
 }
 
@@ -334,3 +1596,496 @@ fn rgba_frame_to_color_image(rgba: &ffmpeg_next::frame::Video) -> ColorImage {
         ColorImage::from_rgba_unmultiplied([width, height], &packed)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{AUDIO_SYNC_TOLERANCE_SECS, CLIP_MAX_FPS};
+
+    /// Writes an uncompressed YUV4MPEG2 clip of `frames` 16×16 frames at 25 fps whose
+    /// brightness increases with the frame index.
+    fn write_test_clip(path: &Path, frames: u8) {
+        let mut data = b"YUV4MPEG2 W16 H16 F25:1 Ip A1:1 C420jpeg\n".to_vec();
+        for index in 0..frames {
+            data.extend_from_slice(b"FRAME\n");
+            data.extend(std::iter::repeat_n(16 + index * 40, 16 * 16));
+            data.extend(std::iter::repeat_n(128, 2 * 8 * 8));
+        }
+        std::fs::write(path, data).unwrap();
+    }
+
+    /// Audio level (as i16) of the test clip's sound during video frame `index`.
+    fn test_audio_level(index: u8) -> i16 {
+        3000 * (i16::from(index) + 1)
+    }
+
+    /// Writes a NUT file with the frames of `write_test_clip` (uncompressed) and 8 kHz
+    /// stereo PCM sound whose level changes with each frame (`test_audio_level`).
+    fn write_test_clip_with_audio(path: &Path, frames: u8) {
+        use ff::format::{sample, Pixel, Sample};
+        use ffmpeg_next as ff;
+
+        let video_time_base = ff::Rational::new(1, 25);
+        let audio_time_base = ff::Rational::new(1, 8000);
+        let mut muxer = ff::format::output_as(path, "nut").unwrap();
+
+        let video_codec = ff::encoder::find(ff::codec::Id::RAWVIDEO).unwrap();
+        let mut video = ff::codec::context::Context::new_with_codec(video_codec)
+            .encoder()
+            .video()
+            .unwrap();
+        video.set_width(16);
+        video.set_height(16);
+        video.set_format(Pixel::YUV420P);
+        video.set_time_base(video_time_base);
+        let mut video = video.open_as(video_codec).unwrap();
+        let mut stream = muxer.add_stream(video_codec).unwrap();
+        stream.set_parameters(&video);
+        stream.set_time_base(video_time_base);
+
+        let audio_codec = ff::encoder::find(ff::codec::Id::PCM_S16LE).unwrap();
+        let mut audio = ff::codec::context::Context::new_with_codec(audio_codec)
+            .encoder()
+            .audio()
+            .unwrap();
+        audio.set_rate(8000);
+        audio.set_channel_layout(ff::ChannelLayout::STEREO);
+        audio.set_format(Sample::I16(sample::Type::Packed));
+        audio.set_time_base(audio_time_base);
+        let mut audio = audio.open_as(audio_codec).unwrap();
+        let mut stream = muxer.add_stream(audio_codec).unwrap();
+        stream.set_parameters(&audio);
+        stream.set_time_base(audio_time_base);
+        muxer.write_header().unwrap();
+
+        // Moves the packets an encoder has ready into the muxer.
+        fn mux(
+            encoder: &mut ff::encoder::Encoder,
+            muxer: &mut ff::format::context::Output,
+            stream: usize,
+            time_base: ff::Rational,
+        ) {
+            let stream_time_base = muxer.stream(stream).unwrap().time_base();
+            let mut packet = ff::Packet::empty();
+            while encoder.receive_packet(&mut packet).is_ok() {
+                packet.set_stream(stream);
+                packet.rescale_ts(time_base, stream_time_base);
+                packet.write_interleaved(muxer).unwrap();
+            }
+        }
+
+        // 40ms of sound per frame.
+        let samples_per_frame = 320;
+        for index in 0..frames {
+            let mut frame = ff::frame::Video::new(Pixel::YUV420P, 16, 16);
+            frame.data_mut(0).fill(16 + index * 40);
+            frame.data_mut(1).fill(128);
+            frame.data_mut(2).fill(128);
+            frame.set_pts(Some(i64::from(index)));
+            video.send_frame(&frame).unwrap();
+            mux(&mut video, &mut muxer, 0, video_time_base);
+
+            let mut sound = ff::frame::Audio::new(
+                Sample::I16(sample::Type::Packed),
+                samples_per_frame,
+                ff::ChannelLayout::STEREO,
+            );
+            sound.set_rate(8000);
+            let level = test_audio_level(index).to_ne_bytes();
+            for sample in sound.data_mut(0).chunks_exact_mut(2) {
+                sample.copy_from_slice(&level);
+            }
+            sound.set_pts(Some(i64::from(index) * samples_per_frame as i64));
+            audio.send_frame(&sound).unwrap();
+            mux(&mut audio, &mut muxer, 1, audio_time_base);
+        }
+        video.send_eof().unwrap();
+        mux(&mut video, &mut muxer, 0, video_time_base);
+        audio.send_eof().unwrap();
+        mux(&mut audio, &mut muxer, 1, audio_time_base);
+        muxer.write_trailer().unwrap();
+    }
+
+    /// Left and right sample at `position` seconds of audio starting at `front`.
+    fn sample_at(front: f64, samples: &[f32], rate: u32, position: f64) -> (f32, f32) {
+        let index = ((position - front) * f64::from(rate)).round() as usize * CHANNELS;
+        (samples[index], samples[index + 1])
+    }
+
+    #[test]
+    fn test_audio_track_resamples_to_stereo() {
+        ffmpeg_next::init().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_sound_{}.nut", uuid::Uuid::new_v4()));
+        write_test_clip_with_audio(&path, 5);
+
+        let rate = 16_000;
+        let mut stream = StreamDecoder::open(&path).unwrap();
+        assert!(stream.enable_audio(rate));
+        stream.read_ahead_audio(1.0);
+        let track = stream.audio.as_ref().unwrap();
+
+        // Chunks follow each other on the timeline, starting with the video; the
+        // resampler holds back a few samples at first.
+        let front = track.chunks.front().unwrap().position;
+        assert!(front.abs() < 0.001);
+        let mut end = front;
+        let mut samples = Vec::new();
+        for chunk in &track.chunks {
+            assert!((chunk.position - end).abs() <= AUDIO_SYNC_TOLERANCE_SECS);
+            end = chunk.position + (chunk.samples.len() / CHANNELS) as f64 / f64::from(rate);
+            samples.extend_from_slice(&chunk.samples);
+        }
+        // 200ms of sound, less what the resampler still holds back.
+        assert!(end > 0.19 && end <= 0.2 + 0.001);
+
+        // The level of each frame survives resampling on both channels.
+        for index in 0..5 {
+            let expected = f32::from(test_audio_level(index)) / 32768.0;
+            let middle = f64::from(index) * 0.04 + 0.02;
+            let (left, right) = sample_at(front, &samples, rate, middle);
+            assert!((left - expected).abs() < 0.01, "{left} != {expected}");
+            assert!((right - expected).abs() < 0.01, "{right} != {expected}");
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stream_sound_follows_timeline_across_loops() {
+        ffmpeg_next::init().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_sound_{}.nut", uuid::Uuid::new_v4()));
+        write_test_clip_with_audio(&path, 5);
+
+        // A mixer without a sink, so nothing drains the samples before they are checked.
+        let rate = 16_000;
+        let source = Mixer::new(rate).add_source();
+        let mut decoder = StreamDecoder::open(&path).unwrap();
+        assert!(decoder.enable_audio(rate));
+        let mut stream =
+            PlaybackStream::new(FrameSource::Video(decoder), Arc::new(Mutex::new(None)));
+        stream.audio = Some(Arc::clone(&source));
+        stream.playing = true;
+
+        // Play into the third pass of the 200ms clip. Without a clock anchor every step
+        // decodes or publishes a frame right away.
+        for _ in 0..200 {
+            if stream.last_timeline >= 0.45 {
+                break;
+            }
+            assert!(stream.step(Instant::now()));
+        }
+        assert!(stream.last_timeline >= 0.45);
+
+        // Every pass is heard right after the previous one, in step with its frames.
+        let (front, samples) = source.take_queued();
+        assert!(front.abs() < 0.001);
+        for pass in 0..2 {
+            for index in 0..5 {
+                let expected = f32::from(test_audio_level(index)) / 32768.0;
+                let middle = f64::from(pass) * 0.2 + f64::from(index) * 0.04 + 0.02;
+                let (left, right) = sample_at(front, &samples, rate, middle);
+                assert!(
+                    (left - expected).abs() < 0.01,
+                    "{middle}s: {left} != {expected}"
+                );
+                assert_eq!(left, right);
+            }
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_decode_video_segment() {
+        ffmpeg_next::init().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_segment_{}.y4m", uuid::Uuid::new_v4()));
+        write_test_clip(&path, 6);
+        fn seconds(frames: &[AnimationFrame]) -> Vec<f64> {
+            frames.iter().map(|f| f.duration.as_secs_f64()).collect()
+        }
+        fn assert_close(actual: Vec<f64>, expected: &[f64]) {
+            assert_eq!(actual.len(), expected.len(), "{actual:?}");
+            for (a, e) in actual.iter().zip(expected) {
+                assert!((a - e).abs() < 1e-6, "{actual:?} != {expected:?}");
+            }
+        }
+
+        // Frames are 40ms apart; each lasts until the next one, the last is cut at the end.
+        let frames = decode_video_segment(&path, 0.0, 0.15, 100.0).unwrap();
+        assert_close(seconds(&frames), &[0.04, 0.04, 0.04, 0.03]);
+        assert_eq!(frames[0].image.size, [16, 16]);
+
+        // At CLIP_MAX_FPS every other frame is dropped, starting from the first frame at or
+        // after the start; the last one lasts 1/CLIP_MAX_FPS unless cut at the end.
+        let frames = decode_video_segment(&path, 0.01, 0.19, CLIP_MAX_FPS).unwrap();
+        assert_close(seconds(&frames), &[0.08, 1.0 / CLIP_MAX_FPS]);
+        let frames = decode_video_segment(&path, 0.01, 0.15, CLIP_MAX_FPS).unwrap();
+        assert_close(seconds(&frames), &[0.08, 0.03]);
+        // The second and the fourth frame are kept.
+        let brightness = |frame: &AnimationFrame| frame.image.pixels[0].r();
+        assert!(brightness(&frames[0]) < brightness(&frames[1]));
+        let expected = load_video_frame_at(&path, 0.12).unwrap();
+        assert_eq!(frames[1].image.pixels, expected.pixels);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stream_loops_without_reopening() {
+        ffmpeg_next::init().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_loop_{}.y4m", uuid::Uuid::new_v4()));
+        write_test_clip(&path, 5);
+
+        let mut stream = StreamDecoder::open(&path).unwrap();
+        let (first, _, _) = stream.next_frame_looping(None).unwrap();
+        let mut frames_per_loop = Vec::new();
+        let mut count = 1;
+        let mut last_position = 0.0;
+        while frames_per_loop.len() < 3 {
+            let (image, position, looped) = stream.next_frame_looping(None).unwrap();
+            if looped {
+                frames_per_loop.push(count);
+                count = 0;
+                // Every loop starts again from the first frame.
+                assert!(position < 0.001);
+                assert_eq!(image.pixels, first.pixels);
+            } else {
+                assert!(position > last_position);
+            }
+            assert_eq!(image.size, [16, 16]);
+            last_position = position;
+            count += 1;
+        }
+        assert_eq!(frames_per_loop, vec![5, 5, 5]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trimmed_stream_loops_segment() {
+        ffmpeg_next::init().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_trim_{}.y4m", uuid::Uuid::new_v4()));
+        write_test_clip(&path, 5);
+
+        // Frames are 40ms apart; the segment holds the second and third frame.
+        let trim = TrimRange {
+            start: 0.03,
+            end: Some(0.1),
+        };
+        let mut stream = StreamDecoder::open(&path).unwrap();
+        let (_, start) = stream.seek(trim.start).unwrap();
+        assert!((start - 0.04).abs() < 0.001);
+        let positions: Vec<(f64, bool)> = (0..5)
+            .map(|_| {
+                let (_, position, looped) = stream.next_frame_looping(Some(trim)).unwrap();
+                ((position * 100.0).round() / 100.0, looped)
+            })
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (0.08, false),
+                (0.04, true),
+                (0.08, false),
+                (0.04, true),
+                (0.08, false)
+            ]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trim_moves_playback_into_range() {
+        ffmpeg_next::init().unwrap();
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_trim_{}.y4m", uuid::Uuid::new_v4()));
+        write_test_clip(&path, 5);
+        let trim = |start, end| StreamCmd::Trim(Some(TrimRange { start, end }));
+
+        let latest_frame = Arc::new(Mutex::new(None));
+        let decoder = FrameSource::Video(StreamDecoder::open(&path).unwrap());
+        let mut stream = PlaybackStream::new(decoder, Arc::clone(&latest_frame));
+        stream.playing = true;
+        // Decodes the first two frames.
+        assert!(stream.step(Instant::now()));
+        assert!(stream.step(Instant::now()));
+        assert!((stream.last_timeline - 0.04).abs() < 0.001);
+
+        // A range around the current position changes nothing.
+        stream.apply(trim(0.0, Some(0.15)));
+        assert_eq!(stream.pending_seek, None);
+
+        // Playback before the new in point continues from it...
+        stream.apply(trim(0.1, None));
+        assert_eq!(stream.pending_seek, Some(0.1));
+        assert!(stream.step(Instant::now()));
+        let position = latest_frame.lock().unwrap().as_ref().unwrap().position;
+        assert!((position - 0.12).abs() < 0.001);
+
+        // ...and playback past the new out point from there.
+        stream.apply(trim(0.0, Some(0.06)));
+        assert_eq!(stream.pending_seek, Some(0.06));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Writes a 4×4 GIF with one frame per delay (in milliseconds), brighter each frame.
+    fn write_test_gif(path: &Path, delays: &[u32]) {
+        use image::codecs::gif::GifEncoder;
+        let mut gif = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut gif);
+            for (index, delay) in delays.iter().enumerate() {
+                let shade = 40 * index as u8;
+                let image = image::RgbaImage::from_pixel(4, 4, image::Rgba([shade, 0, 0, 255]));
+                let delay = image::Delay::from_numer_denom_ms(*delay, 1);
+                encoder
+                    .encode_frame(image::Frame::from_parts(image, 0, 0, delay))
+                    .unwrap();
+            }
+        }
+        std::fs::write(path, gif).unwrap();
+    }
+
+    #[test]
+    fn test_animation_source_loops() {
+        let path =
+            std::env::temp_dir().join(format!("ma_blocks_anim_{}.gif", uuid::Uuid::new_v4()));
+        write_test_gif(&path, &[100, 200, 300]);
+
+        let mut source = FrameSource::open(&path).unwrap();
+        assert_eq!(source.info().frame_duration.as_millis(), 100);
+        let frames: Vec<(u8, f64, bool, usize)> = (0..5)
+            .map(|_| {
+                let (image, position, looped) = source.next_frame_looping(None).unwrap();
+                let position = (position * 10.0).round() / 10.0;
+                (image.pixels[0].r(), position, looped, source.frame_index())
+            })
+            .collect();
+        assert_eq!(
+            frames,
+            vec![
+                (0, 0.0, false, 0),
+                (40, 0.1, false, 1),
+                (80, 0.3, false, 2),
+                (0, 0.0, true, 0),
+                (40, 0.1, false, 1),
+            ]
+        );
+        // The last frame is shown for its own duration before the loop.
+        assert_eq!(source.loop_gap().as_millis(), 300);
+
+        let (image, position) = source.seek(0.25).unwrap();
+        assert_eq!(image.pixels[0].r(), 40);
+        assert!((position - 0.1).abs() < 0.001);
+        assert_eq!(source.frame_index(), 1);
+
+        // A single frame is shown again on every loop.
+        write_test_gif(&path, &[50]);
+        let mut source = FrameSource::open(&path).unwrap();
+        for looped in [false, true, true] {
+            let (image, position, did_loop) = source.next_frame_looping(None).unwrap();
+            assert_eq!((image.size, position, did_loop), ([4, 4], 0.0, looped));
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_trim_range_clamp() {
+        let trim = TrimRange {
+            start: 2.0,
+            end: Some(5.0),
+        };
+        assert_eq!(trim.clamp(0.0), 2.0);
+        assert_eq!(trim.clamp(3.5), 3.5);
+        assert_eq!(trim.clamp(9.0), 5.0);
+        let open_ended = TrimRange {
+            start: 2.0,
+            end: None,
+        };
+        assert_eq!(open_ended.clamp(9.0), 9.0);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0.0), "0:00");
+        assert_eq!(format_timestamp(9.99), "0:09");
+        assert_eq!(format_timestamp(75.0), "1:15");
+        assert_eq!(format_timestamp(3725.0), "1:02:05");
+        assert_eq!(format_timestamp(-1.0), "0:00");
+    }
+
+    #[test]
+    fn test_playback_clock_follows_timestamps() {
+        let start = Instant::now();
+        let mut clock = PlaybackClock::default();
+        assert_eq!(clock.due(1.0, start), start);
+
+        // Variable gaps between timestamps map to the same gaps in wall-clock time.
+        assert_eq!(clock.due(1.04, start), start + Duration::from_millis(40));
+        assert_eq!(
+            clock.deadline(1.5),
+            Some(start + Duration::from_millis(500))
+        );
+        assert_eq!(clock.deadline(0.5), None);
+
+        // A frame that is slightly late keeps its schedule; a stalled one restarts it.
+        let slightly_late = start + Duration::from_millis(90);
+        assert_eq!(
+            clock.due(1.05, slightly_late),
+            start + Duration::from_millis(50)
+        );
+        let stalled = start + Duration::from_secs(2);
+        assert_eq!(clock.due(1.1, stalled), stalled);
+        assert_eq!(
+            clock.deadline(1.2),
+            Some(stalled + Duration::from_millis(100))
+        );
+    }
+
+    #[test]
+    fn test_playback_clock_follows_audio() {
+        let start = Instant::now();
+        let mut clock = PlaybackClock::default();
+        assert_eq!(clock.due(2.0, start), start);
+
+        // The audio output reports 2.0 was heard 30ms late: frames shift with it.
+        let heard_at = start + Duration::from_millis(30);
+        clock.sync(2.0, heard_at);
+        assert_eq!(
+            clock.deadline(2.04),
+            Some(heard_at + Duration::from_millis(40))
+        );
+        assert_eq!(
+            clock.due(2.04, heard_at),
+            heard_at + Duration::from_millis(40)
+        );
+    }
+
+    #[test]
+    fn test_playback_clock_rate() {
+        let start = Instant::now();
+        let mut clock = PlaybackClock::default();
+        clock.set_rate(2.0);
+        assert_eq!(clock.due(1.0, start), start);
+        // At double speed, half a second of video takes 250ms.
+        assert_eq!(
+            clock.deadline(1.5),
+            Some(start + Duration::from_millis(250))
+        );
+
+        // Changing the rate reschedules from the next frame on.
+        clock.set_rate(0.5);
+        assert_eq!(clock.deadline(1.5), None);
+        let now = start + Duration::from_millis(250);
+        assert_eq!(clock.due(1.5, now), now);
+        assert_eq!(clock.deadline(2.0), Some(now + Duration::from_secs(1)));
+    }
+}
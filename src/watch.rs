@@ -0,0 +1,262 @@
+//! Watched folders: directories bound to the canvas or to a Box block.
+//!
+//! Files created in a watched folder (or moved into it) are reported once their size has
+//! stopped changing for `WATCH_SETTLE_MILLIS`, so the background loader never sees a
+//! half-written image. Deleted (or moved away) files are reported immediately so their
+//! blocks can be flagged as missing. Only files with a supported extension are reported.
+
+use crate::constants::WATCH_SETTLE_MILLIS;
+use crate::image_loader;
+use crate::session::WatchedFolder;
+use eframe::egui;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// A change in one of the watched folders.
+#[derive(Debug, PartialEq)]
+pub enum WatchEvent {
+    /// A new file is complete; `target` is the Box it should be added to, if any.
+    Created {
+        path: PathBuf,
+        target: Option<Uuid>,
+    },
+    Removed(PathBuf),
+}
+
+/// Watches the session's folders and turns file system notifications into `WatchEvent`s.
+pub struct FolderWatcher {
+    /// None when the platform watcher could not be created; folders are still kept so
+    /// they persist with the session.
+    watcher: Option<RecommendedWatcher>,
+    rx: Receiver<notify::Result<notify::Event>>,
+    folders: Vec<WatchedFolder>,
+    /// New files waiting for their size to settle: path → (last size, last change).
+    settling: HashMap<PathBuf, (u64, Instant)>,
+}
+
+impl FolderWatcher {
+    /// Creates a watcher that repaints `ctx` whenever a notification arrives.
+    pub fn new(ctx: &egui::Context) -> Self {
+        let (tx, rx) = channel();
+        let ctx = ctx.clone();
+        let watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+            ctx.request_repaint();
+        })
+        .map_err(|err| log::error!("Failed to start folder watcher: {err}"))
+        .ok();
+
+        Self {
+            watcher,
+            rx,
+            folders: Vec::new(),
+            settling: HashMap::new(),
+        }
+    }
+
+    pub fn folders(&self) -> &[WatchedFolder] {
+        &self.folders
+    }
+
+    /// Replaces all watched folders, e.g. when a session is loaded.
+    pub fn set_folders(&mut self, folders: Vec<WatchedFolder>) {
+        for folder in std::mem::take(&mut self.folders) {
+            self.unwatch(&folder);
+        }
+        self.settling.clear();
+        for folder in folders {
+            if let Err(err) = self.watch(&folder) {
+                log::warn!("{err}");
+            }
+            self.folders.push(folder);
+        }
+    }
+
+    /// Starts watching a folder. The path is stored canonicalized.
+    pub fn add(&mut self, path: &Path, target: Option<Uuid>) -> Result<WatchedFolder, String> {
+        let path = path
+            .canonicalize()
+            .map_err(|err| format!("Cannot watch {}: {err}", path.display()))?;
+        let folder = WatchedFolder {
+            path: path.to_string_lossy().into_owned(),
+            target,
+        };
+        if self.folders.iter().any(|f| f.path == folder.path) {
+            return Err(format!("{} is already watched", path.display()));
+        }
+        self.watch(&folder)?;
+        self.folders.push(folder.clone());
+        Ok(folder)
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.folders.len() {
+            let folder = self.folders.remove(index);
+            self.unwatch(&folder);
+            let folders = &self.folders;
+            self.settling
+                .retain(|path, _| folders.iter().any(|f| path.starts_with(&f.path)));
+        }
+    }
+
+    fn watch(&mut self, folder: &WatchedFolder) -> Result<(), String> {
+        let Some(watcher) = self.watcher.as_mut() else {
+            return Err("Folder watching is unavailable".to_string());
+        };
+        watcher
+            .watch(Path::new(&folder.path), RecursiveMode::Recursive)
+            .map_err(|err| format!("Cannot watch {}: {err}", folder.path))
+    }
+
+    fn unwatch(&mut self, folder: &WatchedFolder) {
+        if let Some(watcher) = self.watcher.as_mut() {
+            let _ = watcher.unwatch(Path::new(&folder.path));
+        }
+    }
+
+    /// Drains pending notifications and returns the resulting events. Should be called
+    /// every frame; repaints are requested while files are still settling.
+    pub fn poll(&mut self, ctx: &egui::Context) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        let now = Instant::now();
+
+        while let Ok(result) = self.rx.try_recv() {
+            let event = match result {
+                Ok(event) => event,
+                Err(err) => {
+                    log::warn!("Folder watcher error: {err}");
+                    continue;
+                }
+            };
+            for (path, created) in classify(&event) {
+                let watched = self.folder_for(&path).is_some();
+                if !watched || !image_loader::has_supported_extension(&path) {
+                    continue;
+                }
+                if created {
+                    self.settling.entry(path).or_insert((u64::MAX, now));
+                } else if self.settling.remove(&path).is_none() {
+                    events.push(WatchEvent::Removed(path));
+                }
+            }
+        }
+
+        let settle = Duration::from_millis(WATCH_SETTLE_MILLIS);
+        let mut settled = Vec::new();
+        self.settling.retain(|path, (size, since)| {
+            let Ok(metadata) = std::fs::metadata(path) else {
+                return false;
+            };
+            if metadata.len() != *size {
+                *size = metadata.len();
+                *since = now;
+                true
+            } else if now.duration_since(*since) >= settle {
+                settled.push(path.clone());
+                false
+            } else {
+                true
+            }
+        });
+        if !self.settling.is_empty() {
+            ctx.request_repaint_after(settle);
+        }
+
+        settled.sort();
+        events.extend(settled.into_iter().map(|path| WatchEvent::Created {
+            target: self.folder_for(&path).and_then(|f| f.target),
+            path,
+        }));
+        events
+    }
+
+    /// Returns the innermost watched folder containing `path`.
+    fn folder_for(&self, path: &Path) -> Option<&WatchedFolder> {
+        self.folders
+            .iter()
+            .filter(|f| path.starts_with(&f.path))
+            .max_by_key(|f| f.path.len())
+    }
+}
+
+/// Splits a notification into (path, created) pairs; renames count as a removal of the
+/// old path and a creation of the new one.
+fn classify(event: &notify::Event) -> Vec<(PathBuf, bool)> {
+    let all = |created: bool| event.paths.iter().map(|p| (p.clone(), created)).collect();
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => all(true),
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => all(false),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => event
+            .paths
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.clone(), i > 0))
+            .collect(),
+        // Some backends do not say which side of a rename a path is on.
+        EventKind::Modify(ModifyKind::Name(_)) => event
+            .paths
+            .iter()
+            .map(|p| (p.clone(), p.exists()))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Polls until events arrive, so the test does not depend on how fast the platform
+    /// delivers notifications. Returns no events after 10 seconds.
+    fn poll_until_events(watcher: &mut FolderWatcher, ctx: &egui::Context) -> Vec<WatchEvent> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            let events = watcher.poll(ctx);
+            if !events.is_empty() {
+                return events;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Vec::new()
+    }
+
+    #[test]
+    fn test_new_files_are_reported_once_settled() {
+        let root = std::env::temp_dir().join(format!("ma_blocks_watch_{}", Uuid::new_v4()));
+        fs::create_dir_all(&root).unwrap();
+        let ctx = egui::Context::default();
+        let mut watcher = FolderWatcher::new(&ctx);
+        let box_id = Uuid::new_v4();
+        let folder = watcher.add(&root, Some(box_id)).unwrap();
+        let root = PathBuf::from(folder.path);
+
+        let image = root.join("new.png");
+        let notes = root.join("notes.txt");
+        let written = Instant::now();
+        fs::write(&image, b"partial").unwrap();
+        fs::write(&notes, b"ignored").unwrap();
+        assert_eq!(
+            poll_until_events(&mut watcher, &ctx),
+            vec![WatchEvent::Created {
+                path: image.clone(),
+                target: Some(box_id),
+            }]
+        );
+        // Not reported before its size stayed unchanged for the settle time.
+        assert!(written.elapsed() >= Duration::from_millis(WATCH_SETTLE_MILLIS));
+
+        fs::remove_file(&image).unwrap();
+        assert_eq!(
+            poll_until_events(&mut watcher, &ctx),
+            vec![WatchEvent::Removed(image)]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}